@@ -0,0 +1,70 @@
+// Tracks the speed of the dispatch loop in `VM::run`:
+//
+//     cargo bench --bench dispatch
+//
+// Lox has no functions or loops yet, so fib and the counting loop are unrolled
+// into straight-line code. Each script is compiled once and the resulting
+// chunk is executed repeatedly, so the timings are dominated by dispatch.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use rlox::chunk::Chunk;
+use rlox::compiler::Compiler;
+use rlox::vm::VM;
+
+const RUNS: u32 = 20_000;
+
+fn fib_source() -> String {
+    let mut source = String::from("var a = 0;\nvar b = 1;\n");
+    for _ in 0..70 {
+        source.push_str("{ var t = a + b; a = b; b = t; }\n");
+    }
+    source
+}
+
+fn loop_source() -> String {
+    // number constants aren't deduplicated, so keep the step in a global
+    let mut source = String::from("var one = 1;\nvar i = 0;\nvar sum = 0;\n");
+    for _ in 0..200 {
+        source.push_str("i = i + one;\nsum = sum + i * i - i / one;\n");
+    }
+    source
+}
+
+fn concat_source() -> String {
+    let mut source = String::from("var s = \"\";\n");
+    for _ in 0..200 {
+        source.push_str("s = s + \"ab\";\n");
+    }
+    source
+}
+
+fn bench(name: &str, source: &str) {
+    let mut chunk = Chunk::new();
+    if Compiler::new(source, &mut chunk).compile().is_err() {
+        panic!("benchmark script {name} failed to compile");
+    }
+
+    let mut vm = VM::new();
+    for _ in 0..RUNS / 10 {
+        let _ = vm.interpret_chunk(&chunk);
+    }
+
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        let _ = black_box(vm.interpret_chunk(black_box(&chunk)));
+    }
+    let elapsed = start.elapsed();
+
+    println!("{name:<16} {:>6} bytes {:>12.2} ns/run {:>8.2} ns/byte",
+             chunk.code().len(),
+             elapsed.as_nanos() as f64 / RUNS as f64,
+             elapsed.as_nanos() as f64 / RUNS as f64 / chunk.code().len() as f64);
+}
+
+fn main() {
+    bench("fib (unrolled)", &fib_source());
+    bench("loop (unrolled)", &loop_source());
+    bench("string concat", &concat_source());
+}
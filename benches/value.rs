@@ -0,0 +1,79 @@
+// Compares the two `Value` representations. Run once per representation:
+//
+//     cargo bench --bench value
+//     cargo bench --bench value --features nan-boxing
+//
+// and compare the ns/iter columns.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use rlox::value::{arithmetic, Arith, Value};
+use rlox::vm::VM;
+
+const ITERATIONS: u32 = 1_000_000;
+
+fn bench<F: FnMut()>(name: &str, iterations: u32, mut f: F) {
+    // warm up
+    for _ in 0..iterations / 10 {
+        f();
+    }
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let elapsed = start.elapsed();
+
+    println!("{name:<24} {:>10.2} ns/iter",
+             elapsed.as_nanos() as f64 / iterations as f64);
+}
+
+fn main() {
+    let repr = if cfg!(feature = "nan-boxing") { "nan-boxing" } else { "tagged enum" };
+    println!("representation: {repr}, size_of::<Value>() = {}",
+             std::mem::size_of::<Value>());
+
+    let mut stack: Vec<Value> = Vec::with_capacity(256);
+    bench("push/pop number", ITERATIONS, || {
+        stack.push(black_box(Value::number(1.5)));
+        stack.push(black_box(Value::number(2.5)));
+        let (b, a) = (stack.pop().unwrap(), stack.pop().unwrap());
+        stack.push(arithmetic(Arith::Add, &a, &b).unwrap());
+        black_box(stack.pop());
+    });
+
+    bench("push/pop int", ITERATIONS, || {
+        stack.push(black_box(Value::int(1)));
+        stack.push(black_box(Value::int(2)));
+        let (b, a) = (stack.pop().unwrap(), stack.pop().unwrap());
+        stack.push(arithmetic(Arith::Add, &a, &b).unwrap());
+        black_box(stack.pop());
+    });
+
+    let s = Value::string("a moderately long interned string constant");
+    bench("clone/drop string", ITERATIONS, || {
+        stack.push(black_box(s.clone()));
+        black_box(stack.pop());
+    });
+
+    let (a, b) = (Value::string("hello"), Value::string("hello"));
+    bench("equal strings", ITERATIONS, || {
+        black_box(black_box(&a) == black_box(&b));
+    });
+
+    let (t, n) = (Value::boolean(true), Value::nil());
+    bench("is_falsey", ITERATIONS, || {
+        black_box(black_box(&t).is_falsey() || black_box(&n).is_falsey());
+    });
+
+    // number constants aren't deduplicated, so stay under the 256 constant limit
+    let mut source = String::from("var a = 0;\nvar s = \"\";\n");
+    for _ in 0..50 {
+        source.push_str("a = a + 1 * 2 - 3 / 4;\ns = s + \"x\";\n");
+    }
+    let mut vm = VM::new();
+    bench("interpret script", 10_000, || {
+        let _ = black_box(vm.interpret(black_box(&source)));
+    });
+}
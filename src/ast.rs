@@ -0,0 +1,180 @@
+use crate::token::Token;
+use crate::value::BigInt;
+
+/// Where a node came from: the token that identifies it, i.e. the operator of
+/// a unary or binary expression, the name of a variable, or the keyword or
+/// first token of a statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub line: usize,
+    /// 0-based column, in characters.
+    pub column: usize,
+    /// Character offset in the source.
+    pub start: usize,
+    pub len: usize,
+}
+
+impl Span {
+    pub fn of(token: &Token) -> Self {
+        Self {
+            line: token.line,
+            column: token.column,
+            start: token.start,
+            len: token.len,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identifier {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+    /// `~`
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    /// `~/`
+    FloorDiv,
+    Mod,
+    Pow,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    /// A float literal, one with a point or an exponent.
+    Number(f64),
+    Int(i64),
+    /// An int literal too large for 64 bits.
+    BigInt(BigInt),
+    String(String),
+    Bool(bool),
+    Nil,
+    Variable(Identifier),
+    Assign {
+        name: Identifier,
+        value: Box<Expr>,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    Grouping(Box<Expr>),
+    /// An interpolated string: its literal parts and embedded expressions in
+    /// order, the literal parts being `String`s. Empty literal parts are
+    /// left out.
+    Interpolation(Vec<Expr>),
+    List(Vec<Expr>),
+    /// Key and value pairs, in source order.
+    Map(Vec<(Expr, Expr)>),
+    /// `object[index]`; the span is the `[`.
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+    },
+    SetIndex {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
+    /// `target op= value`, and `++`/`--` as adding or subtracting 1. The
+    /// target is a `Variable` or an `Index`, and is evaluated once. Gives the
+    /// new value, or the old one for a postfix `++`/`--`.
+    Update {
+        target: Box<Expr>,
+        op: BinaryOp,
+        value: Box<Expr>,
+        postfix: bool,
+    },
+    /// `condition ? then_branch : else_branch`; only the chosen branch is
+    /// evaluated.
+    Conditional {
+        condition: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+    },
+    /// `left ?? right`, which evaluates `right` only if `left` is nil.
+    Coalesce {
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    /// The span is the closing parenthesis, as the call happens there.
+    Call {
+        callee: Box<Expr>,
+        arguments: Vec<Expr>,
+    },
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    Expression(Expr),
+    Print(Expr),
+    Var {
+        name: Identifier,
+        initializer: Option<Expr>,
+    },
+    Block {
+        body: Vec<Stmt>,
+        /// The closing brace, where the block's locals are popped.
+        close: Span,
+    },
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+/// A whole script. Statements that failed to parse are left out.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+    pub statements: Vec<Stmt>,
+    /// The end of the source, where the final return goes.
+    pub end: Span,
+}
@@ -1,6 +1,8 @@
 use crate::value::*;
+use crate::disassembler::*;
 
-#[derive(Debug)]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OpCode {
     Return = 0,
     Constant = 1,
@@ -23,13 +25,142 @@ pub enum OpCode {
     Not = 18,
     GetLocal = 19,
     SetLocal = 20,
+    Call = 21,
+    BuildList = 22,
+    GetIndex = 23,
+    SetIndex = 24,
+    Map = 25,
+    MapEntry = 26,
+    ToString = 27,
+    FloorDiv = 28,
+    Mod = 29,
+    Pow = 30,
+    BitAnd = 31,
+    BitOr = 32,
+    BitXor = 33,
+    BitNot = 34,
+    ShiftLeft = 35,
+    ShiftRight = 36,
+    Dup = 37,
+    Dup2 = 38,
+    Bury = 39,
+    Jump = 40,
+    JumpIfFalse = 41,
+    JumpIfNotNil = 42,
 }
 
-#[derive(Debug)]
+impl OpCode {
+    // keep in sync with the last variant above
+    const MAX: u8 = OpCode::JumpIfNotNil as u8;
+
+    /// Converts a byte that has already been checked by `verifier::verify`.
+    ///
+    /// # Safety
+    /// `byte` must be the discriminant of an `OpCode`.
+    #[inline(always)]
+    pub unsafe fn from_unchecked(byte: u8) -> Self {
+        debug_assert!(byte <= Self::MAX);
+        std::mem::transmute::<u8, OpCode>(byte)
+    }
+
+    /// Mnemonic used by the disassembler.
+    pub fn name(self) -> &'static str {
+        match self {
+            OpCode::Return       => "OP_RETURN",
+            OpCode::Constant     => "OP_CONSTANT",
+            OpCode::Nil          => "OP_NIL",
+            OpCode::True         => "OP_TRUE",
+            OpCode::False        => "OP_FALSE",
+            OpCode::Pop          => "OP_POP",
+            OpCode::GetGlobal    => "OP_GET_GLOBAL",
+            OpCode::DefineGlobal => "OP_DEFINE_GLOBAL",
+            OpCode::SetGlobal    => "OP_SET_GLOBAL",
+            OpCode::Equal        => "OP_EQUAL",
+            OpCode::Greater      => "OP_GREATER",
+            OpCode::Less         => "OP_LESS",
+            OpCode::Negate       => "OP_NEGATE",
+            OpCode::Print        => "OP_PRINT",
+            OpCode::Add          => "OP_ADD",
+            OpCode::Sub          => "OP_SUBTRACT",
+            OpCode::Mul          => "OP_MULTIPLY",
+            OpCode::Div          => "OP_DIVIDE",
+            OpCode::Not          => "OP_NOT",
+            OpCode::GetLocal     => "OP_GET_LOCAL",
+            OpCode::SetLocal     => "OP_SET_LOCAL",
+            OpCode::Call         => "OP_CALL",
+            OpCode::BuildList    => "OP_BUILD_LIST",
+            OpCode::GetIndex     => "OP_GET_INDEX",
+            OpCode::SetIndex     => "OP_SET_INDEX",
+            OpCode::Map          => "OP_MAP",
+            OpCode::MapEntry     => "OP_MAP_ENTRY",
+            OpCode::ToString     => "OP_TO_STRING",
+            OpCode::FloorDiv     => "OP_FLOOR_DIVIDE",
+            OpCode::Mod          => "OP_MODULO",
+            OpCode::Pow          => "OP_POWER",
+            OpCode::BitAnd       => "OP_BIT_AND",
+            OpCode::BitOr        => "OP_BIT_OR",
+            OpCode::BitXor       => "OP_BIT_XOR",
+            OpCode::BitNot       => "OP_BIT_NOT",
+            OpCode::ShiftLeft    => "OP_SHIFT_LEFT",
+            OpCode::ShiftRight   => "OP_SHIFT_RIGHT",
+            OpCode::Dup          => "OP_DUP",
+            OpCode::Dup2         => "OP_DUP2",
+            OpCode::Bury         => "OP_BURY",
+            OpCode::Jump         => "OP_JUMP",
+            OpCode::JumpIfFalse  => "OP_JUMP_IF_FALSE",
+            OpCode::JumpIfNotNil => "OP_JUMP_IF_NOT_NIL",
+        }
+    }
+
+    /// Number of operand bytes following the opcode.
+    pub fn operand_len(self) -> usize {
+        match self {
+            OpCode::Constant
+                | OpCode::GetGlobal
+                | OpCode::DefineGlobal
+                | OpCode::SetGlobal
+                | OpCode::GetLocal
+                | OpCode::SetLocal
+                | OpCode::Call
+                | OpCode::BuildList
+                | OpCode::Bury => 1,
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::JumpIfNotNil => 2,
+            _ => 0,
+        }
+    }
+
+    /// Whether the operand is a 16-bit big-endian distance to jump forward,
+    /// counted from the end of the instruction.
+    pub fn is_jump(self) -> bool {
+        matches!(self, OpCode::Jump | OpCode::JumpIfFalse | OpCode::JumpIfNotNil)
+    }
+
+    /// Whether the operand is an index into the constant table.
+    pub fn has_constant_operand(self) -> bool {
+        matches!(self,
+                 OpCode::Constant
+                 | OpCode::GetGlobal
+                 | OpCode::DefineGlobal
+                 | OpCode::SetGlobal)
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct Chunk {
     code: Vec<u8>,
     constants: ValueArray,
     lines: Vec<usize>,
+    locals: Vec<LocalInfo>,
+}
+
+/// Debug info for a local variable: the stack slot it occupies while the code
+/// in `start..end` runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalInfo {
+    pub name: String,
+    pub slot: u8,
+    pub start: usize,
+    pub end: usize,
 }
 
 impl Chunk {
@@ -38,6 +169,7 @@ impl Chunk {
             code: Vec::<u8>::new(),
             constants: ValueArray::new(),
             lines: Vec::<usize>::new(),
+            locals: Vec::new(),
         }
     }
 
@@ -46,40 +178,101 @@ impl Chunk {
         self.lines.push(line);
     }
 
+    /// Overwrites a byte already written, such as a jump operand once its
+    /// target is known.
+    pub fn patch(&mut self, offset: usize, byte: u8) {
+        self.code[offset] = byte;
+    }
+
+    /// Where the jump at `offset` lands.
+    pub fn jump_target(&self, offset: usize) -> usize {
+        let distance = u16::from_be_bytes([self.code[offset + 1], self.code[offset + 2]]);
+        offset + 3 + distance as usize
+    }
+
     pub fn read_code(&self, ip: usize) -> u8 {
         self.code[ip]
     }
 
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
     pub fn read_constant(&self, i: usize) -> &Value {
         self.constants.read_value(i)
     }
 
+    /// # Safety
+    /// `i` must be in bounds, which `verifier::verify` guarantees for every
+    /// constant operand in the chunk.
+    #[inline(always)]
+    pub unsafe fn read_constant_unchecked(&self, i: usize) -> &Value {
+        self.constants.read_value_unchecked(i)
+    }
+
     pub fn write_constant(&mut self, value: Value) -> usize {
         self.constants.write(value)
     }
 
+    pub fn constant_count(&self) -> usize {
+        self.constants.len()
+    }
+
     pub fn get_line(&self, ip: usize) -> usize {
         self.lines[ip]
     }
 
+    /// The first line at or after `line` that has code on it, which is where
+    /// a breakpoint on `line` will actually stop.
+    pub fn breakable_line(&self, line: usize) -> Option<usize> {
+        self.lines.iter().copied().filter(|&l| l >= line).min()
+    }
+
+    /// Records that the local in `slot` comes into scope at the next
+    /// instruction written.
+    pub fn begin_local(&mut self, name: &str, slot: u8) {
+        self.locals.push(LocalInfo {
+            name: name.to_string(),
+            slot,
+            start: self.code.len(),
+            end: usize::MAX,
+        });
+    }
+
+    /// Closes the innermost open local in `slot` at the next instruction.
+    pub fn end_local(&mut self, slot: u8) {
+        let end = self.code.len();
+        if let Some(local) = self.locals
+            .iter_mut()
+            .rev()
+            .find(|l| l.slot == slot && l.end == usize::MAX)
+        {
+            local.end = end;
+        }
+    }
+
+    pub fn locals(&self) -> &[LocalInfo] {
+        &self.locals
+    }
+
+    /// Locals in scope when the instruction at `offset` runs, by slot.
+    pub fn locals_at(&self, offset: usize) -> impl Iterator<Item = &LocalInfo> {
+        self.locals.iter().filter(move |l| l.start <= offset && offset < l.end)
+    }
+
     pub fn free(&mut self) {
         // might be unnecessary
         self.code = Vec::<u8>::new();
         self.constants = ValueArray::new();
+        self.locals = Vec::new();
     }
 
     pub fn disassemble<T: ToString>(&self, name: T) {
-        println!("== {} ==", name.to_string());
-
-        let mut offset = 0;
-        while offset < self.code.len() {
-            offset = self.disassemble_instruction(offset);
-        }
-
-        println!("== end test ==");
+        print!("{}", disassemble(self, name));
     }
 
-
+    /// Prints a single instruction, as used when tracing execution. Returns
+    /// the offset of the next instruction.
     pub fn disassemble_instruction(&self, offset: usize) -> usize {
         print!("{offset:04} ");
 
@@ -89,80 +282,21 @@ impl Chunk {
             print!("{:4} ", self.lines[offset]);
         }
 
-        let instruction: OpCode = self.code[offset].into();
-        match instruction {
-            OpCode::Return   => self.simple_instruction("OP_RETURN", offset),
-            OpCode::Negate   => self.simple_instruction("OP_NEGATE", offset),
-            OpCode::Add      => self.simple_instruction("OP_ADD", offset),
-            OpCode::Sub      => self.simple_instruction("OP_SUBTRACT", offset),
-            OpCode::Mul      => self.simple_instruction("OP_MULTIPLY", offset),
-            OpCode::Div      => self.simple_instruction("OP_DIVIDE", offset),
-            OpCode::Nil      => self.simple_instruction("OP_NIL", offset),
-            OpCode::True     => self.simple_instruction("OP_TRUE", offset),
-            OpCode::False    => self.simple_instruction("OP_FALSE", offset),
-            OpCode::Not      => self.simple_instruction("OP_NOT", offset),
-            OpCode::Pop      => self.simple_instruction("OP_POP", offset),
-            OpCode::Equal    => self.simple_instruction("OP_EQUAL", offset),
-            OpCode::Greater  => self.simple_instruction("OP_GREATER", offset),
-            OpCode::Less     => self.simple_instruction("OP_LESS", offset),
-            OpCode::Print    => self.simple_instruction("OP_PRINT", offset),
-
-            OpCode::Constant => self.const_instruction("OP_CONSTANT", offset),
-            OpCode::GetGlobal => self.const_instruction("OP_GET_GLOBAL", offset),
-            OpCode::DefineGlobal => self.const_instruction("OP_DEFINE_GLOBAL", offset),
-            OpCode::SetGlobal    => self.const_instruction("OP_SET_GLOBAL", offset),
-            OpCode::GetLocal => self.byte_instruction("OP_GET_LOCAL", offset),
-            OpCode::SetLocal => self.byte_instruction("OP_SET_LOCAL", offset),
-        }
-    }
-
-    fn simple_instruction(&self, name: &str, offset: usize) -> usize {
-        println!("{name}");
-        offset + 1
-    }
-
-    fn byte_instruction(&self, name: &str, offset: usize) -> usize {
-        let slot = self.code[offset + 1];
-        println!("{name:-16} {slot:4}");
-        offset + 2
-    }
-
-    fn const_instruction(&self, name: &str, offset: usize) -> usize {
-        // index of constant in self.constants
-        let constant = self.code[offset + 1];
-        print!("{name}     {} ", constant);
-        self.constants.print_value(constant as usize);
-//        self.constants[constant as usize].print();
-        println!("");
-        offset + 2
+        let instruction = disassemble_instruction(self, offset);
+        println!("{instruction}");
+        offset + instruction.len
     }
 }
 
-impl From<u8> for OpCode {
-    fn from(code: u8) -> Self {
-        match code {
-            0 => OpCode::Return,
-            1 => OpCode::Constant,
-            2 => OpCode::Nil,
-            3 => OpCode::True,
-            4 => OpCode::False,
-            5 => OpCode::Pop,
-            6 => OpCode::GetGlobal,
-            7 => OpCode::DefineGlobal,
-            8 => OpCode::SetGlobal,
-            9 => OpCode::Equal,
-            10 => OpCode::Greater,
-            11 => OpCode::Less,
-            12 => OpCode::Negate,
-            13 => OpCode::Print,
-            14 => OpCode::Add,
-            15 => OpCode::Sub,
-            16 => OpCode::Mul,
-            17 => OpCode::Div,
-            18 => OpCode::Not,
-            19 => OpCode::GetLocal,
-            20 => OpCode::SetLocal,
-            _ => unimplemented!("Invalid OpCode"),
+impl TryFrom<u8> for OpCode {
+    type Error = u8;
+
+    fn try_from(code: u8) -> Result<Self, u8> {
+        if code <= OpCode::MAX {
+            // SAFETY: the discriminants are contiguous from 0 to MAX
+            Ok(unsafe { OpCode::from_unchecked(code) })
+        } else {
+            Err(code)
         }
     }
 }
@@ -0,0 +1,437 @@
+use crate::ast::*;
+use crate::chunk::*;
+use crate::compiler::{Declaration, Diagnostic, Reference, Symbols};
+use crate::value::*;
+
+/// Lowers a `Program` into a `Chunk`, resolving variables to stack slots or
+/// globals on the way.
+pub struct CodeGen<'a> {
+    chunk: &'a mut Chunk,
+    locals: Vec<Local>,
+    scope_depth: usize,
+    // line of the node being lowered, recorded with each byte
+    line: usize,
+    symbols: Symbols,
+    diagnostics: Vec<Diagnostic>,
+}
+
+struct Local {
+    name: String,
+    depth: Option<usize>,
+    // index into `Symbols::declarations`
+    declaration: usize,
+}
+
+impl<'a> CodeGen<'a> {
+    pub fn new(chunk: &'a mut Chunk) -> Self {
+        Self {
+            chunk,
+            locals: Vec::new(),
+            scope_depth: 0,
+            line: 0,
+            symbols: Symbols::default(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn generate(&mut self, program: &Program) {
+        for stmt in program.statements.iter() {
+            self.statement(stmt);
+        }
+
+        self.line = program.end.line;
+        self.emit_return();
+        self.symbols.resolve_globals();
+    }
+
+    pub fn had_error(&self) -> bool {
+        !self.diagnostics.is_empty()
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    fn error(&mut self, span: Span, text: &str, msg: &str) {
+        self.diagnostics.push(Diagnostic {
+            line: span.line,
+            column: span.column,
+            len: span.len,
+            location: format!(" at '{text}'"),
+            message: msg.to_string(),
+        });
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        self.chunk.write(byte, self.line);
+    }
+
+    fn emit_bytes(&mut self, byte1: u8, byte2: u8) {
+        self.emit_byte(byte1);
+        self.emit_byte(byte2);
+    }
+
+    /// Emits a jump with a placeholder distance, returning its offset for
+    /// `patch_jump`.
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_byte(op.into());
+        self.emit_bytes(0xff, 0xff);
+        self.chunk.code().len() - 3
+    }
+
+    /// Points the jump at `offset` to the next instruction written.
+    fn patch_jump(&mut self, offset: usize, span: Span, text: &str) {
+        let distance = self.chunk.code().len() - offset - 3;
+        let Ok(distance) = u16::try_from(distance) else {
+            self.error(span, text, "Too much code to jump over.");
+            return;
+        };
+        let [high, low] = distance.to_be_bytes();
+        self.chunk.patch(offset + 1, high);
+        self.chunk.patch(offset + 2, low);
+    }
+
+    fn emit_return(&mut self) {
+        self.emit_byte(OpCode::Return.into());
+    }
+
+    fn make_constant(&mut self, value: Value, span: Span, text: &str) -> u8 {
+        let constant = self.chunk.write_constant(value);
+        if constant > u8::MAX as usize {
+            self.error(span, text, "Too many constants in one chunk.");
+            return 0;
+        };
+
+        constant as u8
+    }
+
+    fn emit_constant(&mut self, value: Value, span: Span, text: &str) {
+        let con = self.make_constant(value, span, text);
+        self.emit_bytes(OpCode::Constant.into(), con);
+    }
+
+    fn identifier_constant(&mut self, name: &Identifier) -> u8 {
+        self.make_constant(Value::string(&name.name), name.span, &name.name)
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        self.line = stmt.span.line;
+        match &stmt.kind {
+            StmtKind::Expression(expr) => {
+                self.expression(expr);
+                self.line = stmt.span.line;
+                self.emit_byte(OpCode::Pop.into());
+            }
+            StmtKind::Print(expr) => {
+                self.expression(expr);
+                self.line = stmt.span.line;
+                self.emit_byte(OpCode::Print.into());
+            }
+            StmtKind::Var { name, initializer } => {
+                self.var_declaration(name, initializer.as_ref(), stmt.span);
+            }
+            StmtKind::Block { body, close } => {
+                self.begin_scope();
+                for stmt in body.iter() {
+                    self.statement(stmt);
+                }
+                self.line = close.line;
+                self.end_scope();
+            }
+        }
+    }
+
+    fn var_declaration(&mut self, name: &Identifier, initializer: Option<&Expr>, span: Span) {
+        let global = if self.scope_depth > 0 {
+            self.declare_local(name);
+            0
+        } else {
+            self.declare_symbol(name);
+            self.identifier_constant(name)
+        };
+
+        match initializer {
+            Some(expr) => self.expression(expr),
+            // Desugars `var a;` into `var a = nil;`
+            None => self.emit_byte(OpCode::Nil.into()),
+        }
+
+        self.line = span.line;
+        if self.scope_depth > 0 {
+            self.mark_initialized();
+        } else {
+            self.emit_bytes(OpCode::DefineGlobal.into(), global);
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        self.line = expr.span.line;
+        match &expr.kind {
+            ExprKind::Number(n) => self.emit_constant(Value::number(*n), expr.span, &n.to_string()),
+            ExprKind::Int(n) => self.emit_constant(Value::int(*n), expr.span, &n.to_string()),
+            ExprKind::BigInt(n) => self.emit_constant(Value::bigint(n.clone()), expr.span, &n.to_string()),
+            ExprKind::String(s) => {
+                self.emit_constant(Value::string(s), expr.span, &format!("\"{s}\""))
+            }
+            ExprKind::Bool(true) => self.emit_byte(OpCode::True.into()),
+            ExprKind::Bool(false) => self.emit_byte(OpCode::False.into()),
+            ExprKind::Nil => self.emit_byte(OpCode::Nil.into()),
+            ExprKind::Variable(name) => {
+                let (arg, get_op, _) = self.resolve(name);
+                self.emit_bytes(get_op.into(), arg);
+            }
+            ExprKind::Assign { name, value } => {
+                let (arg, _, set_op) = self.resolve(name);
+                self.expression(value);
+                self.line = expr.span.line;
+                self.emit_bytes(set_op.into(), arg);
+            }
+            ExprKind::Unary { op, operand } => {
+                self.expression(operand);
+                self.line = expr.span.line;
+                match op {
+                    UnaryOp::Negate => self.emit_byte(OpCode::Negate.into()),
+                    UnaryOp::Not => self.emit_byte(OpCode::Not.into()),
+                    UnaryOp::BitNot => self.emit_byte(OpCode::BitNot.into()),
+                }
+            }
+            ExprKind::Binary { op, left, right } => {
+                self.expression(left);
+                self.expression(right);
+                self.line = expr.span.line;
+                self.binary_op(*op);
+            }
+            ExprKind::Grouping(inner) => self.expression(inner),
+            ExprKind::Interpolation(parts) => {
+                for (i, part) in parts.iter().enumerate() {
+                    self.expression(part);
+                    self.line = expr.span.line;
+                    if !matches!(part.kind, ExprKind::String(_)) {
+                        self.emit_byte(OpCode::ToString.into());
+                    }
+                    if i > 0 {
+                        self.emit_byte(OpCode::Add.into());
+                    }
+                }
+            }
+            ExprKind::List(items) => {
+                for item in items.iter() {
+                    self.expression(item);
+                }
+                self.line = expr.span.line;
+                // more than 255 has already been reported by the parser
+                self.emit_bytes(OpCode::BuildList.into(), items.len() as u8);
+            }
+            ExprKind::Map(entries) => {
+                self.line = expr.span.line;
+                self.emit_byte(OpCode::Map.into());
+                // entries go in one at a time, so a long literal only ever
+                // needs the map, a key and a value on the stack
+                for (key, value) in entries.iter() {
+                    self.expression(key);
+                    self.expression(value);
+                    self.line = key.span.line;
+                    self.emit_byte(OpCode::MapEntry.into());
+                }
+            }
+            ExprKind::Index { object, index } => {
+                self.expression(object);
+                self.expression(index);
+                self.line = expr.span.line;
+                self.emit_byte(OpCode::GetIndex.into());
+            }
+            ExprKind::SetIndex { object, index, value } => {
+                self.expression(object);
+                self.expression(index);
+                self.expression(value);
+                self.line = expr.span.line;
+                self.emit_byte(OpCode::SetIndex.into());
+            }
+            ExprKind::Update { target, op, value, postfix } => self.update(expr, target, *op, value, *postfix),
+            ExprKind::Conditional { condition, then_branch, else_branch } => {
+                self.expression(condition);
+                self.line = expr.span.line;
+                let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit_byte(OpCode::Pop.into());
+                self.expression(then_branch);
+                self.line = expr.span.line;
+                let end_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(else_jump, expr.span, "?");
+                self.emit_byte(OpCode::Pop.into());
+                self.expression(else_branch);
+                self.patch_jump(end_jump, expr.span, "?");
+            }
+            ExprKind::Coalesce { left, right } => {
+                self.expression(left);
+                self.line = expr.span.line;
+                let end_jump = self.emit_jump(OpCode::JumpIfNotNil);
+                self.emit_byte(OpCode::Pop.into());
+                self.expression(right);
+                self.patch_jump(end_jump, expr.span, "??");
+            }
+            ExprKind::Call { callee, arguments } => {
+                self.expression(callee);
+                for argument in arguments.iter() {
+                    self.expression(argument);
+                }
+                self.line = expr.span.line;
+                // more than 255 has already been reported by the parser
+                self.emit_bytes(OpCode::Call.into(), arguments.len() as u8);
+            }
+        }
+    }
+
+    fn binary_op(&mut self, op: BinaryOp) {
+        match op {
+            BinaryOp::NotEqual     => self.emit_bytes(OpCode::Equal.into(), OpCode::Not.into()),
+            BinaryOp::Equal        => self.emit_byte(OpCode::Equal.into()),
+            BinaryOp::Greater      => self.emit_byte(OpCode::Greater.into()),
+            BinaryOp::GreaterEqual => self.emit_bytes(OpCode::Less.into(), OpCode::Not.into()),
+            BinaryOp::Less         => self.emit_byte(OpCode::Less.into()),
+            BinaryOp::LessEqual    => self.emit_bytes(OpCode::Greater.into(), OpCode::Not.into()),
+
+            BinaryOp::Add => self.emit_byte(OpCode::Add.into()),
+            BinaryOp::Sub => self.emit_byte(OpCode::Sub.into()),
+            BinaryOp::Mul => self.emit_byte(OpCode::Mul.into()),
+            BinaryOp::Div => self.emit_byte(OpCode::Div.into()),
+            BinaryOp::FloorDiv => self.emit_byte(OpCode::FloorDiv.into()),
+            BinaryOp::Mod => self.emit_byte(OpCode::Mod.into()),
+            BinaryOp::Pow => self.emit_byte(OpCode::Pow.into()),
+            BinaryOp::BitAnd => self.emit_byte(OpCode::BitAnd.into()),
+            BinaryOp::BitOr => self.emit_byte(OpCode::BitOr.into()),
+            BinaryOp::BitXor => self.emit_byte(OpCode::BitXor.into()),
+            BinaryOp::ShiftLeft => self.emit_byte(OpCode::ShiftLeft.into()),
+            BinaryOp::ShiftRight => self.emit_byte(OpCode::ShiftRight.into()),
+        }
+    }
+
+    /// `target op= value`, leaving the new value on the stack, or the old one
+    /// if `postfix`. A subscript's object and index are evaluated once and
+    /// kept on the stack for the store.
+    fn update(&mut self, expr: &Expr, target: &Expr, op: BinaryOp, value: &Expr, postfix: bool) {
+        match &target.kind {
+            ExprKind::Variable(name) => {
+                let (arg, get_op, set_op) = self.resolve(name);
+                self.emit_bytes(get_op.into(), arg);
+                if postfix {
+                    self.emit_byte(OpCode::Dup.into());
+                }
+                self.expression(value);
+                self.line = expr.span.line;
+                self.binary_op(op);
+                self.emit_bytes(set_op.into(), arg);
+            }
+            ExprKind::Index { object, index } => {
+                self.expression(object);
+                self.expression(index);
+                self.line = expr.span.line;
+                self.emit_bytes(OpCode::Dup2.into(), OpCode::GetIndex.into());
+                if postfix {
+                    // the old value goes under the object and index, to be
+                    // left behind once the store has popped them
+                    self.emit_byte(OpCode::Dup.into());
+                    self.emit_bytes(OpCode::Bury.into(), 3);
+                }
+                self.expression(value);
+                self.line = expr.span.line;
+                self.binary_op(op);
+                self.emit_byte(OpCode::SetIndex.into());
+            }
+            // anything else has already been reported by the parser
+            _ => unreachable!("update of something that can't be assigned"),
+        }
+        if postfix {
+            self.emit_byte(OpCode::Pop.into());
+        }
+    }
+
+    /// The operand and get/set instructions for a variable, recording the
+    /// reference on the way.
+    fn resolve(&mut self, name: &Identifier) -> (u8, OpCode, OpCode) {
+        let mut reference = Reference {
+            name: name.name.clone(),
+            line: name.span.line,
+            column: name.span.column,
+            declaration: None,
+        };
+
+        let resolved = if let Some(local_arg) = self.resolve_local(name) {
+            reference.declaration = Some(self.locals[local_arg as usize].declaration);
+            (local_arg, OpCode::GetLocal, OpCode::SetLocal)
+        } else {
+            (
+                self.identifier_constant(name),
+                OpCode::GetGlobal,
+                OpCode::SetGlobal,
+            )
+        };
+        self.symbols.references.push(reference);
+        resolved
+    }
+
+    fn resolve_local(&mut self, name: &Identifier) -> Option<u8> {
+        let slot = self.locals.iter().rposition(|local| local.name == name.name)?;
+        if self.locals[slot].depth.is_none() {
+            self.error(name.span, &name.name, "Can't read local variable in its own initalizer.");
+        }
+        Some(slot as u8)
+    }
+
+    fn begin_scope(&mut self) {
+        self.scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.scope_depth -= 1;
+
+        while self.locals.last().is_some_and(|local| local.depth.unwrap() > self.scope_depth) {
+            let slot = (self.locals.len() - 1) as u8;
+            self.chunk.end_local(slot);
+            self.emit_byte(OpCode::Pop.into());
+            self.locals.pop();
+        }
+    }
+
+    fn declare_symbol(&mut self, name: &Identifier) -> usize {
+        self.symbols.declarations.push(Declaration {
+            name: name.name.clone(),
+            line: name.span.line,
+            column: name.span.column,
+            local: None,
+        });
+        self.symbols.declarations.len() - 1
+    }
+
+    fn declare_local(&mut self, name: &Identifier) {
+        if self.locals.iter().any(|local| local.name == name.name) {
+            self.error(name.span, &name.name, "already a variable with this name in this scope.");
+            return;
+        }
+        if self.locals.len() > u8::MAX as usize {
+            self.error(name.span, &name.name, "Too many local variables in function.");
+            return;
+        }
+
+        let declaration = self.declare_symbol(name);
+        self.locals.push(Local {
+            name: name.name.clone(),
+            depth: None,
+            declaration,
+        });
+    }
+
+    fn mark_initialized(&mut self) {
+        let slot = self.locals.len().wrapping_sub(1);
+        // nothing to mark if the declaration was rejected
+        let Some(last) = self.locals.last_mut().filter(|local| local.depth.is_none()) else {
+            return;
+        };
+        last.depth = Some(self.scope_depth);
+        self.symbols.declarations[last.declaration].local = Some(self.chunk.locals().len());
+        self.chunk.begin_local(&last.name, slot as u8);
+    }
+}
@@ -0,0 +1,388 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+use crate::chunk::Chunk;
+use crate::compiler::Compiler;
+use crate::debugger::*;
+use crate::json::Json;
+use crate::natives::Capabilities;
+use crate::protocol::{read_message, write_message};
+use crate::vm::*;
+
+// Debug Adapter Protocol over a pair of streams. There is a single thread
+// (id 1); while the script is paused the debugger hook below services
+// requests itself, so the VM never runs on another thread.
+
+const THREAD_ID: usize = 1;
+const LOCALS_REF: usize = 1;
+const GLOBALS_REF: usize = 2;
+
+/// Reads and writes `Content-Length` framed JSON messages.
+struct Transport<R, W> {
+    reader: R,
+    writer: W,
+    seq: usize,
+    /// Set when the client disconnects or terminates while paused.
+    ended: bool,
+}
+
+type Shared<R, W> = Rc<RefCell<Transport<R, W>>>;
+
+impl<R: BufRead, W: Write> Transport<R, W> {
+    /// The next message, or `None` once the client has gone away.
+    fn read(&mut self) -> io::Result<Option<Json>> {
+        read_message(&mut self.reader)
+    }
+
+    fn send(&mut self, mut fields: Vec<(&str, Json)>) -> io::Result<()> {
+        self.seq += 1;
+        fields.insert(0, ("seq", self.seq.into()));
+        write_message(&mut self.writer, &Json::object(fields))
+    }
+
+    fn respond(&mut self, request: &Json, body: Json) -> io::Result<()> {
+        self.send(vec![
+            ("type", "response".into()),
+            ("request_seq", request.get("seq").clone()),
+            ("success", true.into()),
+            ("command", request.get("command").clone()),
+            ("body", body),
+        ])
+    }
+
+    fn respond_error(&mut self, request: &Json, message: &str) -> io::Result<()> {
+        self.send(vec![
+            ("type", "response".into()),
+            ("request_seq", request.get("seq").clone()),
+            ("success", false.into()),
+            ("command", request.get("command").clone()),
+            ("message", message.into()),
+        ])
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send(vec![
+            ("type", "event".into()),
+            ("event", event.into()),
+            ("body", body),
+        ])
+    }
+}
+
+fn empty() -> Json {
+    Json::Object(Vec::new())
+}
+
+fn threads() -> Json {
+    Json::object(vec![("threads", Json::Array(vec![Json::object(vec![
+        ("id", THREAD_ID.into()),
+        ("name", "main".into()),
+    ])]))])
+}
+
+/// The lines in a `setBreakpoints` request, moved to lines with code when a
+/// chunk is available.
+fn set_breakpoints(request: &Json, chunk: Option<&Chunk>, set: &mut BTreeSet<usize>) -> Json {
+    set.clear();
+
+    let requested = request.get("arguments").get("breakpoints").as_array().unwrap_or(&[]);
+    let breakpoints = requested
+        .iter()
+        .map(|bp| {
+            let line = bp.get("line").as_usize().unwrap_or(0);
+            let actual = match chunk {
+                Some(chunk) => chunk.breakable_line(line),
+                None => Some(line),
+            };
+            if let Some(actual) = actual {
+                set.insert(actual);
+            }
+            Json::object(vec![
+                ("verified", actual.is_some().into()),
+                ("line", actual.unwrap_or(line).into()),
+            ])
+        })
+        .collect();
+
+    Json::object(vec![("breakpoints", Json::Array(breakpoints))])
+}
+
+/// Sends everything the script prints as `output` events.
+struct OutputEvents<R, W>(Shared<R, W>);
+
+impl<R: BufRead, W: Write> Write for OutputEvents<R, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let output = String::from_utf8_lossy(buf).into_owned();
+        self.0.borrow_mut().event("output", Json::object(vec![
+            ("category", "stdout".into()),
+            ("output", output.into()),
+        ]))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Services requests while the script is paused.
+struct DapHook<R, W> {
+    transport: Shared<R, W>,
+    path: String,
+}
+
+impl<R: BufRead, W: Write> DapHook<R, W> {
+    fn stack_trace(&self, ctx: &PauseContext) -> Json {
+        let frames: Vec<Json> = ctx.frames
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(id, frame)| Json::object(vec![
+                ("id", id.into()),
+                ("name", frame.name.as_str().into()),
+                ("line", frame.line.into()),
+                ("column", 1usize.into()),
+                ("source", Json::object(vec![("path", self.path.as_str().into())])),
+            ]))
+            .collect();
+
+        Json::object(vec![
+            ("totalFrames", frames.len().into()),
+            ("stackFrames", Json::Array(frames)),
+        ])
+    }
+
+    fn scopes() -> Json {
+        let scope = |name: &str, reference: usize| Json::object(vec![
+            ("name", name.into()),
+            ("variablesReference", reference.into()),
+            ("expensive", false.into()),
+        ]);
+        Json::object(vec![("scopes", Json::Array(vec![
+            scope("Locals", LOCALS_REF),
+            scope("Globals", GLOBALS_REF),
+        ]))])
+    }
+
+    fn variables(request: &Json, ctx: &PauseContext) -> Json {
+        let variables: Vec<(&str, String)> =
+            match request.get("arguments").get("variablesReference").as_usize() {
+                Some(LOCALS_REF) => ctx.locals
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.to_string()))
+                    .collect(),
+                Some(GLOBALS_REF) => {
+                    let mut globals: Vec<(&str, String)> = ctx.globals
+                        .iter()
+                        .filter(|(_, value)| value.as_native().is_none())
+                        .map(|(name, value)| (name.as_str(), value.to_string()))
+                        .collect();
+                    globals.sort();
+                    globals
+                }
+                _ => Vec::new(),
+            };
+
+        let variables = variables
+            .into_iter()
+            .map(|(name, value)| Json::object(vec![
+                ("name", name.into()),
+                ("value", value.into()),
+                ("variablesReference", 0usize.into()),
+            ]))
+            .collect();
+        Json::object(vec![("variables", Json::Array(variables))])
+    }
+
+    fn serve_paused(&mut self, ctx: &mut PauseContext) -> io::Result<Resume> {
+        let mut transport = self.transport.borrow_mut();
+
+        let reason = match ctx.reason {
+            PauseReason::Entry => "entry",
+            PauseReason::Breakpoint => "breakpoint",
+            PauseReason::Step => "step",
+        };
+        transport.event("stopped", Json::object(vec![
+            ("reason", reason.into()),
+            ("threadId", THREAD_ID.into()),
+            ("allThreadsStopped", true.into()),
+        ]))?;
+
+        loop {
+            let Some(request) = transport.read()? else {
+                // the client is gone, let the script finish
+                return Ok(Resume::Continue);
+            };
+
+            let resume = match request.get("command").as_str().unwrap_or("") {
+                "threads" => { transport.respond(&request, threads())?; None }
+                "stackTrace" => { transport.respond(&request, self.stack_trace(ctx))?; None }
+                "scopes" => { transport.respond(&request, Self::scopes())?; None }
+                "variables" => { transport.respond(&request, Self::variables(&request, ctx))?; None }
+                "setBreakpoints" => {
+                    let body = set_breakpoints(&request, Some(ctx.chunk), ctx.breakpoints);
+                    transport.respond(&request, body)?;
+                    None
+                }
+                "continue" => Some(Resume::Continue),
+                "next" => Some(Resume::StepOver),
+                "stepIn" => Some(Resume::StepIn),
+                "stepOut" => Some(Resume::StepOut),
+                "disconnect" | "terminate" => {
+                    transport.ended = true;
+                    transport.respond(&request, empty())?;
+                    return Ok(Resume::Terminate);
+                }
+                _ => { transport.respond_error(&request, "unsupported request while paused")?; None }
+            };
+
+            if let Some(resume) = resume {
+                let body = if resume == Resume::Continue {
+                    Json::object(vec![("allThreadsContinued", true.into())])
+                } else {
+                    empty()
+                };
+                transport.respond(&request, body)?;
+                return Ok(resume);
+            }
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> DebugHook for DapHook<R, W> {
+    fn paused(&mut self, ctx: &mut PauseContext) -> Resume {
+        // if the connection breaks there is nobody left to stop for
+        self.serve_paused(ctx).unwrap_or(Resume::Continue)
+    }
+}
+
+/// What `launch` asked for.
+struct Launch {
+    path: String,
+    chunk: Chunk,
+    stop_on_entry: bool,
+}
+
+fn launch(request: &Json) -> Result<Launch, String> {
+    let arguments = request.get("arguments");
+    let path = arguments.get("program").as_str().ok_or("missing 'program'")?;
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("could not read {path}: {e}"))?;
+
+    // the diagnostics go back to the client, which can't see our stderr
+    let mut chunk = Chunk::new();
+    let mut compiler = Compiler::new(&source, &mut chunk);
+    compiler.set_quiet(true);
+    if compiler.compile().is_err() {
+        let mut message = format!("{path} failed to compile");
+        for diagnostic in compiler.diagnostics() {
+            message += &format!("\n{diagnostic}");
+        }
+        return Err(message);
+    }
+
+    Ok(Launch {
+        path: path.to_string(),
+        chunk,
+        stop_on_entry: arguments.get("stopOnEntry").as_bool().unwrap_or(false),
+    })
+}
+
+fn run<R, W>(transport: &Shared<R, W>, launch: &Launch, breakpoints: BTreeSet<usize>) -> io::Result<()>
+where
+    R: BufRead + 'static,
+    W: Write + 'static,
+{
+    let hook = DapHook { transport: transport.clone(), path: launch.path.clone() };
+    let mut debugger = Debugger::new(Box::new(hook), launch.stop_on_entry);
+    debugger.breakpoints = breakpoints;
+
+    let mut vm = VM::with_capabilities(Capabilities::ALL);
+    vm.set_output(Box::new(OutputEvents(transport.clone())));
+    vm.set_debugger(Some(debugger));
+
+    // the script's `exit` ends the session, not the server
+    let exit_code = match vm.interpret_chunk(&launch.chunk) {
+        Ok(_) => 0,
+        Err(InterpretError::Compile | InterpretError::Verify) => 65,
+        Err(InterpretError::Exit(code)) => code,
+        // the client ended the session while paused
+        Err(InterpretError::Interrupted) if transport.borrow().ended => 0,
+        Err(_) => 70,
+    };
+
+    let mut transport = transport.borrow_mut();
+    transport.event("exited", Json::object(vec![("exitCode", Json::Number(exit_code as f64))]))?;
+    transport.event("terminated", empty())
+}
+
+/// Runs a debug session until the client disconnects or closes the stream.
+pub fn serve<R, W>(reader: R, writer: W) -> io::Result<()>
+where
+    R: BufRead + 'static,
+    W: Write + 'static,
+{
+    let transport = Rc::new(RefCell::new(Transport { reader, writer, seq: 0, ended: false }));
+    let mut launched: Option<Launch> = None;
+    let mut breakpoints = BTreeSet::new();
+    let mut configured = false;
+    let mut finished = false;
+
+    loop {
+        let Some(request) = transport.borrow_mut().read()? else {
+            return Ok(());
+        };
+
+        {
+            let mut transport = transport.borrow_mut();
+            match request.get("command").as_str().unwrap_or("") {
+                "initialize" => {
+                    transport.respond(&request, Json::object(vec![
+                        ("supportsConfigurationDoneRequest", true.into()),
+                    ]))?;
+                    transport.event("initialized", empty())?;
+                }
+                "launch" => match launch(&request) {
+                    Ok(l) => {
+                        // re-check breakpoints that arrived before the code
+                        breakpoints = breakpoints
+                            .iter()
+                            .filter_map(|&line| l.chunk.breakable_line(line))
+                            .collect();
+                        launched = Some(l);
+                        transport.respond(&request, empty())?;
+                    }
+                    Err(message) => transport.respond_error(&request, &message)?,
+                },
+                "setBreakpoints" => {
+                    let chunk = launched.as_ref().map(|l| &l.chunk);
+                    let body = set_breakpoints(&request, chunk, &mut breakpoints);
+                    transport.respond(&request, body)?;
+                }
+                "setExceptionBreakpoints" => transport.respond(&request, empty())?,
+                "configurationDone" => {
+                    configured = true;
+                    transport.respond(&request, empty())?;
+                }
+                "threads" => transport.respond(&request, threads())?,
+                "disconnect" | "terminate" => {
+                    transport.respond(&request, empty())?;
+                    return Ok(());
+                }
+                _ => transport.respond_error(&request, "unsupported request")?,
+            }
+        }
+
+        if configured && !finished {
+            if let Some(launch) = &launched {
+                finished = true;
+                run(&transport, launch, std::mem::take(&mut breakpoints))?;
+                if transport.borrow().ended {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
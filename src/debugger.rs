@@ -0,0 +1,251 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, BufRead, Write};
+
+use crate::chunk::Chunk;
+use crate::value::Value;
+
+/// How execution continues after a pause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    Continue,
+    /// Stop at the next new line, entering calls.
+    StepIn,
+    /// Stop at the next new line in this frame or a caller.
+    StepOver,
+    /// Stop at the next new line once this frame has returned.
+    StepOut,
+    /// End the run here with `InterpretError::Interrupted`.
+    Terminate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    Entry,
+    Breakpoint,
+    Step,
+}
+
+/// One entry in the call stack, innermost last.
+#[derive(Debug, Clone)]
+pub struct FrameInfo {
+    pub name: String,
+    pub line: usize,
+    pub offset: usize,
+}
+
+/// Everything a front end can look at while execution is paused.
+pub struct PauseContext<'a> {
+    pub reason: PauseReason,
+    pub chunk: &'a Chunk,
+    pub line: usize,
+    pub frames: Vec<FrameInfo>,
+    /// Locals in scope in the innermost frame, in slot order.
+    pub locals: Vec<(String, Value)>,
+    pub globals: &'a HashMap<String, Value>,
+    /// Lines to stop on. Front ends may change these while paused.
+    pub breakpoints: &'a mut BTreeSet<usize>,
+}
+
+/// A debugger front end, e.g. the interactive console or a DAP server. `VM`
+/// calls it every time execution pauses and blocks until it returns.
+pub trait DebugHook {
+    fn paused(&mut self, ctx: &mut PauseContext) -> Resume;
+}
+
+/// Breakpoint and stepping state, checked by `VM::run` before each
+/// instruction while a debugger is attached.
+pub struct Debugger {
+    pub breakpoints: BTreeSet<usize>,
+    pub(crate) hook: Box<dyn DebugHook>,
+    mode: Resume,
+    // frame depth the current step started from
+    step_depth: usize,
+    last_line: Option<usize>,
+    last_depth: usize,
+    started: bool,
+}
+
+impl Debugger {
+    /// With `stop_on_entry` the hook is called before the first line runs, so
+    /// breakpoints can be set interactively.
+    pub fn new(hook: Box<dyn DebugHook>, stop_on_entry: bool) -> Self {
+        Self {
+            breakpoints: BTreeSet::new(),
+            hook,
+            mode: if stop_on_entry { Resume::StepIn } else { Resume::Continue },
+            step_depth: 0,
+            last_line: None,
+            last_depth: 0,
+            started: false,
+        }
+    }
+
+    /// Decides whether to pause before an instruction on `line` running at
+    /// frame `depth`. Only the first instruction of each line can pause.
+    pub(crate) fn should_pause(&mut self, line: usize, depth: usize) -> Option<PauseReason> {
+        let new_line = self.last_line != Some(line) || self.last_depth != depth;
+        self.last_line = Some(line);
+        self.last_depth = depth;
+        if !new_line {
+            return None;
+        }
+
+        if !self.started {
+            self.started = true;
+            if self.mode == Resume::StepIn {
+                return Some(PauseReason::Entry);
+            }
+        }
+
+        let stepped = match self.mode {
+            Resume::Continue | Resume::Terminate => false,
+            Resume::StepIn => true,
+            Resume::StepOver => depth <= self.step_depth,
+            Resume::StepOut => depth < self.step_depth,
+        };
+
+        if stepped {
+            Some(PauseReason::Step)
+        } else if self.breakpoints.contains(&line) {
+            Some(PauseReason::Breakpoint)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn resume(&mut self, how: Resume, depth: usize) {
+        self.mode = how;
+        self.step_depth = depth;
+    }
+}
+
+/// The `rlox debug` prompt.
+pub struct ConsoleDebugger {
+    source: Vec<String>,
+}
+
+impl ConsoleDebugger {
+    pub fn new(source: &str) -> Self {
+        Self {
+            source: source.lines().map(str::to_string).collect(),
+        }
+    }
+
+    fn show_line(&self, line: usize) {
+        if let Some(text) = self.source.get(line.wrapping_sub(1)) {
+            println!("-> {line:4}  {text}");
+        }
+    }
+
+    fn list(&self, line: usize) {
+        let first = line.saturating_sub(3).max(1);
+        for n in first..=line + 3 {
+            if let Some(text) = self.source.get(n - 1) {
+                let marker = if n == line { "->" } else { "  " };
+                println!("{marker} {n:4}  {text}");
+            }
+        }
+    }
+
+    fn help() {
+        println!("\
+  b/break <line>   set a breakpoint        d/delete <line>  remove a breakpoint
+  breakpoints      list breakpoints        c/continue       run to the next breakpoint
+  s/step           step into               n/next           step over
+  o/out            step out                bt/backtrace     show the call stack
+  l/locals         show locals             g/globals        show globals
+  p/print <name>   show a variable         list             show source around the line
+  q/quit           stop the program");
+    }
+}
+
+impl DebugHook for ConsoleDebugger {
+    fn paused(&mut self, ctx: &mut PauseContext) -> Resume {
+        match ctx.reason {
+            PauseReason::Entry => println!("Stopped at entry. Type 'help' for commands."),
+            PauseReason::Breakpoint => println!("Breakpoint hit at line {}.", ctx.line),
+            PauseReason::Step => {}
+        }
+        self.show_line(ctx.line);
+
+        let stdin = io::stdin();
+        loop {
+            print!("(rlox) ");
+            let _ = io::stdout().flush();
+
+            let mut input = String::new();
+            if stdin.lock().read_line(&mut input).unwrap_or(0) == 0 {
+                // EOF: let the program run to completion
+                return Resume::Continue;
+            }
+
+            let mut words = input.split_whitespace();
+            let command = words.next().unwrap_or("");
+            let arg = words.next();
+
+            match (command, arg) {
+                ("c" | "continue", _) => return Resume::Continue,
+                ("s" | "step", _) => return Resume::StepIn,
+                ("n" | "next", _) => return Resume::StepOver,
+                ("o" | "out" | "finish", _) => return Resume::StepOut,
+                ("q" | "quit", _) => return Resume::Terminate,
+                ("b" | "break", Some(line)) => match line.parse::<usize>() {
+                    Ok(line) => match ctx.chunk.breakable_line(line) {
+                        Some(line) => {
+                            ctx.breakpoints.insert(line);
+                            println!("Breakpoint set at line {line}.");
+                        }
+                        None => println!("No code at or after line {line}."),
+                    },
+                    Err(_) => println!("Expected a line number."),
+                },
+                ("d" | "delete", Some(line)) => match line.parse::<usize>() {
+                    Ok(line) if ctx.breakpoints.remove(&line) => println!("Breakpoint removed."),
+                    _ => println!("No breakpoint at line {line}."),
+                },
+                ("breakpoints", _) => {
+                    for line in ctx.breakpoints.iter() {
+                        println!("  line {line}");
+                    }
+                }
+                ("bt" | "backtrace", _) => {
+                    for (depth, frame) in ctx.frames.iter().rev().enumerate() {
+                        println!("#{depth} [line {}] in {}", frame.line, frame.name);
+                    }
+                }
+                ("l" | "locals", _) => {
+                    if ctx.locals.is_empty() {
+                        println!("No locals in scope.");
+                    }
+                    for (name, value) in ctx.locals.iter() {
+                        println!("  {name} = {value}");
+                    }
+                }
+                ("g" | "globals", _) => {
+                    // leave out the natives every program starts with
+                    let mut names: Vec<&String> = ctx.globals
+                        .iter()
+                        .filter(|(_, value)| value.as_native().is_none())
+                        .map(|(name, _)| name)
+                        .collect();
+                    names.sort();
+                    for name in names {
+                        println!("  {name} = {}", ctx.globals[name]);
+                    }
+                }
+                ("p" | "print", Some(name)) => {
+                    // innermost local shadows everything else
+                    let local = ctx.locals.iter().rev().find(|(n, _)| n == name);
+                    match local.map(|(_, v)| v).or_else(|| ctx.globals.get(name)) {
+                        Some(value) => println!("  {name} = {value}"),
+                        None => println!("No variable named '{name}'."),
+                    }
+                }
+                ("list", _) => self.list(ctx.line),
+                ("help" | "h", _) => Self::help(),
+                ("", _) => {}
+                _ => println!("Unknown command '{}'. Type 'help' for commands.", input.trim()),
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use crate::chunk::*;
+use crate::json::Json;
+use crate::value::Value;
+
+/// A decoded chunk. Nothing here prints; `Display` gives the familiar text
+/// listing and `to_json` a machine-readable one.
+pub struct Disassembly {
+    pub name: String,
+    pub constants: Vec<Value>,
+    pub instructions: Vec<Instruction>,
+    /// Jump target offset -> label number, in offset order.
+    pub labels: BTreeMap<usize, usize>,
+}
+
+pub struct Instruction {
+    pub offset: usize,
+    pub line: usize,
+    /// The raw byte when it isn't a known opcode.
+    pub op: Result<OpCode, u8>,
+    pub operand: Operand,
+    /// Length in bytes, including operands.
+    pub len: usize,
+}
+
+pub enum Operand {
+    None,
+    /// The operand byte is missing from the end of the code.
+    Truncated,
+    /// `value` is `None` when the index is outside the constant table.
+    Constant { index: u8, value: Option<Value> },
+    Slot(u8),
+    /// How many arguments or elements the instruction takes off the stack.
+    Count(u8),
+    Jump(usize),
+}
+
+impl Instruction {
+    pub fn name(&self) -> &'static str {
+        match self.op {
+            Ok(op) => op.name(),
+            Err(_) => "UNKNOWN",
+        }
+    }
+}
+
+/// Decodes the instruction at `offset`. Never panics on malformed code, so it
+/// is safe to use on bytecode that hasn't been verified.
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> Instruction {
+    let code = chunk.code();
+    let line = chunk.get_line(offset);
+
+    let op = match OpCode::try_from(code[offset]) {
+        Ok(op) => op,
+        Err(byte) => {
+            return Instruction { offset, line, op: Err(byte), operand: Operand::None, len: 1 };
+        }
+    };
+
+    let operand = match (op.operand_len(), code.get(offset + 1)) {
+        (0, _) => Operand::None,
+        (_, None) => Operand::Truncated,
+        _ if op.is_jump() && offset + 2 >= code.len() => Operand::Truncated,
+        _ if op.is_jump() => Operand::Jump(chunk.jump_target(offset)),
+        (_, Some(&byte)) => match op {
+            OpCode::GetLocal | OpCode::SetLocal => Operand::Slot(byte),
+            OpCode::Call | OpCode::BuildList | OpCode::Bury => Operand::Count(byte),
+            _ => Operand::Constant {
+                index: byte,
+                value: (usize::from(byte) < chunk.constant_count())
+                    .then(|| chunk.read_constant(byte.into()).clone()),
+            },
+        },
+    };
+
+    let len = (1 + op.operand_len()).min(code.len() - offset);
+    Instruction { offset, line, op: Ok(op), operand, len }
+}
+
+pub fn disassemble<T: ToString>(chunk: &Chunk, name: T) -> Disassembly {
+    let constants = (0..chunk.constant_count())
+        .map(|i| chunk.read_constant(i).clone())
+        .collect();
+
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while offset < chunk.code().len() {
+        let instruction = disassemble_instruction(chunk, offset);
+        offset += instruction.len;
+        instructions.push(instruction);
+    }
+
+    let mut targets: Vec<usize> = instructions
+        .iter()
+        .filter_map(|i| match i.operand {
+            Operand::Jump(target) => Some(target),
+            _ => None,
+        })
+        .collect();
+    targets.sort_unstable();
+    targets.dedup();
+    let labels = targets.into_iter().enumerate().map(|(n, t)| (t, n)).collect();
+
+    Disassembly {
+        name: name.to_string(),
+        constants,
+        instructions,
+        labels,
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match &self.operand {
+            Operand::None => match self.op {
+                Ok(_) => write!(f, "{}", self.name()),
+                Err(byte) => write!(f, "Unknown opcode {byte}"),
+            },
+            Operand::Truncated => write!(f, "{:<16} <missing operand>", self.name()),
+            Operand::Constant { index, value: Some(v) } =>
+                write!(f, "{:<16} {index:4} '{v}'", self.name()),
+            Operand::Constant { index, value: None } =>
+                write!(f, "{:<16} {index:4} <out of range>", self.name()),
+            Operand::Slot(slot) => write!(f, "{:<16} {slot:4}", self.name()),
+            Operand::Count(count) => write!(f, "{:<16} {count:4}", self.name()),
+            Operand::Jump(target) => write!(f, "{:<16} {target:04}", self.name()),
+        }
+    }
+}
+
+impl Display for Disassembly {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        writeln!(f, "== {} ==", self.name)?;
+
+        if !self.constants.is_empty() {
+            writeln!(f, "-- constants --")?;
+            for (i, constant) in self.constants.iter().enumerate() {
+                writeln!(f, "{i:4} {:<8} '{constant}'", constant.type_name())?;
+            }
+            writeln!(f, "-- code --")?;
+        }
+
+        let mut previous_line = None;
+        for instruction in &self.instructions {
+            if let Some(label) = self.labels.get(&instruction.offset) {
+                writeln!(f, "L{label}:")?;
+            }
+
+            write!(f, "{:04} ", instruction.offset)?;
+            if previous_line == Some(instruction.line) {
+                write!(f, "   | ")?;
+            } else {
+                write!(f, "{:4} ", instruction.line)?;
+            }
+            previous_line = Some(instruction.line);
+
+            match instruction.operand {
+                Operand::Jump(target) => match self.labels.get(&target) {
+                    Some(label) => writeln!(f, "{instruction} -> L{label}")?,
+                    None => writeln!(f, "{instruction}")?,
+                },
+                _ => writeln!(f, "{instruction}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Disassembly {
+    pub fn to_json(&self) -> Json {
+        let constants = self.constants
+            .iter()
+            .enumerate()
+            .map(|(i, c)| Json::object(vec![
+                ("index", i.into()),
+                ("type", c.type_name().into()),
+                ("value", c.to_string().into()),
+            ]))
+            .collect();
+
+        let instructions = self.instructions
+            .iter()
+            .map(|i| {
+                let mut fields = vec![
+                    ("offset", i.offset.into()),
+                    ("line", i.line.into()),
+                    ("op", i.name().into()),
+                ];
+                if let Err(byte) = i.op {
+                    fields.push(("byte", usize::from(byte).into()));
+                }
+                match &i.operand {
+                    Operand::None => {}
+                    Operand::Truncated => fields.push(("truncated", true.into())),
+                    Operand::Constant { index, value } => {
+                        fields.push(("constant", usize::from(*index).into()));
+                        fields.push(("value", value.as_ref().map(|v| v.to_string()).into()));
+                    }
+                    Operand::Slot(slot) => fields.push(("slot", usize::from(*slot).into())),
+                    Operand::Count(count) => fields.push(("count", usize::from(*count).into())),
+                    Operand::Jump(target) => {
+                        fields.push(("target", (*target).into()));
+                        fields.push(("label", self.labels.get(target).map(|l| format!("L{l}")).into()));
+                    }
+                }
+                Json::object(fields)
+            })
+            .collect();
+
+        let labels = self.labels
+            .iter()
+            .map(|(offset, label)| Json::object(vec![
+                ("label", format!("L{label}").into()),
+                ("offset", (*offset).into()),
+            ]))
+            .collect();
+
+        Json::object(vec![
+            ("name", self.name.as_str().into()),
+            ("constants", Json::Array(constants)),
+            ("instructions", Json::Array(instructions)),
+            ("labels", Json::Array(labels)),
+        ])
+    }
+}
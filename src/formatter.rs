@@ -0,0 +1,342 @@
+use std::fmt::{Display, Formatter};
+
+use crate::scanner::tokenize;
+use crate::token::{Token, TokenType};
+
+// A token-level pretty printer. It works from the scanner's stream with
+// comments kept as trivia, so it handles any input that scans and has
+// balanced brackets, including syntax the compiler doesn't support yet.
+//
+// - one statement per line, two spaces of indentation per block
+// - `{` ends its line, `}` gets its own line, `} else` stays together
+// - map literals stay on one line like lists: `{"a": 1, "b": 2}`
+// - no spaces just inside an interpolation: `"${a + b}"`
+// - single spaces around binary operators and after `,` and `;` in headers
+// - runs of blank lines collapse to one; none at the start or end of a block
+// - trailing comments stay on their line, other comments keep their own line
+
+const INDENT: &str = "  ";
+
+/// Why a file couldn't be formatted.
+#[derive(Debug, PartialEq)]
+pub struct FormatError {
+    pub line: usize,
+    pub kind: FormatErrorKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FormatErrorKind {
+    /// The scanner rejected the source.
+    Scan(String),
+    Unmatched(char),
+    Unclosed(char),
+    /// Formatting would have changed the meaning of the code.
+    Changed,
+}
+
+impl Display for FormatErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        use FormatErrorKind::*;
+        match self {
+            Scan(message) => write!(f, "{message}"),
+            Unmatched(c) => write!(f, "unmatched '{c}'"),
+            Unclosed(c) => write!(f, "'{c}' is never closed"),
+            Changed => write!(f, "formatting would change the token stream"),
+        }
+    }
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "[line {}] Error: {}", self.line, self.kind)
+    }
+}
+
+/// The line a token ends on.
+fn end_line(token: &Token) -> usize {
+    token.line + token.lexeme.matches('\n').count()
+}
+
+/// Whether a `-` after `token` negates rather than subtracts.
+fn ends_operand(token: &Token) -> bool {
+    matches!(
+        token.toke,
+        TokenType::Identifier
+            | TokenType::Number
+            | TokenType::String
+            | TokenType::Par
+            | TokenType::Ser
+            | TokenType::True
+            | TokenType::False
+            | TokenType::Nil
+            | TokenType::This
+            | TokenType::Super
+    )
+}
+
+/// Whether `token` is a `-` or `--`, which can't be written next to another.
+fn is_minus(token: &Token) -> bool {
+    matches!(token.toke, TokenType::Hep | TokenType::HepHep)
+}
+
+/// Whether a `{` after `token` opens a map literal rather than a block: it
+/// does wherever an expression is expected.
+fn opens_map(token: Option<&Token>) -> bool {
+    token.is_some_and(|token| {
+        !matches!(
+            token.toke,
+            TokenType::Mic | TokenType::Kel | TokenType::Ker | TokenType::Par | TokenType::Else
+        )
+    })
+}
+
+/// Whether `token` carries on an interpolated string after an embedded
+/// expression.
+fn resumes_string(token: &Token) -> bool {
+    matches!(token.toke, TokenType::String | TokenType::Interpolation) && token.lexeme.starts_with('}')
+}
+
+/// Comments lose trailing whitespace when formatted.
+fn normalize(mut token: Token) -> Token {
+    if token.toke == TokenType::Comment {
+        token.lexeme.truncate(token.lexeme.trim_end().len());
+    }
+    token
+}
+
+struct Printer {
+    out: String,
+    line: String,
+    // indentation of `line`, fixed by its first token
+    indent: usize,
+    depth: usize,
+    // open `(`, `[` and map `{`, with the lines they are on
+    parens: Vec<(char, usize)>,
+    // `parens.len()` at each `?` still waiting for its `:`
+    conditionals: Vec<usize>,
+    // the current line has ended, but a trailing comment may still join it
+    newline_pending: bool,
+    // the current statement was broken by a comment, so indent one more level
+    continuation: bool,
+}
+
+impl Printer {
+    fn push(&mut self, text: &str) {
+        if self.line.is_empty() {
+            self.indent = self.depth + if self.continuation { 1 } else { 0 };
+        }
+        self.line.push_str(text);
+    }
+
+    fn flush(&mut self) {
+        if !self.line.is_empty() {
+            self.out.push_str(&INDENT.repeat(self.indent));
+            self.out.push_str(&self.line);
+            self.line.clear();
+        }
+        self.out.push('\n');
+        self.newline_pending = false;
+    }
+
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    /// Ends the current statement.
+    fn end_statement(&mut self) {
+        self.newline_pending = true;
+        self.continuation = false;
+    }
+}
+
+/// Formats `source`, or explains why it can't be.
+pub fn format(source: &str) -> Result<String, FormatError> {
+    let tokens: Vec<Token> = tokenize(source, true).into_iter().map(normalize).collect();
+    if let Some(error) = tokens.iter().find(|t| t.toke == TokenType::Error) {
+        return Err(FormatError {
+            line: error.line,
+            kind: FormatErrorKind::Scan(error.lexeme.clone()),
+        });
+    }
+
+    let mut p = Printer {
+        out: String::new(),
+        line: String::new(),
+        indent: 0,
+        depth: 0,
+        parens: Vec::new(),
+        conditionals: Vec::new(),
+        newline_pending: false,
+        continuation: false,
+    };
+    let mut prev: Option<&Token> = None;
+    // the last token that isn't a comment
+    let mut prev_code: Option<&Token> = None;
+    // whether `prev` was a prefix operator
+    let mut prev_unary = false;
+    // whether `prev` was a postfix `++` or `--`
+    let mut prev_postfix = false;
+    // whether `prev` opened or closed a map
+    let mut prev_map = false;
+    let mut prev_map_close = false;
+
+    for token in tokens.iter() {
+        let same_line = prev.is_some_and(|prev| end_line(prev) == token.line);
+        let blank_before = prev.is_some_and(|prev| token.line > end_line(prev) + 1);
+
+        // decide whether this token starts a new line
+        if token.toke == TokenType::Comment && same_line {
+            // a trailing comment joins the line it was on
+            if !p.line.is_empty() {
+                p.line.push(' ');
+            }
+            p.push(&token.lexeme);
+            if !p.newline_pending {
+                // the statement carries on after the comment
+                p.flush();
+                p.continuation = true;
+            }
+            prev = Some(token);
+            prev_unary = false;
+            prev_postfix = false;
+            prev_map = false;
+            prev_map_close = false;
+            continue;
+        }
+
+        let joins_brace = token.toke == TokenType::Else
+            && prev.is_some_and(|prev| prev.toke == TokenType::Ker);
+        if joins_brace {
+            p.newline_pending = false;
+        } else if p.newline_pending {
+            p.flush();
+        }
+        let at_line_start = p.line.is_empty();
+        let map_open = token.toke == TokenType::Kel && opens_map(prev_code);
+        let map_close = token.toke == TokenType::Ker
+            && p.parens.last().is_some_and(|&(open, _)| open == '{');
+
+        if token.toke == TokenType::Comment {
+            // a comment on its own line
+            if !at_line_start {
+                p.flush();
+                p.continuation = true;
+            }
+        }
+
+        if map_close {
+            p.parens.pop();
+        } else if token.toke == TokenType::Ker {
+            if !p.parens.is_empty() {
+                return Err(FormatError { line: token.line, kind: FormatErrorKind::Unmatched('}') });
+            }
+            if !at_line_start {
+                p.flush();
+            }
+            if p.depth == 0 {
+                return Err(FormatError { line: token.line, kind: FormatErrorKind::Unmatched('}') });
+            }
+            p.depth -= 1;
+            p.continuation = false;
+        } else if blank_before
+            && p.line.is_empty()
+            && prev.is_some_and(|prev| prev.toke != TokenType::Kel)
+        {
+            p.blank_line();
+        }
+
+        // a `:` ending the then branch of `?` is spaced like an operator,
+        // unlike one in a map entry
+        p.conditionals.retain(|&level| level <= p.parens.len());
+        let else_colon = token.toke == TokenType::Col && p.conditionals.last() == Some(&p.parens.len());
+        if else_colon {
+            p.conditionals.pop();
+        }
+
+        let postfix = matches!(token.toke, TokenType::LusLus | TokenType::HepHep)
+            && (prev.is_some_and(ends_operand) || prev_map_close);
+
+        // spacing within the line
+        if !p.line.is_empty() {
+            let prev = prev.unwrap();
+            let tight = matches!(
+                token.toke,
+                TokenType::Par | TokenType::Ser | TokenType::Mic | TokenType::Com | TokenType::Dot
+            ) || (token.toke == TokenType::Col && !else_colon)
+                || matches!(prev.toke, TokenType::Pal | TokenType::Sel | TokenType::Dot | TokenType::Interpolation)
+                || resumes_string(token)
+                || map_close
+                || prev_map
+                || postfix
+                // `- -x` stays apart, as `--x` would decrement
+                || (prev_unary && !(is_minus(prev) && is_minus(token)))
+                || (matches!(token.toke, TokenType::Pal | TokenType::Sel)
+                    && (ends_operand(prev) || prev_map_close));
+            if !tight {
+                p.line.push(' ');
+            }
+        }
+        p.push(&token.lexeme);
+
+        prev_unary = match token.toke {
+            TokenType::Zap | TokenType::Sig => true,
+            TokenType::LusLus | TokenType::HepHep => !postfix,
+            TokenType::Hep => !prev.is_some_and(ends_operand) && !prev_map_close && !prev_postfix,
+            _ => false,
+        };
+        prev_postfix = postfix;
+
+        match token.toke {
+            TokenType::Kel if map_open => p.parens.push(('{', token.line)),
+            TokenType::Ker if map_close => {}
+            TokenType::Kel => {
+                p.depth += 1;
+                p.end_statement();
+            }
+            TokenType::Ker => p.end_statement(),
+            TokenType::Pal => p.parens.push(('(', token.line)),
+            TokenType::Sel => p.parens.push(('[', token.line)),
+            TokenType::Par if p.parens.pop().is_none_or(|(open, _)| open != '(') => {
+                return Err(FormatError { line: token.line, kind: FormatErrorKind::Unmatched(')') });
+            }
+            TokenType::Ser if p.parens.pop().is_none_or(|(open, _)| open != '[') => {
+                return Err(FormatError { line: token.line, kind: FormatErrorKind::Unmatched(']') });
+            }
+            TokenType::Wut => p.conditionals.push(p.parens.len()),
+            TokenType::Mic if p.parens.is_empty() => {
+                p.conditionals.clear();
+                p.end_statement();
+            }
+            TokenType::Comment => p.newline_pending = true,
+            _ => {}
+        }
+
+        prev = Some(token);
+        if token.toke != TokenType::Comment {
+            prev_code = Some(token);
+        }
+        prev_map = map_open;
+        prev_map_close = map_close;
+    }
+
+    if let Some(&(open, line)) = p.parens.first() {
+        return Err(FormatError { line, kind: FormatErrorKind::Unclosed(open) });
+    }
+    if p.depth > 0 {
+        let line = prev.map_or(1, end_line);
+        return Err(FormatError { line, kind: FormatErrorKind::Unclosed('{') });
+    }
+    if !p.line.is_empty() {
+        p.flush();
+    }
+
+    // whatever happens above, the code itself must come through untouched
+    let formatted: Vec<Token> = tokenize(&p.out, true).into_iter().map(normalize).collect();
+    if formatted != tokens {
+        return Err(FormatError { line: 1, kind: FormatErrorKind::Changed });
+    }
+
+    Ok(p.out)
+}
@@ -0,0 +1,67 @@
+//! Entry points for the fuzz targets in `fuzz/`. They live here so that
+//! `tests/fuzz.rs` can replay the corpus through exactly the same code
+//! without libFuzzer.
+//!
+//! The policy is that no input may panic: whatever the bytes, each of these
+//! returns normally, with errors reported through the usual channels.
+//!
+//! With cargo-fuzz and a nightly toolchain, from the repository root:
+//!
+//! ```text
+//! cargo +nightly fuzz run interpret fuzz/seeds
+//! ```
+
+use std::io;
+
+use crate::chunk::Chunk;
+use crate::compiler::Compiler;
+use crate::scanner::Scanner;
+use crate::token::TokenType;
+use crate::vm::{Limits, VM};
+
+/// Instructions `interpret` runs before giving up on an input.
+pub const INSTRUCTION_BUDGET: usize = 100_000;
+/// Heap bytes `interpret` lets an input allocate, so doubling a string a
+/// few dozen times can't exhaust memory.
+pub const HEAP_BUDGET: usize = 16 << 20;
+
+fn source(data: &[u8]) -> String {
+    String::from_utf8_lossy(data).into_owned()
+}
+
+/// Scans the input to the end, with and without comment tokens.
+pub fn scan_tokens(data: &[u8]) {
+    let source = source(data);
+    for comments in [false, true] {
+        let mut scanner = Scanner::new(&source);
+        scanner.set_comments(comments);
+        // every token consumes at least one character, so this is a bound
+        // on how many the scanner may produce
+        for _ in 0..=source.chars().count() {
+            if scanner.scan_token().toke == TokenType::EOF {
+                break;
+            }
+        }
+    }
+}
+
+/// Compiles the input, discarding the diagnostics.
+pub fn compile(data: &[u8]) {
+    let mut chunk = Chunk::new();
+    let mut compiler = Compiler::new(&source(data), &mut chunk);
+    compiler.set_quiet(true);
+    let _ = compiler.compile();
+}
+
+/// Compiles and runs the input within `INSTRUCTION_BUDGET` and `HEAP_BUDGET`.
+pub fn interpret(data: &[u8]) {
+    let mut vm = VM::new();
+    vm.set_output(Box::new(io::sink()));
+    vm.set_error_output(Box::new(io::sink()));
+    vm.set_limits(Limits {
+        instructions: Some(INSTRUCTION_BUDGET),
+        heap: Some(HEAP_BUDGET),
+        ..Limits::default()
+    });
+    let _ = vm.interpret(&source(data));
+}
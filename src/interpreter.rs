@@ -0,0 +1,325 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::ast::*;
+use crate::chunk::Chunk;
+use crate::compiler::Compiler;
+use crate::natives::{builtins, Capabilities, Context, Native};
+use crate::value::*;
+use crate::vm::InterpretError;
+
+/// A tree-walking evaluator over the same front end and values as `VM`. It
+/// is deliberately simple so it can serve as a reference: for any program
+/// both must print the same output and report the same errors.
+pub struct Interpreter {
+    globals: HashMap<String, Value>,
+    // one frame per block, innermost last
+    scopes: Vec<Vec<(String, Value)>>,
+    output: Box<dyn Write>,
+    errors: Box<dyn Write>,
+    capabilities: Capabilities,
+}
+
+struct RuntimeError {
+    message: String,
+    line: usize,
+    // set when the script called `exit`, which isn't reported as an error
+    exit: Option<i32>,
+}
+
+impl RuntimeError {
+    fn new(message: &str, span: Span) -> Self {
+        Self { message: message.to_string(), line: span.line, exit: None }
+    }
+
+    fn exit(code: i32, span: Span) -> Self {
+        Self { exit: Some(code), ..Self::new("", span) }
+    }
+
+    fn undefined(name: &Identifier) -> Self {
+        Self::new(&format!("Undefined variable {}", name.name), name.span)
+    }
+}
+
+type Result<T> = std::result::Result<T, RuntimeError>;
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::with_capabilities(Capabilities::default())
+    }
+
+    /// Like `VM::with_capabilities`.
+    pub fn with_capabilities(capabilities: Capabilities) -> Self {
+        let mut interpreter = Self {
+            globals: HashMap::new(),
+            scopes: Vec::new(),
+            output: Box::new(io::stdout()),
+            errors: Box::new(io::stderr()),
+            capabilities,
+        };
+        for native in builtins() {
+            interpreter.define_native(native);
+        }
+        interpreter
+    }
+
+    pub fn define_native(&mut self, native: Native) {
+        self.globals.insert(native.name.clone(), Value::obj(Obj::Native(native)));
+    }
+
+    /// Redirects the output of `print` statements, e.g. to capture it.
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    /// Redirects error reports, which go to stderr by default.
+    pub fn set_error_output(&mut self, errors: Box<dyn Write>) {
+        self.errors = errors;
+    }
+
+    pub fn globals(&self) -> &HashMap<String, Value> {
+        &self.globals
+    }
+
+    pub fn interpret(&mut self, source: &str) -> std::result::Result<(), InterpretError> {
+        // the bytecode is thrown away, but compiling reports exactly the
+        // errors the VM would see, including its limits on constants and
+        // locals
+        let mut chunk = Chunk::new();
+        let mut compiler = Compiler::new(source, &mut chunk);
+        compiler.set_quiet(true);
+        let compiled = compiler.compile();
+        for diagnostic in compiler.diagnostics() {
+            let _ = writeln!(self.errors, "{diagnostic}");
+        }
+        compiled?;
+
+        for stmt in compiler.program().statements.iter() {
+            if let Err(e) = self.statement(stmt) {
+                self.scopes.clear();
+                if let Some(code) = e.exit {
+                    return Err(InterpretError::Exit(code));
+                }
+                let _ = writeln!(self.errors, "{}", e.message);
+                let _ = writeln!(self.errors, "[line {}] in script", e.line);
+                return Err(InterpretError::Runtime);
+            }
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<()> {
+        match &stmt.kind {
+            StmtKind::Expression(expr) => {
+                self.expression(expr)?;
+            }
+            StmtKind::Print(expr) => {
+                let value = self.expression(expr)?;
+                let _ = writeln!(self.output, "{}\n", value);
+            }
+            StmtKind::Var { name, initializer } => {
+                let value = match initializer {
+                    Some(expr) => self.expression(expr)?,
+                    None => Value::nil(),
+                };
+                match self.scopes.last_mut() {
+                    Some(scope) => scope.push((name.name.clone(), value)),
+                    None => { self.globals.insert(name.name.clone(), value); }
+                }
+            }
+            StmtKind::Block { body, .. } => {
+                self.scopes.push(Vec::new());
+                let result = body.iter().try_for_each(|stmt| self.statement(stmt));
+                self.scopes.pop();
+                result?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The innermost local called `name`.
+    fn local(&mut self, name: &str) -> Option<&mut Value> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .flat_map(|scope| scope.iter_mut().rev())
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value)
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<Value> {
+        Ok(match &expr.kind {
+            ExprKind::Number(n) => Value::number(*n),
+            ExprKind::Int(n) => Value::int(*n),
+            ExprKind::BigInt(n) => Value::bigint(n.clone()),
+            ExprKind::String(s) => Value::string(s),
+            ExprKind::Bool(b) => Value::boolean(*b),
+            ExprKind::Nil => Value::nil(),
+            ExprKind::Grouping(inner) => self.expression(inner)?,
+            ExprKind::Variable(name) => {
+                if let Some(value) = self.local(&name.name) {
+                    value.clone()
+                } else {
+                    self.globals
+                        .get(&name.name)
+                        .cloned()
+                        .ok_or_else(|| RuntimeError::undefined(name))?
+                }
+            }
+            ExprKind::Assign { name, value } => {
+                let value = self.expression(value)?;
+                if let Some(slot) = self.local(&name.name) {
+                    *slot = value.clone();
+                } else if let Some(slot) = self.globals.get_mut(&name.name) {
+                    *slot = value.clone();
+                } else {
+                    return Err(RuntimeError::undefined(name));
+                }
+                value
+            }
+            ExprKind::Update { target, op, value, postfix } => {
+                // the target is read before the value is evaluated, as the VM
+                // does
+                let (old, new) = match &target.kind {
+                    ExprKind::Variable(name) => {
+                        let old = self.expression(target)?;
+                        let value = self.expression(value)?;
+                        let new = Self::binary(*op, old.clone(), value, expr.span)?;
+                        match self.local(&name.name) {
+                            Some(slot) => *slot = new.clone(),
+                            None => { self.globals.insert(name.name.clone(), new.clone()); }
+                        }
+                        (old, new)
+                    }
+                    ExprKind::Index { object, index } => {
+                        let object = self.expression(object)?;
+                        let index = self.expression(index)?;
+                        let old = object.get_index(&index).map_err(|msg| RuntimeError::new(&msg, expr.span))?;
+                        let value = self.expression(value)?;
+                        let new = Self::binary(*op, old.clone(), value, expr.span)?;
+                        object
+                            .set_index(&index, new.clone())
+                            .map_err(|msg| RuntimeError::new(&msg, expr.span))?;
+                        (old, new)
+                    }
+                    _ => unreachable!("update of something that can't be assigned"),
+                };
+                if *postfix { old } else { new }
+            }
+            ExprKind::Unary { op, operand } => {
+                let value = self.expression(operand)?;
+                match op {
+                    UnaryOp::Not => Value::boolean(value.is_falsey()),
+                    UnaryOp::Negate => negate(&value).map_err(|msg| RuntimeError::new(&msg, expr.span))?,
+                    UnaryOp::BitNot => bit_not(&value).map_err(|msg| RuntimeError::new(&msg, expr.span))?,
+                }
+            }
+            ExprKind::Binary { op, left, right } => {
+                let a = self.expression(left)?;
+                let b = self.expression(right)?;
+                Self::binary(*op, a, b, expr.span)?
+            }
+            ExprKind::Conditional { condition, then_branch, else_branch } => {
+                if self.expression(condition)?.is_falsey() {
+                    self.expression(else_branch)?
+                } else {
+                    self.expression(then_branch)?
+                }
+            }
+            ExprKind::Coalesce { left, right } => {
+                let value = self.expression(left)?;
+                if value.is_nil() { self.expression(right)? } else { value }
+            }
+            ExprKind::Call { callee, arguments } => {
+                let callee = self.expression(callee)?;
+                let args = arguments
+                    .iter()
+                    .map(|argument| self.expression(argument))
+                    .collect::<Result<Vec<_>>>()?;
+                let Some(native) = callee.as_native() else {
+                    return Err(RuntimeError::new("Can only call functions and classes.", expr.span));
+                };
+                let mut context = Context::new(&mut *self.output);
+                let result = native.call(self.capabilities, &mut context, &args);
+                if let Some(code) = context.exit_code() {
+                    return Err(RuntimeError::exit(code, expr.span));
+                }
+                result.map_err(|msg| RuntimeError::new(&msg, expr.span))?
+            }
+            ExprKind::Interpolation(parts) => {
+                let mut text = String::new();
+                for part in parts.iter() {
+                    text.push_str(&self.expression(part)?.to_string());
+                }
+                Value::string(text)
+            }
+            ExprKind::List(items) => Value::list(
+                items.iter().map(|item| self.expression(item)).collect::<Result<Vec<_>>>()?,
+            ),
+            ExprKind::Map(entries) => {
+                let mut map = Map::new();
+                for (key, value) in entries.iter() {
+                    let k = self.expression(key)?;
+                    let v = self.expression(value)?;
+                    map.insert(&k, v).map_err(|msg| RuntimeError::new(&msg, key.span))?;
+                }
+                Value::map(map)
+            }
+            ExprKind::Index { object, index } => {
+                let object = self.expression(object)?;
+                let index = self.expression(index)?;
+                object.get_index(&index).map_err(|msg| RuntimeError::new(&msg, expr.span))?
+            }
+            ExprKind::SetIndex { object, index, value } => {
+                let object = self.expression(object)?;
+                let index = self.expression(index)?;
+                let value = self.expression(value)?;
+                object
+                    .set_index(&index, value.clone())
+                    .map_err(|msg| RuntimeError::new(&msg, expr.span))?;
+                value
+            }
+        })
+    }
+
+    fn binary(op: BinaryOp, a: Value, b: Value, span: Span) -> Result<Value> {
+        let arithmetic = |op| arithmetic(op, &a, &b).map_err(|msg| RuntimeError::new(&msg, span));
+        let ordering = || match (a.is_number() && b.is_number(), compare(&a, &b)) {
+            (true, ordering) => Ok(ordering),
+            (false, _) => Err(RuntimeError::new("Operands must be numbers.", span)),
+        };
+
+        Ok(match op {
+            BinaryOp::Equal => Value::boolean(a == b),
+            BinaryOp::NotEqual => Value::boolean(a != b),
+            BinaryOp::Add => match (a.as_str(), b.as_str()) {
+                (Some(x), Some(y)) => Value::string(format!("{x}{y}")),
+                _ if a.is_number() && b.is_number() => arithmetic(Arith::Add)?,
+                _ => return Err(RuntimeError::new("Operands must be two numbers or two strings.", span)),
+            },
+            BinaryOp::Sub => arithmetic(Arith::Sub)?,
+            BinaryOp::Mul => arithmetic(Arith::Mul)?,
+            BinaryOp::Div => arithmetic(Arith::Div)?,
+            BinaryOp::FloorDiv => arithmetic(Arith::FloorDiv)?,
+            BinaryOp::Mod => arithmetic(Arith::Mod)?,
+            BinaryOp::Pow => arithmetic(Arith::Pow)?,
+            BinaryOp::BitAnd => arithmetic(Arith::BitAnd)?,
+            BinaryOp::BitOr => arithmetic(Arith::BitOr)?,
+            BinaryOp::BitXor => arithmetic(Arith::BitXor)?,
+            BinaryOp::ShiftLeft => arithmetic(Arith::ShiftLeft)?,
+            BinaryOp::ShiftRight => arithmetic(Arith::ShiftRight)?,
+            BinaryOp::Greater => Value::boolean(ordering()? == Some(Ordering::Greater)),
+            // like the VM's `a < b` then OP_NOT, so NaN compares true
+            BinaryOp::GreaterEqual => Value::boolean(ordering()? != Some(Ordering::Less)),
+            BinaryOp::Less => Value::boolean(ordering()? == Some(Ordering::Less)),
+            BinaryOp::LessEqual => Value::boolean(ordering()? != Some(Ordering::Greater)),
+        })
+    }
+}
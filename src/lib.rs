@@ -0,0 +1,28 @@
+pub mod ast;
+pub mod chunk;
+pub mod codegen;
+pub mod dap;
+pub mod debugger;
+pub mod disassembler;
+pub mod formatter;
+pub mod fuzz;
+pub mod interpreter;
+pub mod json;
+pub mod lsp;
+pub mod natives;
+mod protocol;
+pub mod serialize;
+pub mod value;
+pub mod vm;
+pub mod compiler;
+pub mod parser;
+pub mod token;
+pub mod scanner;
+pub mod verifier;
+
+/// The most values a single chunk may keep on the stack, since local slots
+/// are one byte. The verifier enforces this; `vm::Limits` bounds the stack as
+/// a whole at run time.
+pub const STACK_MAX: usize = 256;
+/// Default call depth, see `vm::Limits`.
+pub const FRAMES_MAX: usize = 64;
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use crate::chunk::Chunk;
+use crate::compiler::{Compiler, Declaration, Diagnostic, Symbols};
+use crate::json::Json;
+use crate::natives::builtins;
+use crate::protocol::{read_message, write_message};
+use crate::scanner::tokenize;
+use crate::token::{TokenCategory, TokenType};
+
+// Language Server Protocol over a pair of streams. Documents are synced in
+// full and recompiled on every change. Positions are 0-based lines and
+// columns in UTF-16 code units, the protocol's default; the compiler's lines
+// are 1-based and its columns count chars, so positions are converted on the
+// way in and out.
+
+const KEYWORDS: &[&str] = &[
+    "and", "class", "else", "false", "for", "fun", "if", "nil", "or", "print",
+    "return", "super", "this", "true", "var", "while",
+];
+
+const SYNC_FULL: usize = 1;
+const SEVERITY_ERROR: usize = 1;
+const SYMBOL_VARIABLE: usize = 13;
+const COMPLETION_FUNCTION: usize = 3;
+const COMPLETION_VARIABLE: usize = 6;
+const COMPLETION_KEYWORD: usize = 14;
+const METHOD_NOT_FOUND: i32 = -32601;
+
+// the semantic token legend, in the order the client is told about it
+const SEMANTIC_TOKEN_TYPES: &[(&str, TokenCategory)] = &[
+    ("keyword", TokenCategory::Keyword),
+    ("variable", TokenCategory::Identifier),
+    ("string", TokenCategory::String),
+    ("number", TokenCategory::Number),
+    ("operator", TokenCategory::Operator),
+    ("comment", TokenCategory::Comment),
+];
+
+/// An open document and what the compiler found in it.
+struct Document {
+    text: String,
+    // the text split at each `\n`, as the scanner counts lines
+    lines: Vec<String>,
+    chunk: Chunk,
+    symbols: Symbols,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        let mut chunk = Chunk::new();
+        let (symbols, diagnostics) = {
+            let mut compiler = Compiler::new(&text, &mut chunk);
+            compiler.set_quiet(true);
+            let _ = compiler.compile();
+            (compiler.symbols().clone(), compiler.diagnostics())
+        };
+        let lines = text.split('\n').map(str::to_string).collect();
+        Self { text, lines, chunk, symbols, diagnostics }
+    }
+
+    fn line(&self, line: usize) -> &str {
+        self.lines.get(line.wrapping_sub(1)).map_or("", String::as_str)
+    }
+
+    /// The UTF-16 column of the char `column` on 1-based `line`. Columns past
+    /// the end of the line count one unit per char.
+    fn utf16_column(&self, line: usize, column: usize) -> usize {
+        let (mut units, mut chars) = (0, 0);
+        for c in self.line(line).chars().take(column) {
+            units += c.len_utf16();
+            chars += 1;
+        }
+        units + (column - chars)
+    }
+
+    /// The char column at UTF-16 column `units` on 1-based `line`. A column
+    /// inside a surrogate pair moves on to the next char.
+    fn char_column(&self, line: usize, units: usize) -> usize {
+        let mut seen = 0;
+        let mut chars = 0;
+        for c in self.line(line).chars() {
+            if seen >= units {
+                return chars;
+            }
+            seen += c.len_utf16();
+            chars += 1;
+        }
+        chars + units.saturating_sub(seen)
+    }
+
+    fn position(&self, line: usize, column: usize) -> Json {
+        Json::object(vec![
+            ("line", line.saturating_sub(1).into()),
+            ("character", self.utf16_column(line, column).into()),
+        ])
+    }
+
+    fn range(&self, line: usize, column: usize, len: usize) -> Json {
+        Json::object(vec![
+            ("start", self.position(line, column)),
+            ("end", self.position(line, column + len)),
+        ])
+    }
+
+    fn declaration_range(&self, declaration: &Declaration) -> Json {
+        self.range(declaration.line, declaration.column, declaration.name.chars().count())
+    }
+
+    /// The declaration under an LSP position.
+    fn declaration_at(&self, position: &Json) -> Option<&Declaration> {
+        let line = position.get("line").as_usize()? + 1;
+        let column = self.char_column(line, position.get("character").as_usize()?);
+        self.symbols.declaration_at(line, column)
+    }
+
+    /// Every identifier in the source, declared or not.
+    fn identifiers(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for token in tokenize(&self.text, false) {
+            if token.toke == TokenType::Identifier && !names.contains(&token.lexeme) {
+                names.push(token.lexeme);
+            }
+        }
+        names
+    }
+
+    /// `textDocument/semanticTokens/full` data: five numbers per token, each
+    /// position relative to the one before. Tokens spanning lines are split
+    /// into one token per line.
+    fn semantic_tokens(&self) -> Json {
+        let mut data = Vec::new();
+        let (mut last_line, mut last_column) = (0, 0);
+        for token in tokenize(&self.text, true) {
+            let Some(kind) = token.toke
+                .category()
+                .and_then(|c| SEMANTIC_TOKEN_TYPES.iter().position(|&(_, t)| t == c))
+            else {
+                continue;
+            };
+
+            // error tokens are skipped above, so the lexeme is the source text
+            for (i, part) in token.lexeme.split('\n').enumerate() {
+                let line = token.line - 1 + i;
+                let column = if i == 0 { self.utf16_column(token.line, token.column) } else { 0 };
+                let len = part.trim_end_matches('\r').encode_utf16().count();
+                if len == 0 {
+                    continue;
+                }
+
+                let delta_column = if line == last_line { column - last_column } else { column };
+                data.extend([line - last_line, delta_column, len, kind, 0]);
+                (last_line, last_column) = (line, column);
+            }
+        }
+        Json::object(vec![("data", Json::Array(data.into_iter().map(Json::from).collect()))])
+    }
+}
+
+struct Server<W> {
+    writer: W,
+    documents: HashMap<String, Document>,
+}
+
+impl<W: Write> Server<W> {
+    fn send(&mut self, mut fields: Vec<(&str, Json)>) -> io::Result<()> {
+        fields.insert(0, ("jsonrpc", "2.0".into()));
+        write_message(&mut self.writer, &Json::object(fields))
+    }
+
+    fn respond(&mut self, request: &Json, result: Json) -> io::Result<()> {
+        self.send(vec![("id", request.get("id").clone()), ("result", result)])
+    }
+
+    fn respond_error(&mut self, request: &Json, code: i32, message: &str) -> io::Result<()> {
+        self.send(vec![
+            ("id", request.get("id").clone()),
+            ("error", Json::object(vec![
+                ("code", Json::Number(code as f64)),
+                ("message", message.into()),
+            ])),
+        ])
+    }
+
+    fn notify(&mut self, method: &str, params: Json) -> io::Result<()> {
+        self.send(vec![("method", method.into()), ("params", params)])
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> io::Result<()> {
+        let diagnostics = match self.documents.get(uri) {
+            Some(document) => document.diagnostics
+                .iter()
+                .map(|d| Json::object(vec![
+                    ("range", document.range(d.line, d.column, d.len)),
+                    ("severity", SEVERITY_ERROR.into()),
+                    ("source", "rlox".into()),
+                    ("message", format!("Error{}: {}", d.location, d.message).into()),
+                ]))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        self.notify("textDocument/publishDiagnostics", Json::object(vec![
+            ("uri", uri.into()),
+            ("diagnostics", Json::Array(diagnostics)),
+        ]))
+    }
+
+    fn open(&mut self, params: &Json, text: Option<&str>) -> io::Result<()> {
+        let Some(uri) = params.get("textDocument").get("uri").as_str() else {
+            return Ok(());
+        };
+        match text {
+            Some(text) => {
+                self.documents.insert(uri.to_string(), Document::new(text.to_string()));
+            }
+            None => {
+                self.documents.remove(uri);
+            }
+        }
+        self.publish_diagnostics(uri)
+    }
+
+    fn definition(&self, params: &Json) -> Json {
+        let uri = params.get("textDocument").get("uri");
+        let Some(document) = uri.as_str().and_then(|uri| self.documents.get(uri)) else {
+            return Json::Null;
+        };
+        match document.declaration_at(params.get("position")) {
+            Some(declaration) => Json::object(vec![
+                ("uri", uri.clone()),
+                ("range", document.declaration_range(declaration)),
+            ]),
+            None => Json::Null,
+        }
+    }
+
+    fn hover(&self, params: &Json) -> Json {
+        let uri = params.get("textDocument").get("uri");
+        let Some(document) = uri.as_str().and_then(|uri| self.documents.get(uri)) else {
+            return Json::Null;
+        };
+        let Some(declaration) = document.declaration_at(params.get("position")) else {
+            return Json::Null;
+        };
+
+        let detail = match declaration.local.map(|i| &document.chunk.locals()[i]) {
+            Some(local) => format!("local variable, slot {}", local.slot),
+            None => "global variable".to_string(),
+        };
+        let value = format!(
+            "```lox\nvar {}\n```\n{detail}, declared on line {}",
+            declaration.name, declaration.line
+        );
+
+        Json::object(vec![(
+            "contents",
+            Json::object(vec![("kind", "markdown".into()), ("value", value.into())]),
+        )])
+    }
+
+    fn document_symbols(&self, params: &Json) -> Json {
+        let uri = params.get("textDocument").get("uri");
+        let Some(document) = uri.as_str().and_then(|uri| self.documents.get(uri)) else {
+            return Json::Array(Vec::new());
+        };
+
+        let symbols = document.symbols.declarations
+            .iter()
+            .map(|d| Json::object(vec![
+                ("name", d.name.as_str().into()),
+                ("kind", SYMBOL_VARIABLE.into()),
+                ("location", Json::object(vec![
+                    ("uri", uri.clone()),
+                    ("range", document.declaration_range(d)),
+                ])),
+            ]))
+            .collect();
+        Json::Array(symbols)
+    }
+
+    fn semantic_tokens(&self, params: &Json) -> Json {
+        let uri = params.get("textDocument").get("uri");
+        match uri.as_str().and_then(|uri| self.documents.get(uri)) {
+            Some(document) => document.semantic_tokens(),
+            None => Json::Null,
+        }
+    }
+
+    fn completion(&self, params: &Json) -> Json {
+        let item = |label: &str, kind: usize| Json::object(vec![
+            ("label", label.into()),
+            ("kind", kind.into()),
+        ]);
+
+        let mut items: Vec<Json> = KEYWORDS.iter().map(|k| item(k, COMPLETION_KEYWORD)).collect();
+        items.extend(builtins().iter().map(|native| item(&native.name, COMPLETION_FUNCTION)));
+        if let Some(document) = params
+            .get("textDocument")
+            .get("uri")
+            .as_str()
+            .and_then(|uri| self.documents.get(uri))
+        {
+            items.extend(document.identifiers().iter().map(|name| item(name, COMPLETION_VARIABLE)));
+        }
+        Json::Array(items)
+    }
+}
+
+fn capabilities() -> Json {
+    Json::object(vec![
+        ("capabilities", Json::object(vec![
+            ("textDocumentSync", SYNC_FULL.into()),
+            ("definitionProvider", true.into()),
+            ("hoverProvider", true.into()),
+            ("documentSymbolProvider", true.into()),
+            ("completionProvider", Json::object(Vec::<(&str, Json)>::new())),
+            ("semanticTokensProvider", Json::object(vec![
+                ("legend", Json::object(vec![
+                    ("tokenTypes", Json::Array(SEMANTIC_TOKEN_TYPES.iter().map(|&(t, _)| t.into()).collect())),
+                    ("tokenModifiers", Json::Array(Vec::new())),
+                ])),
+                ("full", true.into()),
+            ])),
+        ])),
+        ("serverInfo", Json::object(vec![("name", "rlox".into())])),
+    ])
+}
+
+/// Serves one client until it sends `exit` or closes the stream. Returns
+/// whether `shutdown` was requested first, which decides the exit code.
+pub fn serve<R: BufRead, W: Write>(mut reader: R, writer: W) -> io::Result<bool> {
+    let mut server = Server { writer, documents: HashMap::new() };
+    let mut shut_down = false;
+
+    while let Some(message) = read_message(&mut reader)? {
+        let params = message.get("params");
+        let is_request = message.get("id") != &Json::Null;
+
+        match message.get("method").as_str().unwrap_or("") {
+            "initialize" => server.respond(&message, capabilities())?,
+            "shutdown" => {
+                shut_down = true;
+                server.respond(&message, Json::Null)?;
+            }
+            "exit" => return Ok(shut_down),
+            "textDocument/didOpen" => {
+                server.open(params, params.get("textDocument").get("text").as_str())?
+            }
+            "textDocument/didChange" => {
+                // full sync: the last change holds the whole document
+                let changes = params.get("contentChanges").as_array().unwrap_or(&[]);
+                if let Some(text) = changes.last().and_then(|c| c.get("text").as_str()) {
+                    server.open(params, Some(text))?;
+                }
+            }
+            "textDocument/didClose" => server.open(params, None)?,
+            "textDocument/definition" => {
+                let result = server.definition(params);
+                server.respond(&message, result)?;
+            }
+            "textDocument/hover" => {
+                let result = server.hover(params);
+                server.respond(&message, result)?;
+            }
+            "textDocument/documentSymbol" => {
+                let result = server.document_symbols(params);
+                server.respond(&message, result)?;
+            }
+            "textDocument/semanticTokens/full" => {
+                let result = server.semantic_tokens(params);
+                server.respond(&message, result)?;
+            }
+            "textDocument/completion" => {
+                let result = server.completion(params);
+                server.respond(&message, result)?;
+            }
+            method if is_request => {
+                server.respond_error(&message, METHOD_NOT_FOUND, &format!("unsupported method '{method}'"))?
+            }
+            // other notifications, e.g. `initialized`, need no answer
+            _ => {}
+        }
+    }
+
+    Ok(shut_down)
+}
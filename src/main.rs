@@ -1,54 +1,240 @@
-#![allow(dead_code)]
-
-mod chunk;
-mod value;
-mod vm;
-mod compiler;
-mod token;
-mod scanner;
-
 use std::env;
 use std::env::args;
 use std::io;
-use vm::*;
+use rlox::chunk::Chunk;
+use rlox::compiler::Compiler;
+use rlox::debugger::{ConsoleDebugger, Debugger};
+use rlox::disassembler::disassemble;
+use rlox::formatter::format;
+use rlox::json::Json;
+use rlox::natives::Capabilities;
+use rlox::scanner::tokenize;
+use rlox::serialize::{read_chunk, write_chunk};
+use rlox::token::Token;
+use rlox::vm::*;
 
 use text_io::*;
 
-const STACK_MAX: usize = 256;
+const USAGE: &str = "\
+Usage: rlox [path]
+       rlox compile <file.lox> [-o <file.loxc>]
+       rlox disasm [--json] <file.lox|file.loxc>
+       rlox tokens <file.lox>
+       rlox fmt [--check] <file.lox>...
+       rlox debug <file.lox>
+       rlox dap
+       rlox lsp";
 
 fn main() {
     env::set_var("RUST_BACKTRACE", "1");
     let args: Vec<String> = args().collect();
-    let mut vm: VM = VM::new();
+    // the command line trusts the scripts it is given
+    let mut vm: VM = VM::with_capabilities(Capabilities::ALL);
 
-    if args.len() == 1 {
-        repl(&mut vm);
-    } else if args.len() == 2 {
-        run_file(&mut vm, &args[1]);
-    } else {
-        eprintln!("Usage: rlox [path]");
-        std::process::exit(64);
+    match args.get(1).map(String::as_str) {
+        None => repl(&mut vm),
+        Some("compile") => compile_file(&args[2..]),
+        Some("disasm") => disasm(&args[2..]),
+        Some("tokens") if args.len() == 3 => tokens(&args[2]),
+        Some("fmt") => fmt(&args[2..]),
+        Some("debug") if args.len() == 3 => debug(&mut vm, &args[2]),
+        Some("dap") if args.len() == 2 => {
+            if let Err(e) = rlox::dap::serve(io::stdin().lock(), io::stdout()) {
+                eprintln!("dap: {e}");
+                std::process::exit(74);
+            }
+        }
+        Some("lsp") if args.len() == 2 => {
+            match rlox::lsp::serve(io::stdin().lock(), io::stdout()) {
+                Ok(shut_down) => std::process::exit(if shut_down { 0 } else { 1 }),
+                Err(e) => {
+                    eprintln!("lsp: {e}");
+                    std::process::exit(74);
+                }
+            }
+        }
+        Some(path) if args.len() == 2 => {
+            if let Err(e) = run_file(&mut vm, path) {
+                eprintln!("Could not read file \"{path}\": {e}");
+                std::process::exit(74);
+            }
+        }
+        _ => usage(),
     }
 
     vm.free();
 }
 
+fn usage() -> ! {
+    eprintln!("{USAGE}");
+    std::process::exit(64);
+}
+
 fn repl(vm: &mut VM) {
     loop {
         print!("> ");
 
         let line: String = read!("{}\n");
-        vm.interpret(&line);
+        // errors have already been reported, keep reading lines
+        if let Err(InterpretError::Exit(code)) = vm.interpret(&line) {
+            std::process::exit(code);
+        }
     }
 }
 
 fn run_file(vm: &mut VM, path: &str) -> io::Result<()> {
-    let source = std::fs::read_to_string(path.to_string())?;
-    let result = vm.interpret(&source);
+    let result = if path.ends_with(".loxc") {
+        let chunk = load_chunk(path)?;
+        vm.interpret_chunk(&chunk)
+    } else {
+        let source = std::fs::read_to_string(path)?;
+        vm.interpret(&source)
+    };
 
     match result {
         Err(InterpretError::Compile) => std::process::exit(65),
-        Err(InterpretError::Runtime) => std::process::exit(70),
+        Err(InterpretError::Verify) => std::process::exit(65),
+        Err(InterpretError::Exit(code)) => std::process::exit(code),
+        Err(_) => std::process::exit(70),
         Ok(_) => std::process::exit(0),
     };
 }
+
+/// Reads a .loxc file, or compiles a .lox file. Exits on compile errors.
+fn load_chunk(path: &str) -> io::Result<Chunk> {
+    if path.ends_with(".loxc") {
+        let bytes = std::fs::read(path)?;
+        return Ok(read_chunk(&bytes).unwrap_or_else(|e| {
+            eprintln!("{path}: {e}");
+            std::process::exit(65);
+        }));
+    }
+
+    let source = std::fs::read_to_string(path)?;
+    let mut chunk = Chunk::new();
+    if Compiler::new(&source, &mut chunk).compile().is_err() {
+        std::process::exit(65);
+    }
+    Ok(chunk)
+}
+
+fn read_or_exit(path: &str) -> Chunk {
+    load_chunk(path).unwrap_or_else(|e| {
+        eprintln!("Could not read file \"{path}\": {e}");
+        std::process::exit(74);
+    })
+}
+
+fn compile_file(args: &[String]) {
+    let (input, output) = match args {
+        [input] => (input, format!("{}.loxc", input.strip_suffix(".lox").unwrap_or(input))),
+        [input, flag, output] if flag == "-o" => (input, output.clone()),
+        _ => usage(),
+    };
+
+    let chunk = read_or_exit(input);
+    let bytes = write_chunk(&chunk).unwrap_or_else(|e| {
+        eprintln!("{input}: {e}");
+        std::process::exit(65);
+    });
+
+    if let Err(e) = std::fs::write(&output, bytes) {
+        eprintln!("Could not write file \"{output}\": {e}");
+        std::process::exit(74);
+    }
+}
+
+fn disasm(args: &[String]) {
+    let (path, json) = match args {
+        [path] => (path, false),
+        [flag, path] if flag == "--json" => (path, true),
+        _ => usage(),
+    };
+
+    let chunk = read_or_exit(path);
+    let disassembly = disassemble(&chunk, path);
+    if json {
+        println!("{}", disassembly.to_json());
+    } else {
+        print!("{disassembly}");
+    }
+}
+
+fn tokens(path: &str) {
+    let source = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Could not read file \"{path}\": {e}");
+        std::process::exit(74);
+    });
+
+    let tokens = tokenize(&source, true).iter().map(Token::to_json).collect();
+    println!("{}", Json::Array(tokens));
+}
+
+/// Formats files in place. With `--check` nothing is written, and the exit
+/// status says whether any file needs formatting.
+fn fmt(args: &[String]) {
+    let (check, paths) = match args {
+        [flag, paths @ ..] if flag == "--check" => (true, paths),
+        paths => (false, paths),
+    };
+    if paths.is_empty() {
+        usage();
+    }
+
+    let mut unformatted = false;
+    let mut failed = false;
+    for path in paths {
+        let source = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Could not read file \"{path}\": {e}");
+            std::process::exit(74);
+        });
+
+        match format(&source) {
+            Ok(formatted) if formatted == source => {}
+            Ok(_) if check => {
+                println!("{path}");
+                unformatted = true;
+            }
+            Ok(formatted) => {
+                if let Err(e) = std::fs::write(path, formatted) {
+                    eprintln!("Could not write file \"{path}\": {e}");
+                    std::process::exit(74);
+                }
+            }
+            Err(e) => {
+                eprintln!("{path}: {e}");
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(65);
+    }
+    if unformatted {
+        std::process::exit(1);
+    }
+}
+
+fn debug(vm: &mut VM, path: &str) {
+    let source = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Could not read file \"{path}\": {e}");
+        std::process::exit(74);
+    });
+
+    let console = ConsoleDebugger::new(&source);
+    vm.set_debugger(Some(Debugger::new(Box::new(console), true)));
+
+    let result = vm.interpret(&source);
+    vm.set_debugger(None);
+
+    match result {
+        Ok(_) => println!("Program finished."),
+        // quit at the prompt
+        Err(InterpretError::Interrupted) => {}
+        Err(InterpretError::Compile) => std::process::exit(65),
+        Err(InterpretError::Verify) => std::process::exit(65),
+        Err(InterpretError::Exit(code)) => std::process::exit(code),
+        Err(_) => std::process::exit(70),
+    }
+}
@@ -0,0 +1,289 @@
+use std::cell::RefCell;
+use std::fmt::{Debug, Formatter};
+use std::io::Write;
+use std::ops::BitOr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::value::*;
+
+/// What the natives a script calls are allowed to touch. An embedder picks
+/// the set when building a `VM` and every native declares the one it needs;
+/// calling a native whose capability wasn't granted is a runtime error.
+///
+/// `print` statements aren't natives and always write to the VM's output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    pub const NONE: Self = Self(0);
+    pub const STDOUT: Self = Self(1 << 0);
+    pub const FS_READ: Self = Self(1 << 1);
+    pub const FS_WRITE: Self = Self(1 << 2);
+    pub const CLOCK: Self = Self(1 << 3);
+    pub const ENV: Self = Self(1 << 4);
+    pub const PROCESS: Self = Self(1 << 5);
+    pub const ALL: Self = Self((1 << 6) - 1);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The names of the capabilities in the set, e.g. for error messages.
+    pub fn names(self) -> Vec<&'static str> {
+        [
+            (Self::STDOUT, "stdout"),
+            (Self::FS_READ, "fs-read"),
+            (Self::FS_WRITE, "fs-write"),
+            (Self::CLOCK, "clock"),
+            (Self::ENV, "env"),
+            (Self::PROCESS, "process"),
+        ]
+        .into_iter()
+        .filter(|&(c, _)| self.contains(c))
+        .map(|(_, name)| name)
+        .collect()
+    }
+}
+
+/// Output and timing only; nothing that reaches the filesystem, the
+/// environment or other processes.
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::STDOUT | Self::CLOCK
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+/// A native gets the caller's context and its arguments, and returns its
+/// result or the message for a runtime error.
+pub type NativeFn = fn(&mut Context, &[Value]) -> Result<Value, String>;
+
+/// What a native can reach of the VM calling it.
+pub struct Context<'a> {
+    output: &'a mut dyn Write,
+    allocated: usize,
+    exit: Option<i32>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(output: &'a mut dyn Write) -> Self {
+        Self { output, allocated: 0, exit: None }
+    }
+
+    /// Where `print` writes.
+    pub fn output(&mut self) -> &mut dyn Write {
+        self.output
+    }
+
+    /// Records heap bytes the native allocated, which the VM counts against
+    /// its heap limit once the call returns.
+    pub fn allocate(&mut self, bytes: usize) {
+        self.allocated = self.allocated.saturating_add(bytes);
+    }
+
+    pub fn allocated(&self) -> usize {
+        self.allocated
+    }
+
+    /// Asks for the script to end with `code` once the native returns. The
+    /// run stops with `InterpretError::Exit`; exiting the process, if at all,
+    /// is up to the embedder.
+    pub fn exit(&mut self, code: i32) {
+        self.exit = Some(code);
+    }
+
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit
+    }
+}
+
+/// A function implemented in Rust, stored in a global like any other value.
+pub struct Native {
+    pub name: String,
+    pub arity: usize,
+    /// What calling it requires.
+    pub capability: Capabilities,
+    pub function: NativeFn,
+}
+
+impl Native {
+    pub fn new(name: &str, arity: usize, capability: Capabilities, function: NativeFn) -> Self {
+        Self { name: name.to_string(), arity, capability, function }
+    }
+
+    /// Checks the arguments and capability, then runs the function.
+    pub fn call(&self, granted: Capabilities, context: &mut Context, args: &[Value]) -> Result<Value, String> {
+        if args.len() != self.arity {
+            return Err(format!("Expected {} arguments but got {}.", self.arity, args.len()));
+        }
+        if !granted.contains(self.capability) {
+            return Err(format!(
+                "{}() needs the {} capability, which was not granted.",
+                self.name,
+                self.capability.names().join(", ")
+            ));
+        }
+        (self.function)(context, args)
+    }
+}
+
+impl Debug for Native {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "Native({})", self.name)
+    }
+}
+
+// natives are compared by what they are called and need, since function
+// pointers have no reliable identity
+impl PartialEq for Native {
+    fn eq(&self, other: &Native) -> bool {
+        self.name == other.name && self.arity == other.arity && self.capability == other.capability
+    }
+}
+
+/// The natives every VM starts with. They are all installed whatever the
+/// capabilities, so a denied call gets a clear error rather than looking
+/// like an undefined variable.
+pub fn builtins() -> Vec<Native> {
+    vec![
+        Native::new("clock", 0, Capabilities::CLOCK, clock),
+        Native::new("write", 1, Capabilities::STDOUT, write),
+        Native::new("readFile", 1, Capabilities::FS_READ, read_file),
+        Native::new("writeFile", 2, Capabilities::FS_WRITE, write_file),
+        Native::new("getEnv", 1, Capabilities::ENV, get_env),
+        Native::new("exit", 1, Capabilities::PROCESS, exit),
+        Native::new("len", 1, Capabilities::NONE, len),
+        Native::new("push", 2, Capabilities::NONE, push),
+        Native::new("pop", 1, Capabilities::NONE, pop),
+        Native::new("slice", 3, Capabilities::NONE, slice),
+        Native::new("keys", 1, Capabilities::NONE, keys),
+        Native::new("has", 2, Capabilities::NONE, has),
+        Native::new("remove", 2, Capabilities::NONE, remove),
+    ]
+}
+
+fn string_arg<'a>(args: &'a [Value], i: usize, what: &str) -> Result<&'a str, String> {
+    args[i].as_str().ok_or_else(|| format!("{what} must be a string."))
+}
+
+fn list_arg(args: &[Value], i: usize) -> Result<&RefCell<Vec<Value>>, String> {
+    args[i].as_list().ok_or_else(|| format!("Expected a list but got {}.", args[i].type_name()))
+}
+
+fn map_arg(args: &[Value], i: usize) -> Result<&RefCell<Map>, String> {
+    args[i].as_map().ok_or_else(|| format!("Expected a map but got {}.", args[i].type_name()))
+}
+
+/// Seconds since the Unix epoch.
+fn clock(_: &mut Context, _: &[Value]) -> Result<Value, String> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok(Value::number(now.as_secs_f64()))
+}
+
+/// Like `print` but without the newline.
+fn write(context: &mut Context, args: &[Value]) -> Result<Value, String> {
+    write!(context.output(), "{}", args[0]).map_err(|e| format!("Could not write: {e}."))?;
+    Ok(Value::nil())
+}
+
+fn read_file(context: &mut Context, args: &[Value]) -> Result<Value, String> {
+    let path = string_arg(args, 0, "Path")?;
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read file \"{path}\": {e}."))?;
+    context.allocate(Obj::str_size(contents.len()));
+    Ok(Value::string(contents))
+}
+
+fn write_file(_: &mut Context, args: &[Value]) -> Result<Value, String> {
+    let path = string_arg(args, 0, "Path")?;
+    let contents = string_arg(args, 1, "Contents")?;
+    std::fs::write(path, contents).map_err(|e| format!("Could not write file \"{path}\": {e}."))?;
+    Ok(Value::nil())
+}
+
+/// The variable's value, or nil if it isn't set.
+fn get_env(context: &mut Context, args: &[Value]) -> Result<Value, String> {
+    let name = string_arg(args, 0, "Name")?;
+    match std::env::var(name) {
+        Ok(value) => {
+            context.allocate(Obj::str_size(value.len()));
+            Ok(Value::string(value))
+        }
+        Err(_) => Ok(Value::nil()),
+    }
+}
+
+/// Ends the script, but not the process running it; see `Context::exit`.
+fn exit(context: &mut Context, args: &[Value]) -> Result<Value, String> {
+    let code = args[0].as_number().ok_or("Exit code must be a number.")?;
+    let _ = context.output().flush();
+    context.exit(code as i32);
+    Ok(Value::nil())
+}
+
+/// The number of elements in a list, entries in a map, or characters in a
+/// string.
+fn len(_: &mut Context, args: &[Value]) -> Result<Value, String> {
+    if let Some(s) = args[0].as_str() {
+        return Ok(Value::int(s.chars().count() as i64));
+    }
+    if let Some(map) = args[0].as_map() {
+        return Ok(Value::int(map.borrow().len() as i64));
+    }
+    Ok(Value::int(list_arg(args, 0)?.borrow().len() as i64))
+}
+
+/// Appends to a list in place.
+fn push(context: &mut Context, args: &[Value]) -> Result<Value, String> {
+    list_arg(args, 0)?.borrow_mut().push(args[1].clone());
+    context.allocate(std::mem::size_of::<Value>());
+    Ok(Value::nil())
+}
+
+/// Removes and returns the last element of a list.
+fn pop(_: &mut Context, args: &[Value]) -> Result<Value, String> {
+    list_arg(args, 0)?.borrow_mut().pop().ok_or_else(|| "Can't pop from an empty list.".to_string())
+}
+
+/// A new list of the elements from `start` up to but not including `end`.
+fn slice(context: &mut Context, args: &[Value]) -> Result<Value, String> {
+    let items = list_arg(args, 0)?.borrow();
+    let bound = |i: usize| {
+        let n = args[i].as_number().ok_or("Slice bounds must be numbers.")?;
+        if n.fract() != 0.0 || n < 0.0 || n > items.len() as f64 {
+            return Err(format!("Slice bound {n} is out of range for length {}.", items.len()));
+        }
+        Ok(n as usize)
+    };
+    let (start, end) = (bound(1)?, bound(2)?);
+    if start > end {
+        return Err(format!("Slice start {start} is after its end {end}."));
+    }
+
+    context.allocate(Obj::list_size(end - start));
+    Ok(Value::list(items[start..end].to_vec()))
+}
+
+/// A new list of a map's keys, in the order they were first added.
+fn keys(context: &mut Context, args: &[Value]) -> Result<Value, String> {
+    let map = map_arg(args, 0)?.borrow();
+    context.allocate(Obj::list_size(map.len()));
+    Ok(Value::list(map.keys().cloned().collect()))
+}
+
+/// Whether a map has an entry for the key.
+fn has(_: &mut Context, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::boolean(map_arg(args, 0)?.borrow().get(&args[1])?.is_some()))
+}
+
+/// Removes and returns the value for a key.
+fn remove(_: &mut Context, args: &[Value]) -> Result<Value, String> {
+    map_arg(args, 0)?.borrow_mut().remove(&args[1])?.ok_or_else(|| undefined_key(&args[1]))
+}
@@ -0,0 +1,818 @@
+use crate::ast::*;
+use crate::compiler::Diagnostic;
+use crate::scanner::*;
+use crate::token::*;
+use int_enum::IntEnum;
+
+/// How deeply expressions and blocks may nest. Parsing, code generation and
+/// the tree-walker all recurse over the nesting, so this keeps hostile input
+/// from overflowing the native stack.
+const MAX_NESTING: usize = 256;
+
+/// Builds a `Program` from source with a Pratt parser. Errors are collected
+/// rather than printed; parsing carries on after each one so a single pass
+/// reports as many as it can.
+pub struct Parser {
+    scanner: Scanner,
+    current: Token,
+    previous: Token,
+    rules: Vec<ParseRule>,
+    had_error: bool,
+    panic_mode: bool,
+    diagnostics: Vec<Diagnostic>,
+    // expressions and blocks currently open
+    depth: usize,
+    // the block just parsed was a map literal where a statement belongs
+    map_statement: bool,
+}
+
+#[repr(usize)]
+#[derive(PartialEq, PartialOrd, Copy, Clone, IntEnum)]
+enum Precedence {
+    None        = 0,
+    Assignment  = 1,  // = += -= *= /= %=
+    Conditional = 2,  // ?:
+    Coalesce    = 3,  // ??
+    Or          = 4,  // or
+    And         = 5,  // and
+    Equality    = 6,  // == !=
+    Comparison  = 7,  // < > <= >=
+    BitOr       = 8,  // |
+    BitXor      = 9,  // ^
+    BitAnd      = 10, // &
+    Shift       = 11, // << >>
+    Term        = 12, // + -
+    Factor      = 13, // * / ~/ %
+    Unary       = 14, // ! - ~
+    Exponent    = 15, // **
+    Call        = 16, // . () [] x++ x--
+    Primary     = 17,
+}
+
+impl Precedence {
+    fn next(self) -> Self {
+        if self == Precedence::Primary {
+            panic!("no next() after Primary");
+        }
+        Self::from_int(self.int_value() + 1).unwrap()
+    }
+}
+
+#[derive(Copy, Clone)]
+struct ParseRule {
+    prefix: Option<fn(&mut Parser, bool) -> Expr>,
+    infix: Option<fn(&mut Parser, Expr, bool) -> Expr>,
+    precedence: Precedence,
+}
+
+impl Parser {
+    pub fn new(source: &str) -> Self {
+        Self {
+            scanner: Scanner::new(source),
+            current: Token::default(),
+            previous: Token::default(),
+            rules: Self::build_parse_rule_table(),
+            had_error: false,
+            panic_mode: false,
+            diagnostics: Vec::new(),
+            depth: 0,
+            map_statement: false,
+        }
+    }
+
+    pub fn parse(&mut self) -> Program {
+        self.advance();
+
+        let mut statements = Vec::new();
+        while !self.mate(TokenType::EOF) {
+            statements.extend(self.declaration());
+        }
+
+        Program {
+            statements,
+            end: Span::of(&self.previous),
+        }
+    }
+
+    pub fn had_error(&self) -> bool {
+        self.had_error
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    fn advance(&mut self) {
+        self.previous = self.current.clone();
+
+        loop {
+            self.current = self.scanner.scan_token();
+            if self.current.toke != TokenType::Error {
+                break;
+            }
+
+            self.error_at(self.current.clone(), &self.current.lexeme.clone());
+        }
+    }
+
+    fn error_at_current(&mut self, msg: &str) {
+        self.error_at(self.current.clone(), msg);
+    }
+
+    fn error(&mut self, msg: &str) {
+        self.error_at(self.previous.clone(), msg);
+    }
+
+    fn error_at(&mut self, token: Token, msg: &str) {
+        if self.panic_mode {
+            // if its already panicking, don't bother accumulating more errors
+            return;
+        };
+
+        self.panic_mode = true;
+
+        let location = if token.toke == TokenType::EOF {
+            " at end".to_string()
+        } else if token.toke == TokenType::Error {
+            String::new()
+        } else {
+            format!(" at '{}'", token.lexeme)
+        };
+
+        self.diagnostics.push(Diagnostic {
+            line: token.line,
+            column: token.column,
+            len: token.len,
+            location,
+            message: msg.to_string(),
+        });
+        self.had_error = true;
+    }
+
+    fn consume(&mut self, toke: TokenType, msg: &str) {
+        if self.current.toke == toke {
+            self.advance();
+            return;
+        }
+
+        self.error_at_current(msg);
+    }
+
+    fn check(&self, toke: TokenType) -> bool {
+        self.current.toke == toke
+    }
+
+    fn mate(&mut self, toke: TokenType) -> bool {
+        if !self.check(toke) {
+            return false;
+        }
+        self.advance();
+        true
+    }
+
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+
+        while self.current.toke != TokenType::EOF {
+            if self.previous.toke == TokenType::Mic {
+                return;
+            }
+            use crate::token::TokenType::*;
+            match self.current.toke {
+                Class | Fun | Var | For
+                    | If | While | Print
+                    | Return => return,
+                _ => self.advance(), //TODO double check this
+            }
+        }
+    }
+
+    /// A statement, or `None` if it had an error.
+    fn declaration(&mut self) -> Option<Stmt> {
+        let stmt = if self.mate(TokenType::Var) {
+            self.var_declaration()
+        } else {
+            self.statement()
+        };
+
+        if self.panic_mode {
+            self.synchronize();
+            return None;
+        }
+        Some(stmt)
+    }
+
+    fn var_declaration(&mut self) -> Stmt {
+        let span = Span::of(&self.previous);
+        self.consume(TokenType::Identifier, "Expect variable name.");
+        let name = Identifier {
+            name: self.previous.lexeme.clone(),
+            span: Span::of(&self.previous),
+        };
+
+        // `var a;` is left without an initializer, codegen makes it nil
+        let initializer = if self.mate(TokenType::Tis) {
+            Some(self.expression())
+        } else {
+            None
+        };
+
+        self.consume(TokenType::Mic,
+                     "Expect ';' after variable declaration.");
+        Stmt::new(StmtKind::Var { name, initializer }, span)
+    }
+
+    fn statement(&mut self) -> Stmt {
+        let span = Span::of(&self.current);
+        let block_start = self.previous.toke == TokenType::Kel;
+        if self.mate(TokenType::Print) {
+            let value = self.expression();
+            self.consume(TokenType::Mic, "Expect ';' after value.");
+            Stmt::new(StmtKind::Print(value), span)
+        } else if self.mate(TokenType::Kel) {
+            // a `{` starting a statement is always a block; a map literal
+            // there has to be wrapped in parentheses
+            let body = self.block();
+            let close = Span::of(&self.previous);
+            if std::mem::take(&mut self.map_statement) {
+                self.mate(TokenType::Mic);
+            }
+            Stmt::new(StmtKind::Block { body, close }, span)
+        } else {
+            let expr = self.expression();
+            if block_start && self.check(TokenType::Col) {
+                self.misplaced_map();
+            } else {
+                self.consume(TokenType::Mic, "Expect ';' after expression.");
+            }
+            Stmt::new(StmtKind::Expression(expr), span)
+        }
+    }
+
+    /// Reports `{"k": 1};`, a map literal parsed as a block, once, then skips
+    /// to the block's `}` so it and a `;` after it close the map quietly.
+    fn misplaced_map(&mut self) {
+        self.error_at_current("A statement can't start with a map literal; wrap it in parentheses.");
+        let mut open = 0;
+        loop {
+            match self.current.toke {
+                TokenType::EOF => break,
+                TokenType::Ker if open == 0 => break,
+                TokenType::Kel => open += 1,
+                TokenType::Ker => open -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
+        self.panic_mode = false;
+        self.map_statement = true;
+    }
+
+    /// Opens one more level of nesting, or reports that there are too many.
+    /// Called once the opening token is consumed, so parsing still advances.
+    fn nest(&mut self) -> bool {
+        if self.depth == MAX_NESTING {
+            self.error("Too much nesting.");
+            return false;
+        }
+        self.depth += 1;
+        true
+    }
+
+    fn block(&mut self) -> Vec<Stmt> {
+        let mut body = Vec::new();
+        if !self.nest() {
+            return body;
+        }
+        while !self.check(TokenType::Ker) && !self.check(TokenType::EOF) {
+            body.extend(self.declaration());
+        }
+
+        self.depth -= 1;
+        self.consume(TokenType::Ker, "Expect '}' after block.");
+        body
+    }
+
+    fn expression(&mut self) -> Expr {
+        self.parse_precedence(Precedence::Assignment)
+    }
+
+    fn binary(&mut self, left: Expr, _: bool) -> Expr {
+        let operator_type = self.previous.toke;
+        let span = Span::of(&self.previous);
+        let rule = match operator_type {
+            // right-associative, and `2 ** -1` is fine
+            TokenType::TarTar => Precedence::Unary,
+            _ => self.get_rule(operator_type).precedence.next(),
+        };
+
+        let right = self.parse_precedence(rule);
+
+        let op = match operator_type {
+            TokenType::ZapTis => BinaryOp::NotEqual,
+            TokenType::TisTis => BinaryOp::Equal,
+            TokenType::Gar    => BinaryOp::Greater,
+            TokenType::GarTis => BinaryOp::GreaterEqual,
+            TokenType::Gal    => BinaryOp::Less,
+            TokenType::GalTis => BinaryOp::LessEqual,
+
+            TokenType::Lus => BinaryOp::Add,
+            TokenType::Hep => BinaryOp::Sub,
+            TokenType::Tar => BinaryOp::Mul,
+            TokenType::Fas => BinaryOp::Div,
+            TokenType::SigFas => BinaryOp::FloorDiv,
+            TokenType::Cen => BinaryOp::Mod,
+            TokenType::TarTar => BinaryOp::Pow,
+
+            TokenType::Pam    => BinaryOp::BitAnd,
+            TokenType::Bar    => BinaryOp::BitOr,
+            TokenType::Ket    => BinaryOp::BitXor,
+            TokenType::GalGal => BinaryOp::ShiftLeft,
+            TokenType::GarGar => BinaryOp::ShiftRight,
+
+            _ => unreachable!("no binary operator for {operator_type:?}"),
+        };
+        Expr::new(ExprKind::Binary { op, left: Box::new(left), right: Box::new(right) }, span)
+    }
+
+    fn conditional(&mut self, condition: Expr, _: bool) -> Expr {
+        let span = Span::of(&self.previous);
+        let then_branch = Box::new(self.expression());
+        self.consume(TokenType::Col, "Expect ':' after then branch of conditional expression.");
+        // right-associative, so `a ? b : c ? d : e` chains
+        let else_branch = Box::new(self.parse_precedence(Precedence::Conditional));
+        let condition = Box::new(condition);
+        Expr::new(ExprKind::Conditional { condition, then_branch, else_branch }, span)
+    }
+
+    fn coalesce(&mut self, left: Expr, _: bool) -> Expr {
+        let span = Span::of(&self.previous);
+        let right = Box::new(self.parse_precedence(Precedence::Coalesce.next()));
+        Expr::new(ExprKind::Coalesce { left: Box::new(left), right }, span)
+    }
+
+    fn literal(&mut self, _: bool) -> Expr {
+        let kind = match self.previous.toke {
+            TokenType::False => ExprKind::Bool(false),
+            TokenType::Nil   => ExprKind::Nil,
+            TokenType::True  => ExprKind::Bool(true),
+            _                => panic!("Invalid literal"),
+        };
+        Expr::new(kind, Span::of(&self.previous))
+    }
+
+    fn grouping(&mut self, _: bool) -> Expr {
+        let span = Span::of(&self.previous);
+        let expr = self.expression();
+        self.consume(TokenType::Par, "Expect ')' after expression.");
+        Expr::new(ExprKind::Grouping(Box::new(expr)), span)
+    }
+
+    fn call(&mut self, callee: Expr, _: bool) -> Expr {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::Par) {
+            loop {
+                if arguments.len() == u8::MAX as usize {
+                    self.error_at_current("Can't have more than 255 arguments.");
+                }
+                arguments.push(self.expression());
+                if !self.mate(TokenType::Com) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::Par, "Expect ')' after arguments.");
+
+        let callee = Box::new(callee);
+        Expr::new(ExprKind::Call { callee, arguments }, Span::of(&self.previous))
+    }
+
+    fn list(&mut self, _: bool) -> Expr {
+        let span = Span::of(&self.previous);
+        let mut items = Vec::new();
+        // a trailing comma is allowed, for lists written one item per line
+        while !self.check(TokenType::Ser) && !self.check(TokenType::EOF) {
+            if items.len() == u8::MAX as usize {
+                self.error_at_current("Can't have more than 255 elements in a list literal.");
+            }
+            items.push(self.expression());
+            if !self.mate(TokenType::Com) {
+                break;
+            }
+        }
+        self.consume(TokenType::Ser, "Expect ']' after list elements.");
+        Expr::new(ExprKind::List(items), span)
+    }
+
+    fn map(&mut self, _: bool) -> Expr {
+        let span = Span::of(&self.previous);
+        let mut entries = Vec::new();
+        while !self.check(TokenType::Ker) && !self.check(TokenType::EOF) {
+            let key = self.expression();
+            self.consume(TokenType::Col, "Expect ':' after map key.");
+            let value = self.expression();
+            entries.push((key, value));
+            if !self.mate(TokenType::Com) {
+                break;
+            }
+        }
+        self.consume(TokenType::Ker, "Expect '}' after map entries.");
+        Expr::new(ExprKind::Map(entries), span)
+    }
+
+    fn subscript(&mut self, object: Expr, can_assign: bool) -> Expr {
+        let span = Span::of(&self.previous);
+        let object = Box::new(object);
+        let index = Box::new(self.expression());
+        self.consume(TokenType::Ser, "Expect ']' after index.");
+
+        if can_assign && self.mate(TokenType::Tis) {
+            let value = Box::new(self.expression());
+            Expr::new(ExprKind::SetIndex { object, index, value }, span)
+        } else if let Some(op) = self.mate_compound(can_assign) {
+            let target = Box::new(Expr::new(ExprKind::Index { object, index }, span));
+            let value = Box::new(self.expression());
+            Expr::new(ExprKind::Update { target, op, value, postfix: false }, span)
+        } else {
+            Expr::new(ExprKind::Index { object, index }, span)
+        }
+    }
+
+    fn number(&mut self, _: bool) -> Expr {
+        let kind = match number_value(&self.previous.lexeme) {
+            Ok(NumberLiteral::Int(n)) => ExprKind::Int(n),
+            Ok(NumberLiteral::Big(n)) => ExprKind::BigInt(n),
+            Ok(NumberLiteral::Float(n)) => ExprKind::Number(n),
+            Err(message) => {
+                self.error(&message);
+                ExprKind::Int(0)
+            }
+        };
+        Expr::new(kind, Span::of(&self.previous))
+    }
+
+    fn string(&mut self, _: bool) -> Expr {
+        let value = string_value(&self.previous.lexeme);
+        Expr::new(ExprKind::String(value), Span::of(&self.previous))
+    }
+
+    fn interpolation(&mut self, _: bool) -> Expr {
+        let span = Span::of(&self.previous);
+        let mut parts = Vec::new();
+        loop {
+            let text = string_value(&self.previous.lexeme);
+            if !text.is_empty() {
+                parts.push(Expr::new(ExprKind::String(text), Span::of(&self.previous)));
+            }
+            parts.push(self.expression());
+            if !self.mate(TokenType::Interpolation) {
+                break;
+            }
+        }
+
+        if !self.mate(TokenType::String) {
+            self.error_at_current("Expect '}' after interpolated expression.");
+            return Expr::new(ExprKind::Interpolation(parts), span);
+        }
+        let last = self.string(false);
+        if !matches!(&last.kind, ExprKind::String(text) if text.is_empty()) {
+            parts.push(last);
+        }
+        Expr::new(ExprKind::Interpolation(parts), span)
+    }
+
+    fn variable(&mut self, can_assign: bool) -> Expr {
+        let name = Identifier {
+            name: self.previous.lexeme.clone(),
+            span: Span::of(&self.previous),
+        };
+
+        if can_assign && self.mate(TokenType::Tis) {
+            let span = name.span;
+            let value = Box::new(self.expression());
+            Expr::new(ExprKind::Assign { name, value }, span)
+        } else if let Some(op) = self.mate_compound(can_assign) {
+            let span = name.span;
+            let target = Box::new(Expr::new(ExprKind::Variable(name), span));
+            let value = Box::new(self.expression());
+            Expr::new(ExprKind::Update { target, op, value, postfix: false }, span)
+        } else {
+            let span = name.span;
+            Expr::new(ExprKind::Variable(name), span)
+        }
+    }
+
+    fn unary(&mut self, _: bool) -> Expr {
+        let operator_type = self.previous.toke;
+        let span = Span::of(&self.previous);
+
+        // Parse the operand
+        let operand = Box::new(self.parse_precedence(Precedence::Unary));
+
+        let op = match operator_type {
+            TokenType::Hep => UnaryOp::Negate,
+            TokenType::Zap => UnaryOp::Not,
+            TokenType::Sig => UnaryOp::BitNot,
+            _ => unreachable!("no unary operator for {operator_type:?}"),
+        };
+        Expr::new(ExprKind::Unary { op, operand }, span)
+    }
+
+    /// `++x` and `--x`.
+    fn prefix_update(&mut self, _: bool) -> Expr {
+        let operator_type = self.previous.toke;
+        let span = Span::of(&self.previous);
+        let target = self.parse_precedence(Precedence::Unary);
+        self.step(target, operator_type, span, false)
+    }
+
+    /// `x++` and `x--`.
+    fn postfix_update(&mut self, target: Expr, _: bool) -> Expr {
+        let operator_type = self.previous.toke;
+        let span = Span::of(&self.previous);
+        self.step(target, operator_type, span, true)
+    }
+
+    // adds or subtracts 1, if `target` is something that can be assigned
+    fn step(&mut self, target: Expr, operator_type: TokenType, span: Span, postfix: bool) -> Expr {
+        if !matches!(target.kind, ExprKind::Variable(_) | ExprKind::Index { .. }) {
+            self.error("Invalid assignment target.");
+            return target;
+        }
+        let op = match operator_type {
+            TokenType::LusLus => BinaryOp::Add,
+            TokenType::HepHep => BinaryOp::Sub,
+            _ => unreachable!("no update operator for {operator_type:?}"),
+        };
+        let value = Box::new(Expr::new(ExprKind::Int(1), span));
+        Expr::new(ExprKind::Update { target: Box::new(target), op, value, postfix }, span)
+    }
+
+    /// Consumes a compound assignment operator such as `+=` if one is next
+    /// and `can_assign`, giving the operator it applies.
+    fn mate_compound(&mut self, can_assign: bool) -> Option<BinaryOp> {
+        let op = match self.current.toke {
+            TokenType::LusTis => BinaryOp::Add,
+            TokenType::HepTis => BinaryOp::Sub,
+            TokenType::TarTis => BinaryOp::Mul,
+            TokenType::FasTis => BinaryOp::Div,
+            TokenType::CenTis => BinaryOp::Mod,
+            _ => return None,
+        };
+        if !can_assign {
+            return None;
+        }
+        self.advance();
+        Some(op)
+    }
+
+    fn parse_precedence(&mut self, precedence: Precedence) -> Expr {
+        self.advance();
+        if !self.nest() {
+            return Expr::new(ExprKind::Nil, Span::of(&self.previous));
+        }
+        let Some(prefix_rule) = self.get_rule(self.previous.toke).prefix else {
+            self.depth -= 1;
+            self.error("Expect expression.");
+            return Expr::new(ExprKind::Nil, Span::of(&self.previous));
+        };
+
+        let can_assign: bool = precedence <= Precedence::Assignment;
+        let mut expr = prefix_rule(self, can_assign);
+
+        while precedence <= self.get_rule(self.current.toke).precedence {
+            self.advance();
+
+            if let Some(infix_rule) = self.get_rule(self.previous.toke).infix {
+                expr = infix_rule(self, expr, can_assign);
+            }
+        }
+
+        if can_assign && (self.mate(TokenType::Tis) || self.mate_compound(true).is_some()) {
+            self.error("Invalid assignment target.");
+        }
+        self.depth -= 1;
+        expr
+    }
+
+    fn get_rule(&self, toke: TokenType) -> ParseRule {
+        self.rules[toke.int_value()]
+    }
+
+    fn build_parse_rule_table() -> Vec<ParseRule> {
+        let mut rules: Vec<ParseRule> = vec!
+            [ ParseRule {
+                prefix: None,
+                infix: None,
+                precedence: Precedence::None,
+            }; TokenType::NumberOfTokens.int_value()];
+
+        rules[TokenType::Pal.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.grouping(b)),
+                infix: Some(|c, callee, b| c.call(callee, b)),
+                precedence: Precedence::Call,
+            };
+        rules[TokenType::Sel.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.list(b)),
+                infix: Some(|c, object, b| c.subscript(object, b)),
+                precedence: Precedence::Call,
+            };
+        rules[TokenType::Kel.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.map(b)),
+                infix: None,
+                precedence: Precedence::None,
+            };
+        rules[TokenType::Hep.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.unary(b)),
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Term,
+            };
+        rules[TokenType::Lus.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Term,
+            };
+        rules[TokenType::Fas.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Factor,
+            };
+        rules[TokenType::Tar.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Factor,
+            };
+        rules[TokenType::SigFas.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Factor,
+            };
+        rules[TokenType::Cen.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Factor,
+            };
+        rules[TokenType::TarTar.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Exponent,
+            };
+        rules[TokenType::Pam.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::BitAnd,
+            };
+        rules[TokenType::Bar.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::BitOr,
+            };
+        rules[TokenType::Ket.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::BitXor,
+            };
+        rules[TokenType::GalGal.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Shift,
+            };
+        rules[TokenType::GarGar.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Shift,
+            };
+        rules[TokenType::Sig.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.unary(b)),
+                infix: None,
+                precedence: Precedence::None,
+            };
+        rules[TokenType::LusLus.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.prefix_update(b)),
+                infix: Some(|c, target, b| c.postfix_update(target, b)),
+                precedence: Precedence::Call,
+            };
+        rules[TokenType::HepHep.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.prefix_update(b)),
+                infix: Some(|c, target, b| c.postfix_update(target, b)),
+                precedence: Precedence::Call,
+            };
+        rules[TokenType::Wut.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, condition, b| c.conditional(condition, b)),
+                precedence: Precedence::Conditional,
+            };
+        rules[TokenType::WutWut.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.coalesce(left, b)),
+                precedence: Precedence::Coalesce,
+            };
+        rules[TokenType::Number.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.number(b)),
+                infix: None,
+                precedence: Precedence::None,
+            };
+        rules[TokenType::False.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.literal(b)),
+                infix: None,
+                precedence: Precedence::None,
+            };
+        rules[TokenType::True.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.literal(b)),
+                infix: None,
+                precedence: Precedence::None,
+            };
+        rules[TokenType::Nil.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.literal(b)),
+                infix: None,
+                precedence: Precedence::None,
+            };
+        rules[TokenType::Zap.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.unary(b)),
+                infix: None,
+                precedence: Precedence::None,
+            };
+        rules[TokenType::ZapTis.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Equality,
+            };
+        rules[TokenType::TisTis.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Equality,
+            };
+        rules[TokenType::Gar.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Comparison,
+            };
+        rules[TokenType::GarTis.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Comparison,
+            };
+        rules[TokenType::Gal.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Comparison,
+            };
+        rules[TokenType::GalTis.int_value()] =
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, left, b| c.binary(left, b)),
+                precedence: Precedence::Comparison,
+            };
+        rules[TokenType::String.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.string(b)),
+                infix: None,
+                precedence: Precedence::None,
+            };
+        rules[TokenType::Interpolation.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.interpolation(b)),
+                infix: None,
+                precedence: Precedence::None,
+            };
+        rules[TokenType::Identifier.int_value()] =
+            ParseRule {
+                prefix: Some(|c, b| c.variable(b)),
+                infix: None,
+                precedence: Precedence::None,
+            };
+
+        rules
+    }
+
+}
@@ -0,0 +1,52 @@
+use std::io::{self, BufRead, Write};
+
+use crate::json::Json;
+
+// The `Content-Length` framing shared by the debug adapter and the language
+// server.
+
+/// The largest body accepted, far more than any document or request needs.
+/// The buffer is allocated before reading, so a bigger length is refused
+/// rather than trusted.
+const MAX_CONTENT_LENGTH: usize = 64 << 20;
+
+/// The next message, or `None` once the client has gone away.
+pub(crate) fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(n) = header.strip_prefix("Content-Length:") {
+            length = n.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    if length > MAX_CONTENT_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Content-Length {length} exceeds the maximum of {MAX_CONTENT_LENGTH}"),
+        ));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    let text = String::from_utf8_lossy(&body);
+    Json::parse(&text)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub(crate) fn write_message<W: Write>(writer: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()
+}
@@ -1,10 +1,20 @@
 use crate::token::*;
+use crate::value::BigInt;
 
 pub struct Scanner {
     source: Vec<char>,
     start: usize,
     current: usize,
     line: usize,
+    // offset of the first character on the current line
+    line_start: usize,
+    start_line: usize,
+    start_column: usize,
+    comments: bool,
+    // one entry per `${` still open, counting the `{` opened inside it, so
+    // the `}` that ends the expression can be told apart, and whether the
+    // string it is in is triple-quoted
+    interpolations: Vec<(usize, bool)>,
 }
 
 impl Scanner {
@@ -14,12 +24,25 @@ impl Scanner {
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            start_line: 1,
+            start_column: 0,
+            comments: false,
+            interpolations: Vec::new(),
         }
     }
 
+    /// Keeps `//` comments as `Comment` tokens instead of skipping them. The
+    /// compiler can't parse them, so this is for tooling only.
+    pub fn set_comments(&mut self, comments: bool) {
+        self.comments = comments;
+    }
+
     pub fn scan_token(&mut self) -> Token {
         self.skip_whitespace();
         self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.start - self.line_start;
 
         if self.is_at_end() {
             return self.make_token(TokenType::EOF)
@@ -31,15 +54,43 @@ impl Scanner {
             // 1 character lexemes
             '(' => self.make_token(TokenType::Pal),
             ')' => self.make_token(TokenType::Par),
-            '{' => self.make_token(TokenType::Kel),
-            '}' => self.make_token(TokenType::Ker),
+            '{' => {
+                if let Some((open, _)) = self.interpolations.last_mut() {
+                    *open += 1;
+                }
+                self.make_token(TokenType::Kel)
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(&mut (0, triple)) => {
+                    // back inside the string
+                    self.interpolations.pop();
+                    self.string(triple, false)
+                }
+                Some((open, _)) => {
+                    *open -= 1;
+                    self.make_token(TokenType::Ker)
+                }
+                None => self.make_token(TokenType::Ker),
+            },
+            '[' => self.make_token(TokenType::Sel),
+            ']' => self.make_token(TokenType::Ser),
             ';' => self.make_token(TokenType::Mic),
+            ':' => self.make_token(TokenType::Col),
             ',' => self.make_token(TokenType::Com),
+            '.' if self.peek().is_ascii_digit() => {
+                self.number_error("A number can't start with '.'; add a leading '0'.")
+            }
             '.' => self.make_token(TokenType::Dot),
-            '-' => self.make_token(TokenType::Hep),
-            '+' => self.make_token(TokenType::Lus),
-            '/' => self.make_token(TokenType::Fas),
-            '*' => self.make_token(TokenType::Tar),
+            '/' => if self.mate('/') {
+                        self.comment()
+                    } else if self.mate('=') {
+                        self.make_token(TokenType::FasTis)
+                    } else {
+                        self.make_token(TokenType::Fas)
+                    },
+            '&' => self.make_token(TokenType::Pam),
+            '|' => self.make_token(TokenType::Bar),
+            '^' => self.make_token(TokenType::Ket),
 
             // 2 character lexemes
             '!' => if self.mate('=') {
@@ -54,17 +105,65 @@ impl Scanner {
                     },
             '<' => if self.mate('=') {
                         self.make_token(TokenType::GalTis)
+                    } else if self.mate('<') {
+                        self.make_token(TokenType::GalGal)
                     } else {
                         self.make_token(TokenType::Gal)
                     },
             '>' => if self.mate('=') {
                         self.make_token(TokenType::GarTis)
+                    } else if self.mate('>') {
+                        self.make_token(TokenType::GarGar)
                     } else {
                         self.make_token(TokenType::Gar)
                     },
+            '-' => if self.mate('-') {
+                        self.make_token(TokenType::HepHep)
+                    } else if self.mate('=') {
+                        self.make_token(TokenType::HepTis)
+                    } else {
+                        self.make_token(TokenType::Hep)
+                    },
+            '+' => if self.mate('+') {
+                        self.make_token(TokenType::LusLus)
+                    } else if self.mate('=') {
+                        self.make_token(TokenType::LusTis)
+                    } else {
+                        self.make_token(TokenType::Lus)
+                    },
+            '?' => if self.mate('?') {
+                        self.make_token(TokenType::WutWut)
+                    } else {
+                        self.make_token(TokenType::Wut)
+                    },
+            '*' => if self.mate('*') {
+                        self.make_token(TokenType::TarTar)
+                    } else if self.mate('=') {
+                        self.make_token(TokenType::TarTis)
+                    } else {
+                        self.make_token(TokenType::Tar)
+                    },
+            '%' => if self.mate('=') {
+                        self.make_token(TokenType::CenTis)
+                    } else {
+                        self.make_token(TokenType::Cen)
+                    },
+            '~' => if self.mate('/') {
+                        self.make_token(TokenType::SigFas)
+                    } else {
+                        self.make_token(TokenType::Sig)
+                    },
 
             // literal tokens
-            '"' => self.string(),
+            '"' => {
+                let triple = self.triple_quote();
+                self.string(triple, false)
+            }
+            'r' if self.peek() == '"' => {
+                self.advance();
+                let triple = self.triple_quote();
+                self.string(triple, true)
+            }
 
             '0'..='9' => self.number(),
             _ if c.is_alphabetic() || c == '_' => self.identifier(),
@@ -78,9 +177,7 @@ impl Scanner {
     }
 
     fn mate(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
-            return false
-        } else if self.source[self.current] != expected {
+        if self.is_at_end() || self.source[self.current] != expected {
             return false
         }
 
@@ -88,24 +185,107 @@ impl Scanner {
         true
     }
 
-    fn string(&mut self) -> Token {
-        while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+    /// Consumes the rest of a `"""` after its first quote, if it is one.
+    fn triple_quote(&mut self) -> bool {
+        if self.peek() == '"' && self.peek_next() == Some('"') {
+            self.advance();
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    /// Scans up to the closing quote, or to the next `${`, which starts an
+    /// embedded expression. Raw strings have neither escapes nor
+    /// interpolation. A bad escape is reported once the whole literal has
+    /// been consumed, so scanning picks up again after it.
+    fn string(&mut self, triple: bool, raw: bool) -> Token {
+        let mut error: Option<String> = None;
+        loop {
+            if self.is_at_end() {
+                return self.error_token("Unterminated string.");
+            }
+            match self.peek() {
+                '"' if !triple => break,
+                '"' if self.source.get(self.current + 1..self.current + 3) == Some(&['"', '"']) => break,
+                '$' if !raw && self.peek_next() == Some('{') => {
+                    self.advance();
+                    self.advance();
+                    self.interpolations.push((0, triple));
+                    return match error {
+                        Some(message) => self.error_token(&message),
+                        None => self.make_token(TokenType::Interpolation),
+                    };
+                }
+                '\\' if !raw => {
+                    self.advance();
+                    if let Err(message) = self.escape() {
+                        error.get_or_insert(message);
+                    }
+                }
+                _ => {
+                    self.advance_in_string();
+                }
             }
+        }
+
+        for _ in 0..if triple { 3 } else { 1 } {
             self.advance();
         }
+        match error {
+            Some(message) => self.error_token(&message),
+            None => self.make_token(TokenType::String),
+        }
+    }
 
+    /// Checks the escape sequence after a backslash.
+    fn escape(&mut self) -> Result<(), String> {
         if self.is_at_end() {
-            return self.error_token("Unterminated string.")
-        };
+            // reported as an unterminated string
+            return Ok(());
+        }
+        match self.advance_in_string() {
+            'n' | 't' | 'r' | '0' | '\\' | '"' | '$' => Ok(()),
+            'u' => {
+                if !self.mate('{') {
+                    return Err("Expect '{' after '\\u'.".to_string());
+                }
+                let start = self.current;
+                while self.peek().is_ascii_hexdigit() {
+                    self.advance();
+                }
+                let digits: String = self.source[start..self.current].iter().collect();
+                if !self.mate('}') {
+                    return Err("Expect '}' after Unicode escape digits.".to_string());
+                }
+                match u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32) {
+                    Some(_) if digits.len() <= 6 => Ok(()),
+                    _ => Err(format!("Invalid Unicode escape '\\u{{{digits}}}'.")),
+                }
+            }
+            c => Err(format!("Invalid escape sequence '\\{c}'.")),
+        }
+    }
 
-        self.advance();
-        self.make_token(TokenType::String)
+    /// `advance`, keeping the line count right inside a literal.
+    fn advance_in_string(&mut self) -> char {
+        let c = self.advance();
+        if c == '\n' {
+            self.line += 1;
+            self.line_start = self.current;
+        }
+        c
+    }
+
+    fn comment(&mut self) -> Token {
+        while self.peek() != '\n' && !self.is_at_end() {
+            self.advance();
+        }
+        self.make_token(TokenType::Comment)
     }
 
     fn identifier(&mut self) -> Token {
-        while self.peek().is_alphabetic() || self.peek().is_digit(10) {
+        while self.peek().is_alphabetic() || self.peek().is_ascii_digit() {
             self.advance();
         }
         self.make_token(self.identifier_type())
@@ -126,7 +306,7 @@ impl Scanner {
             'w' => self.check_keyword(1, 4, "hile", TokenType::While),
 
             'f' => if self.current - self.start > 1 {
-                return match self.source[self.start + 1] {
+                match self.source[self.start + 1] {
                     'a' => self.check_keyword(2, 3, "lse", TokenType::False),
                     'o' => self.check_keyword(2, 1, "r", TokenType::For),
                     'u' => self.check_keyword(2, 1, "n", TokenType::Fun),
@@ -137,7 +317,7 @@ impl Scanner {
             },
 
             't' => if self.current - self.start > 1 {
-                return match self.source[self.start + 1] {
+                match self.source[self.start + 1] {
                     'h' => self.check_keyword(2, 2, "is", TokenType::This),
                     'r' => self.check_keyword(2, 2, "ue", TokenType::True),
                     _ => TokenType::Identifier,
@@ -167,37 +347,112 @@ impl Scanner {
         TokenType::Identifier
     }
 
+    /// Scans a number: decimal with an optional fraction and exponent, or
+    /// hexadecimal, octal or binary after `0x`, `0o` or `0b`. Digits can be
+    /// grouped with underscores.
     fn number(&mut self) -> Token {
-        while self.peek().is_digit(10) {
+        let radix = match (self.source[self.start], self.peek()) {
+            ('0', 'x' | 'X') => Some((16, "hexadecimal")),
+            ('0', 'o' | 'O') => Some((8, "octal")),
+            ('0', 'b' | 'B') => Some((2, "binary")),
+            _ => None,
+        };
+        if let Some((radix, name)) = radix {
             self.advance();
+            return match self.digits(radix, name) {
+                Ok(0) => self.number_error(&format!("Expect {name} digits after '0{}'.", self.source[self.start + 1])),
+                Ok(_) => self.number_end(),
+                Err(message) => self.number_error(&message),
+            };
+        }
+
+        // the first digit is already consumed
+        self.current -= 1;
+        if let Err(message) = self.digits(10, "decimal") {
+            return self.number_error(&message);
         }
 
         // Look for a fractional part
-        if self.peek() == '.' && self.peek_next().unwrap().is_digit(10) {
+        if self.peek() == '.' && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
             // Consume the "."
             self.advance();
+            if let Err(message) = self.digits(10, "decimal") {
+                return self.number_error(&message);
+            }
+        }
 
-            while self.peek().is_digit(10) {
+        if matches!(self.peek(), 'e' | 'E') {
+            self.advance();
+            if matches!(self.peek(), '+' | '-') {
                 self.advance();
             }
+            match self.digits(10, "decimal") {
+                Ok(0) => return self.number_error("Expect digits in exponent."),
+                Ok(_) => {}
+                Err(message) => return self.number_error(&message),
+            }
         }
 
+        self.number_end()
+    }
+
+    /// Consumes a run of digits in `radix`, with single underscores between
+    /// them, returning how many digits there were.
+    fn digits(&mut self, radix: u32, name: &str) -> Result<usize, String> {
+        let mut count = 0;
+        loop {
+            let c = self.peek();
+            if c.is_digit(radix) {
+                count += 1;
+            } else if c == '_' {
+                if count == 0 || !self.peek_next().is_some_and(|c| c.is_digit(radix)) {
+                    self.advance();
+                    return Err("Underscores in numbers must be between digits.".to_string());
+                }
+            } else if c.is_ascii_digit() {
+                self.advance();
+                return Err(format!("Invalid digit '{c}' in {name} number."));
+            } else {
+                return Ok(count);
+            }
+            self.advance();
+        }
+    }
+
+    /// Finishes a number, which mustn't run straight into a name.
+    fn number_end(&mut self) -> Token {
+        let c = self.peek();
+        if c.is_alphanumeric() || c == '_' {
+            self.advance();
+            return self.number_error(&format!("Unexpected '{c}' in number."));
+        }
         self.make_token(TokenType::Number)
     }
 
+    /// Reports a malformed number once the rest of it is consumed, so it
+    /// gives one error rather than several.
+    fn number_error(&mut self, message: &str) -> Token {
+        while self.peek().is_alphanumeric() || self.peek() == '_'
+            || (self.peek() == '.' && self.peek_next().is_some_and(|c| c.is_ascii_digit()))
+        {
+            self.advance();
+        }
+        self.error_token(message)
+    }
+
     fn skip_whitespace(&mut self) {
         loop {
             let c = self.peek();
             match c {
-                ' ' | '\r' | '\t' => { self.advance(); break; },
+                ' ' | '\r' | '\t' => { self.advance(); },
 
                 '\n' => {
                     self.line += 1;
+                    self.line_start = self.current + 1;
                     self.advance();
-                    break;
                 },
                 '/' => {
-                    if self.peek_next().unwrap() == '/' {
+                    if self.peek_next() == Some('/') && !self.comments {
                         // A comment goes until the end of the line.
                         while self.peek() != '\n' && !self.is_at_end() {
                             self.advance();
@@ -221,16 +476,11 @@ impl Scanner {
     }
 
     fn peek_next(&self) -> Option<char> {
-        if self.is_at_end() {
-            None
-        } else {
-            Some(self.source[self.current + 1])
-        }
+        self.source.get(self.current + 1).copied()
     }
 
     fn is_at_end(&self) -> bool {
-        let res = self.current == self.source.len();
-        res
+        self.current == self.source.len()
 //        self.peek() == '\0'
 //        self.source.as_bytes()[self.current] == b'\0'
     }
@@ -239,7 +489,10 @@ impl Scanner {
         let tok = Token {
             toke,
             lexeme: self.source[self.start..self.current].iter().collect(),
-            line: self.line,
+            line: self.start_line,
+            start: self.start,
+            column: self.start_column,
+            len: self.current - self.start,
         };
         tok
     }
@@ -248,7 +501,112 @@ impl Scanner {
         Token {
             toke: TokenType::Error,
             lexeme: err.to_string(),
-            line: self.line,
+            line: self.start_line,
+            start: self.start,
+            column: self.start_column,
+            len: self.current - self.start,
+        }
+    }
+}
+
+/// The text a `String` or `Interpolation` token stands for: the lexeme
+/// without its delimiters, with escapes replaced. A triple-quoted string drops
+/// the line break straight after its opening quotes. The scanner has already
+/// rejected bad escapes.
+pub fn string_value(lexeme: &str) -> String {
+    let (body, raw) = if let Some(rest) = lexeme.strip_prefix("r\"\"\"") {
+        (strip_line_break(rest), true)
+    } else if let Some(rest) = lexeme.strip_prefix("r\"") {
+        (rest, true)
+    } else if let Some(rest) = lexeme.strip_prefix("\"\"\"") {
+        (strip_line_break(rest), false)
+    } else {
+        // an opening quote, or the `}` ending an embedded expression
+        (&lexeme[1..], false)
+    };
+
+    // an unescaped quote can't come before the closing one of an ordinary
+    // string, so three at the end close a triple-quoted one
+    let body = body
+        .strip_suffix("${")
+        .or_else(|| body.strip_suffix("\"\"\""))
+        .unwrap_or_else(|| &body[..body.len() - 1]);
+
+    if raw {
+        return body.to_string();
+    }
+    let mut value = String::with_capacity(body.len());
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some('r') => value.push('\r'),
+            Some('0') => value.push('\0'),
+            Some('u') => {
+                let digits: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+                value.extend(u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32));
+            }
+            Some(c) => value.push(c),
+            None => {}
+        }
+    }
+    value
+}
+
+fn strip_line_break(s: &str) -> &str {
+    s.strip_prefix("\r\n").or_else(|| s.strip_prefix('\n')).unwrap_or(s)
+}
+
+/// What a `Number` token stands for. Literals with a point or an exponent
+/// are floats; the others, including all hex, octal and binary ones, are ints,
+/// big ones if they don't fit in 64 bits.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NumberLiteral {
+    Int(i64),
+    Big(BigInt),
+    Float(f64),
+}
+
+/// The value of a `Number` token, or why it can't be represented.
+pub fn number_value(lexeme: &str) -> Result<NumberLiteral, String> {
+    let digits = lexeme.replace('_', "");
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x" | "0X") => (16, &digits[2..]),
+        Some("0o" | "0O") => (8, &digits[2..]),
+        Some("0b" | "0B") => (2, &digits[2..]),
+        _ => (10, &digits[..]),
+    };
+
+    if radix == 10 && digits.contains(['.', 'e', 'E']) {
+        let value: f64 = digits.parse().map_err(|_| format!("Invalid number '{lexeme}'."))?;
+        if value.is_infinite() {
+            return Err(format!("Number '{lexeme}' is too large."));
+        }
+        return Ok(NumberLiteral::Float(value));
+    }
+    // the scanner has checked the digits, so this only fails on overflow
+    Ok(match i64::from_str_radix(digits, radix) {
+        Ok(n) => NumberLiteral::Int(n),
+        Err(_) => NumberLiteral::Big(BigInt::parse(digits, radix)),
+    })
+}
+
+/// The whole token stream of `source`, up to but not including EOF.
+pub fn tokenize(source: &str, comments: bool) -> Vec<Token> {
+    let mut scanner = Scanner::new(source);
+    scanner.set_comments(comments);
+
+    let mut tokens = Vec::new();
+    loop {
+        let token = scanner.scan_token();
+        if token.toke == TokenType::EOF {
+            return tokens;
         }
+        tokens.push(token);
     }
 }
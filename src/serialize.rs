@@ -0,0 +1,179 @@
+use std::fmt::{Display, Formatter};
+
+use crate::chunk::*;
+use crate::value::{BigInt, Value};
+
+// .loxc layout, all integers little-endian:
+//
+//   "LOXC" version:u8
+//   constant_count:u32 { tag:u8 payload }*
+//   code_len:u32 code:[u8; code_len] lines:[u32; code_len]
+//
+// Loading only checks the container; the bytecode itself still has to go
+// through `verifier::verify` before it runs.
+const MAGIC: &[u8; 4] = b"LOXC";
+const VERSION: u8 = 1;
+
+const TAG_NUMBER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_INT: u8 = 2;
+const TAG_BIGINT: u8 = 3;
+
+#[derive(Debug, PartialEq)]
+pub enum SerializeError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnexpectedEof,
+    TrailingBytes(usize),
+    UnknownConstantTag(u8),
+    InvalidUtf8,
+    InvalidBigInt,
+    DuplicateConstant(usize),
+    UnsupportedConstant(&'static str),
+}
+
+impl Display for SerializeError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        use SerializeError::*;
+        match self {
+            BadMagic => write!(f, "not a .loxc file"),
+            UnsupportedVersion(v) => write!(f, "unsupported .loxc version {v} (expected {VERSION})"),
+            UnexpectedEof => write!(f, "unexpected end of file"),
+            TrailingBytes(n) => write!(f, "{n} unexpected bytes after the chunk"),
+            UnknownConstantTag(t) => write!(f, "unknown constant tag {t}"),
+            InvalidUtf8 => write!(f, "string constant is not valid UTF-8"),
+            InvalidBigInt => write!(f, "big int constant is not a decimal number"),
+            DuplicateConstant(i) => write!(f, "constant {i} duplicates an earlier string"),
+            UnsupportedConstant(t) => write!(f, "can't serialize a {t} constant"),
+        }
+    }
+}
+
+pub fn write_chunk(chunk: &Chunk) -> Result<Vec<u8>, SerializeError> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+
+    out.extend_from_slice(&(chunk.constant_count() as u32).to_le_bytes());
+    for i in 0..chunk.constant_count() {
+        let constant = chunk.read_constant(i);
+        if let Some(n) = constant.as_int() {
+            out.push(TAG_INT);
+            out.extend_from_slice(&n.to_le_bytes());
+        } else if let Some(n) = constant.as_bigint() {
+            // as decimal text, which is simple and rare enough to not matter
+            let digits = n.to_string();
+            out.push(TAG_BIGINT);
+            out.extend_from_slice(&(digits.len() as u32).to_le_bytes());
+            out.extend_from_slice(digits.as_bytes());
+        } else if let Some(n) = constant.as_number() {
+            out.push(TAG_NUMBER);
+            out.extend_from_slice(&n.to_le_bytes());
+        } else if let Some(s) = constant.as_str() {
+            out.push(TAG_STRING);
+            out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            out.extend_from_slice(s.as_bytes());
+        } else {
+            return Err(SerializeError::UnsupportedConstant(constant.type_name()));
+        }
+    }
+
+    let code = chunk.code();
+    out.extend_from_slice(&(code.len() as u32).to_le_bytes());
+    out.extend_from_slice(code);
+    for offset in 0..code.len() {
+        out.extend_from_slice(&(chunk.get_line(offset) as u32).to_le_bytes());
+    }
+
+    Ok(out)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], SerializeError> {
+        let end = self.pos.checked_add(n).ok_or(SerializeError::UnexpectedEof)?;
+        let slice = self.bytes.get(self.pos..end).ok_or(SerializeError::UnexpectedEof)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, SerializeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, SerializeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64, SerializeError> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> Result<&'a str, SerializeError> {
+        let len = self.u32()? as usize;
+        std::str::from_utf8(self.take(len)?).map_err(|_| SerializeError::InvalidUtf8)
+    }
+
+    fn i64(&mut self) -> Result<i64, SerializeError> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+pub fn read_chunk(bytes: &[u8]) -> Result<Chunk, SerializeError> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    if reader.take(MAGIC.len()).map_err(|_| SerializeError::BadMagic)? != MAGIC {
+        return Err(SerializeError::BadMagic);
+    }
+    let version = reader.u8()?;
+    if version != VERSION {
+        return Err(SerializeError::UnsupportedVersion(version));
+    }
+
+    let mut chunk = Chunk::new();
+
+    let constant_count = reader.u32()? as usize;
+    for i in 0..constant_count {
+        let value = match reader.u8()? {
+            TAG_NUMBER => Value::number(reader.f64()?),
+            TAG_INT => Value::int(reader.i64()?),
+            TAG_STRING => Value::string(reader.str()?),
+            TAG_BIGINT => {
+                let s = reader.str()?;
+                let (negative, digits) = match s.strip_prefix('-') {
+                    Some(digits) => (true, digits),
+                    None => (false, s),
+                };
+                if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(SerializeError::InvalidBigInt);
+                }
+                let n = BigInt::parse(digits, 10);
+                Value::bigint(if negative { -&n } else { n })
+            }
+            tag => return Err(SerializeError::UnknownConstantTag(tag)),
+        };
+
+        // The constant table interns strings, so a duplicate would shift
+        // every index after it.
+        if chunk.write_constant(value) != i {
+            return Err(SerializeError::DuplicateConstant(i));
+        }
+    }
+
+    let code_len = reader.u32()? as usize;
+    let code = reader.take(code_len)?;
+    for &byte in code {
+        let line = reader.u32()? as usize;
+        chunk.write(byte, line);
+    }
+
+    if reader.pos != bytes.len() {
+        return Err(SerializeError::TrailingBytes(bytes.len() - reader.pos));
+    }
+
+    Ok(chunk)
+}
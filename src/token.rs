@@ -1,10 +1,20 @@
 use int_enum::IntEnum;
 
+use crate::json::Json;
+
 #[derive(Clone, Debug)]
 pub struct Token {
     pub toke: TokenType,
     pub lexeme: String,
+    /// Line the token starts on.
     pub line: usize,
+    /// Character offset of the first character in the source.
+    pub start: usize,
+    /// 0-based column of the first character, counted in characters.
+    pub column: usize,
+    /// Length of the source text in characters. For error tokens this is not
+    /// the length of `lexeme`, which holds the message.
+    pub len: usize,
 }
 
 impl Default for Token {
@@ -13,6 +23,9 @@ impl Default for Token {
             toke: TokenType::Undefined,
             lexeme: String::new(),
             line: 0,
+            start: 0,
+            column: 0,
+            len: 0,
         }
     }
 }
@@ -26,6 +39,7 @@ impl PartialEq for Token {
 impl Eq for Token {}
 
 #[repr(usize)]
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Eq, Clone, Copy, IntEnum)]
 pub enum TokenType {
     // Single-character tokens
@@ -51,7 +65,95 @@ pub enum TokenType {
 
     Error = 38, EOF = 39,
 
+    // Trivia, only produced when the scanner is asked to keep it
+    Comment = 40,
+
+    Sel = 41, Ser = 42, // [ ]
+    Col = 43,           // :
+
+    // The part of an interpolated string up to and including a `${`. The
+    // embedded expression's tokens follow, then the rest of the string as
+    // another `Interpolation` or, for the last part, a `String`, either of
+    // which starts with the `}`.
+    Interpolation = 44,
+
+    Cen = 45,    // %
+    SigFas = 46, // ~/, since `//` starts a comment
+    TarTar = 47, // **
+    Pam = 48, Bar = 49, Ket = 50, Sig = 51, // & | ^ ~
+    GalGal = 52, GarGar = 53,               // << >>
+
+    // Compound assignment
+    LusTis = 54, HepTis = 55, TarTis = 56, FasTis = 57, CenTis = 58, // += -= *= /= %=
+    LusLus = 59, HepHep = 60,                                       // ++ --
+
+    Wut = 61, WutWut = 62, // ? ??
+
     // initial value for parser, otherwise need to use Options everywhere
-    Undefined = 40,
-    NumberOfTokens = 41, // for rule generation, seems kinda silly
+    Undefined = 63,
+    NumberOfTokens = 64, // for rule generation, seems kinda silly
+}
+
+/// What a token looks like to a syntax highlighter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenCategory {
+    Keyword,
+    Identifier,
+    String,
+    Number,
+    Operator,
+    Punctuation,
+    Comment,
+    Error,
+}
+
+impl TokenCategory {
+    pub fn name(self) -> &'static str {
+        match self {
+            TokenCategory::Keyword => "keyword",
+            TokenCategory::Identifier => "identifier",
+            TokenCategory::String => "string",
+            TokenCategory::Number => "number",
+            TokenCategory::Operator => "operator",
+            TokenCategory::Punctuation => "punctuation",
+            TokenCategory::Comment => "comment",
+            TokenCategory::Error => "error",
+        }
+    }
+}
+
+impl TokenType {
+    /// `None` for the tokens that never appear in source text.
+    pub fn category(self) -> Option<TokenCategory> {
+        use TokenType::*;
+        Some(match self {
+            Pal | Par | Kel | Ker | Sel | Ser | Com | Col | Dot | Mic => TokenCategory::Punctuation,
+            Hep | Lus | Fas | Tar | Cen | SigFas | TarTar | Pam | Bar | Ket | Sig | Zap | ZapTis | Tis
+            | TisTis | Gar | GarTis | GarGar | Gal | GalTis | GalGal | LusTis | HepTis | TarTis | FasTis
+            | CenTis | LusLus | HepHep | Wut | WutWut => TokenCategory::Operator,
+            Identifier => TokenCategory::Identifier,
+            String | Interpolation => TokenCategory::String,
+            Number => TokenCategory::Number,
+            And | Class | Else | False | For | Fun | If | Nil | Or | Print | Return | Super
+            | This | True | Var | While => TokenCategory::Keyword,
+            Comment => TokenCategory::Comment,
+            Error => TokenCategory::Error,
+            EOF | Undefined | NumberOfTokens => return None,
+        })
+    }
+}
+
+impl Token {
+    pub fn to_json(&self) -> Json {
+        let text = if self.toke == TokenType::Error { "message" } else { "lexeme" };
+        Json::object(vec![
+            ("type", format!("{:?}", self.toke).into()),
+            ("category", self.toke.category().map(TokenCategory::name).into()),
+            (text, self.lexeme.as_str().into()),
+            ("line", self.line.into()),
+            ("column", self.column.into()),
+            ("start", self.start.into()),
+            ("end", (self.start + self.len).into()),
+        ])
+    }
 }
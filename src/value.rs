@@ -1,5 +1,52 @@
-use std::fmt::{Display, Formatter};
-use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::cell::RefCell;
+use std::fmt::{Debug, Display, Formatter};
+
+mod bigint;
+mod map;
+mod number;
+mod object;
+pub use bigint::BigInt;
+pub use map::{HashKey, Map};
+pub use number::{arithmetic, bit_not, compare, negate, Arith};
+pub use object::Obj;
+
+use crate::natives::Native;
+
+// Both representations expose the same constructors and accessors, so the rest
+// of the crate never looks at the bits directly.
+#[cfg(not(feature = "nan-boxing"))]
+mod tagged;
+#[cfg(not(feature = "nan-boxing"))]
+pub use tagged::Value;
+
+#[cfg(feature = "nan-boxing")]
+mod nanbox;
+#[cfg(feature = "nan-boxing")]
+pub use nanbox::Value;
+
+// A `Value` shares non-atomic reference counts and `RefCell`s, so neither
+// representation may be `Send` or `Sync`. `is_shareable` resolves to a single
+// impl, and so compiles, only while `Value` is neither.
+const _: fn() = || {
+    trait AmbiguousIfThreadSafe<A> {
+        fn is_shareable() {}
+    }
+    impl<T: ?Sized> AmbiguousIfThreadSafe<()> for T {}
+    impl<T: ?Sized + Send> AmbiguousIfThreadSafe<u8> for T {}
+    impl<T: ?Sized + Sync> AmbiguousIfThreadSafe<u16> for T {}
+    let _ = <Value as AmbiguousIfThreadSafe<_>>::is_shareable;
+};
+
+/// A borrowed view of a `Value`, used for matching on it regardless of how it
+/// is packed.
+pub(crate) enum Kind<'a> {
+    /// A float; ints are `Int`.
+    Number(f64),
+    Int(i64),
+    Bool(bool),
+    Nil,
+    Obj(&'a Obj),
+}
 
 #[derive(Debug)]
 pub struct ValueArray {
@@ -13,12 +60,10 @@ impl ValueArray {
 
     pub fn write(&mut self, value: Value) -> usize {
         // String interning
-        if let Value::Str(s) = &value {
+        if let Some(s) = value.as_str() {
             for (i, v) in self.values.iter().enumerate() {
-                if let Value::Str(t) = v {
-                    if t == s {
-                        return i;
-                    }
+                if v.as_str() == Some(s) {
+                    return i;
                 }
             }
         }
@@ -39,104 +84,221 @@ impl ValueArray {
     pub fn read_value(&self, which: usize) -> &Value {
         &self.values[which]
     }
+
+    /// # Safety
+    /// `which` must be less than `len()`.
+    #[inline(always)]
+    pub unsafe fn read_value_unchecked(&self, which: usize) -> &Value {
+        self.values.get_unchecked(which)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
 }
 
-#[derive(Debug, PartialEq, PartialOrd)]
-pub enum Value {
-    Number(f64),
-    Bool(bool),
-    Nil,
-    Str(String),
+impl Default for ValueArray {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Value {
+    pub fn list(items: Vec<Value>) -> Value {
+        Value::obj(Obj::List(RefCell::new(items)))
+    }
+
+    pub fn map(map: Map) -> Value {
+        Value::obj(Obj::Map(RefCell::new(map)))
+    }
+
+    /// An int of any size. Only ints outside the i64 range are kept as big
+    /// ints, so each int has one representation.
+    pub fn bigint(n: BigInt) -> Value {
+        match n.to_i64() {
+            Some(n) => Value::int(n),
+            None => Value::obj(Obj::BigInt(n)),
+        }
+    }
+
+    /// Whether this is an int or a float.
     pub fn is_number(&self) -> bool {
-        if let Value::Number(_) = self {
-            true
-        } else {
-            false
+        matches!(self.kind(), Kind::Number(_) | Kind::Int(_) | Kind::Obj(Obj::BigInt(_)))
+    }
+
+    pub fn is_bool(&self) -> bool {
+        matches!(self.kind(), Kind::Bool(_))
+    }
+
+    pub fn is_nil(&self) -> bool {
+        matches!(self.kind(), Kind::Nil)
+    }
+
+    pub fn is_string(&self) -> bool {
+        self.as_str().is_some()
+    }
+
+    /// The value of an int or float, as a float.
+    pub fn as_number(&self) -> Option<f64> {
+        match self.kind() {
+            Kind::Number(n) => Some(n),
+            Kind::Int(n) => Some(n as f64),
+            Kind::Obj(Obj::BigInt(n)) => Some(n.to_f64()),
+            _ => None,
         }
     }
 
-    pub fn is_falsey(&self) -> bool {
-        matches!(self, Value::Nil | Value::Bool(false))
+    /// The value of an int that fits in 64 bits.
+    pub fn as_int(&self) -> Option<i64> {
+        match self.kind() {
+            Kind::Int(n) => Some(n),
+            _ => None,
+        }
     }
-}
 
-impl Clone for Value {
-    fn clone(&self) -> Self {
-        match self {
-            Value::Number(n) => Value::Number(*n),
-            Value::Bool(b)   => Value::Bool(*b),
-            Value::Nil       => Value::Nil,
-            Value::Str(s)    => Value::Str(s.clone()),
+    pub fn as_bigint(&self) -> Option<&BigInt> {
+        match self.kind() {
+            Kind::Obj(Obj::BigInt(n)) => Some(n),
+            _ => None,
         }
     }
-}
 
-impl Display for Value {
-    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
-        match self {
-            Value::Bool(b) => write!(f, "{b}"),
-            Value::Number(n) => write!(f, "{n}"),
-            Value::Nil => write!(f, "nil"),
-            Value::Str(s) => write!(f, "{s}"),
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.kind() {
+            Kind::Bool(b) => Some(b),
+            _ => None,
         }
     }
-}
 
-impl Add for Value {
-    type Output = Value;
+    pub fn as_str(&self) -> Option<&str> {
+        match self.kind() {
+            Kind::Obj(Obj::Str(s)) => Some(s),
+            _ => None,
+        }
+    }
 
-    fn add(self, other: Value) -> Value {
-        match (self, other) {
-            (Value::Number(a), Value::Number(b)) => Value::Number(a + b),
-            (Value::Str(a), Value::Str(b)) => Value::Str(a + &b),
-            _ => panic!("Operands must be two numbers or two strings"),
+    pub fn as_list(&self) -> Option<&RefCell<Vec<Value>>> {
+        match self.kind() {
+            Kind::Obj(Obj::List(items)) => Some(items),
+            _ => None,
         }
     }
-}
 
-impl Sub for Value {
-    type Output = Value;
+    pub fn as_map(&self) -> Option<&RefCell<Map>> {
+        match self.kind() {
+            Kind::Obj(Obj::Map(map)) => Some(map),
+            _ => None,
+        }
+    }
 
-    fn sub(self, other: Value) -> Value {
-        match (self, other) {
-            (Value::Number(a), Value::Number(b)) => Value::Number(a - b),
-            _ => panic!("Operands must be two numbers"),
+    /// `self[index]`, or the message for the runtime error. Reading a key a
+    /// map doesn't have is an error, like reading an undefined variable.
+    pub fn get_index(&self, index: &Value) -> Result<Value, String> {
+        if let Some(map) = self.as_map() {
+            return map.borrow().get(index)?.cloned().ok_or_else(|| undefined_key(index));
         }
+        let items = self.as_list().ok_or("Only lists and maps can be indexed.")?.borrow();
+        let i = list_index(index, items.len())?;
+        Ok(items[i].clone())
     }
-}
 
-impl Mul for Value {
-    type Output = Value;
+    /// `self[index] = value`, or the message for the runtime error. Returns
+    /// whether a map gained an entry, so the caller can account for it.
+    pub fn set_index(&self, index: &Value, value: Value) -> Result<bool, String> {
+        if let Some(map) = self.as_map() {
+            return map.borrow_mut().insert(index, value);
+        }
+        let mut items = self.as_list().ok_or("Only lists and maps can be indexed.")?.borrow_mut();
+        let i = list_index(index, items.len())?;
+        items[i] = value;
+        Ok(false)
+    }
 
-    fn mul(self, other: Value) -> Value {
-        match (self, other) {
-            (Value::Number(a), Value::Number(b)) => Value::Number(a * b),
-            _ => panic!("Operands must be two numbers"),
+    pub fn as_native(&self) -> Option<&Native> {
+        match self.kind() {
+            Kind::Obj(Obj::Native(n)) => Some(n),
+            _ => None,
         }
     }
+
+    pub fn type_name(&self) -> &'static str {
+        match self.kind() {
+            Kind::Number(_) => "float",
+            Kind::Int(_) | Kind::Obj(Obj::Int(_) | Obj::BigInt(_)) => "int",
+            Kind::Bool(_) => "bool",
+            Kind::Nil => "nil",
+            Kind::Obj(Obj::Str(_)) => "string",
+            Kind::Obj(Obj::Native(_)) => "native function",
+            Kind::Obj(Obj::List(_)) => "list",
+            Kind::Obj(Obj::Map(_)) => "map",
+        }
+    }
+
+    pub fn is_falsey(&self) -> bool {
+        matches!(self.kind(), Kind::Nil | Kind::Bool(false))
+    }
 }
 
-impl Div for Value {
-    type Output = Value;
+/// Checks that `index` is a whole number within a list of `len` elements.
+pub fn list_index(index: &Value, len: usize) -> Result<usize, String> {
+    let n = index.as_number().ok_or("List index must be a number.")?;
+    if n.fract() != 0.0 {
+        return Err(format!("List index {n} is not an integer."));
+    }
+    if n < 0.0 || n >= len as f64 {
+        return Err(format!("List index {n} is out of bounds for length {len}."));
+    }
+    Ok(n as usize)
+}
 
-    fn div(self, other: Value) -> Value {
-        match (self, other) {
-            (Value::Number(a), Value::Number(b)) => Value::Number(a / b),
-            _ => panic!("Operands must be two numbers"),
+/// The message for reading a key a map doesn't have. String keys are quoted
+/// so `m["1"]` and `m[1]` can be told apart.
+pub fn undefined_key(key: &Value) -> String {
+    match key.as_str() {
+        Some(s) => format!("Undefined key \"{s}\"."),
+        None => format!("Undefined key {key}."),
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        if self.is_number() && other.is_number() {
+            return compare(self, other) == Some(std::cmp::Ordering::Equal);
+        }
+        match (self.kind(), other.kind()) {
+            (Kind::Bool(a), Kind::Bool(b))     => a == b,
+            (Kind::Nil, Kind::Nil)             => true,
+            (Kind::Obj(a), Kind::Obj(b))       => a == b,
+            _ => false,
         }
     }
 }
 
-impl Neg for Value {
-    type Output = Value;
+impl Debug for Value {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self.kind() {
+            Kind::Number(n) => write!(f, "Number({n:?})"),
+            Kind::Int(n)    => write!(f, "Int({n})"),
+            Kind::Bool(b)   => write!(f, "Bool({b:?})"),
+            Kind::Nil       => write!(f, "Nil"),
+            Kind::Obj(o)    => write!(f, "{o:?}"),
+        }
+    }
+}
 
-    fn neg(self) -> Value {
-        match self {
-            Value::Number(a) => Value::Number(-a),
-            _ => panic!("Operand must be a number"),
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self.kind() {
+            Kind::Bool(b) => write!(f, "{b}"),
+            // floats always show a point or exponent, so `1.0` isn't `1`
+            Kind::Number(n) => write!(f, "{n:?}"),
+            Kind::Int(n) => write!(f, "{n}"),
+            Kind::Nil => write!(f, "nil"),
+            Kind::Obj(o) => write!(f, "{o}"),
         }
     }
 }
@@ -0,0 +1,467 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::ops::{Add, BitAnd, BitOr, BitXor, Mul, Neg, Not, Shl, Shr, Sub};
+
+/// An arbitrary-precision integer: a sign and a magnitude in base 2^32 limbs,
+/// least significant first. The magnitude never has a leading zero limb and
+/// zero is never negative, so equal numbers are structurally equal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut magnitude: Vec<u32>) -> BigInt {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        let negative = negative && !magnitude.is_empty();
+        BigInt { negative, magnitude }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    /// The number of 32-bit limbs in the magnitude, for heap accounting.
+    pub fn limbs(&self) -> usize {
+        self.magnitude.len()
+    }
+
+    /// The number of bits in the magnitude.
+    pub fn bits(&self) -> u64 {
+        match self.magnitude.last() {
+            None => 0,
+            Some(top) => (self.magnitude.len() as u64 - 1) * 32 + (32 - top.leading_zeros()) as u64,
+        }
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.magnitude.len() > 2 {
+            return None;
+        }
+        let m = self.magnitude.iter().rev().fold(0u64, |acc, &limb| acc << 32 | limb as u64);
+        if self.negative {
+            0i64.checked_sub_unsigned(m)
+        } else {
+            i64::try_from(m).ok()
+        }
+    }
+
+    /// The nearest float, or an infinity past the float range.
+    pub fn to_f64(&self) -> f64 {
+        // each step can round, so the last bit may be off for huge values
+        let m = self.magnitude.iter().rev().fold(0.0, |acc, &limb| acc * 4_294_967_296.0 + limb as f64);
+        if self.negative { -m } else { m }
+    }
+
+    /// The exact value of a whole, finite float.
+    pub fn from_f64(f: f64) -> BigInt {
+        debug_assert!(f.is_finite() && f.fract() == 0.0);
+        let bits = f.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64 - 1075;
+        let mantissa = (bits & ((1 << 52) - 1)) | if bits >> 52 & 0x7ff == 0 { 0 } else { 1 << 52 };
+        let magnitude = if exponent >= 0 {
+            shift_left(&from_u64(mantissa), exponent as usize)
+        } else {
+            // only fractional bits are shifted out, and a whole float has none
+            from_u64(mantissa >> (-exponent).min(63))
+        };
+        BigInt::new(f < 0.0, magnitude)
+    }
+
+    /// Parses digits in `radix`, which have already been checked.
+    pub fn parse(digits: &str, radix: u32) -> BigInt {
+        let mut magnitude = Vec::new();
+        for c in digits.chars() {
+            let digit = c.to_digit(radix).expect("the scanner only lets digits through");
+            mul_add_small(&mut magnitude, radix, digit);
+        }
+        BigInt::new(false, magnitude)
+    }
+
+    /// Division rounding towards negative infinity, and the remainder that
+    /// goes with it, which has the sign of the divisor.
+    ///
+    /// # Panics
+    /// If `divisor` is zero.
+    pub fn div_mod_floor(&self, divisor: &BigInt) -> (BigInt, BigInt) {
+        assert!(!divisor.is_zero(), "division by zero");
+        let (q, r) = div_rem(&self.magnitude, &divisor.magnitude);
+        let (q, r) = (BigInt::new(self.negative != divisor.negative, q), BigInt::new(self.negative, r));
+        if !r.is_zero() && self.negative != divisor.negative {
+            (&q - &BigInt::from(1i64), &r + divisor)
+        } else {
+            (q, r)
+        }
+    }
+
+    /// `self` raised to `exponent`, by repeated squaring.
+    pub fn pow(&self, mut exponent: u64) -> BigInt {
+        let mut base = self.clone();
+        let mut result = BigInt::from(1i64);
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = &result * &base;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+
+    /// The two's complement bits, sign-extended to `len` limbs.
+    fn twos_complement(&self, len: usize) -> Vec<u32> {
+        let mut limbs = self.magnitude.clone();
+        limbs.resize(len, 0);
+        if self.negative {
+            // -m is !(m - 1)
+            let mut borrow = true;
+            for limb in &mut limbs {
+                let (v, b) = limb.overflowing_sub(borrow as u32);
+                *limb = !v;
+                borrow = b;
+            }
+        }
+        limbs
+    }
+
+    fn from_twos_complement(mut limbs: Vec<u32>) -> BigInt {
+        let negative = limbs.last().is_some_and(|top| top >> 31 == 1);
+        if negative {
+            // m is !x + 1
+            let mut carry = true;
+            for limb in &mut limbs {
+                let (v, c) = (!*limb).overflowing_add(carry as u32);
+                *limb = v;
+                carry = c;
+            }
+        }
+        BigInt::new(negative, limbs)
+    }
+
+    /// Applies `f` to the two's complement bits of both, as if each were
+    /// sign-extended forever.
+    fn bitwise(&self, other: &BigInt, f: impl Fn(u32, u32) -> u32) -> BigInt {
+        // one extra limb holds the sign
+        let len = self.magnitude.len().max(other.magnitude.len()) + 1;
+        let (a, b) = (self.twos_complement(len), other.twos_complement(len));
+        BigInt::from_twos_complement(a.iter().zip(&b).map(|(&x, &y)| f(x, y)).collect())
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(n: i64) -> BigInt {
+        BigInt::new(n < 0, from_u64(n.unsigned_abs()))
+    }
+}
+
+impl From<i128> for BigInt {
+    fn from(n: i128) -> BigInt {
+        let m = n.unsigned_abs();
+        let mut magnitude = from_u64(m as u64);
+        magnitude.resize(2, 0);
+        magnitude.extend(from_u64((m >> 64) as u64));
+        BigInt::new(n < 0, magnitude)
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitudes(&self.magnitude, &other.magnitude),
+            (true, true) => compare_magnitudes(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.magnitude.clone())
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_magnitudes(&self.magnitude, &other.magnitude));
+        }
+        // opposite signs: the larger magnitude wins
+        match compare_magnitudes(&self.magnitude, &other.magnitude) {
+            Ordering::Less => BigInt::new(other.negative, sub_magnitudes(&other.magnitude, &self.magnitude)),
+            _ => BigInt::new(self.negative, sub_magnitudes(&self.magnitude, &other.magnitude)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        let mut product = vec![0u32; self.magnitude.len() + other.magnitude.len()];
+        for (i, &a) in self.magnitude.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.magnitude.iter().enumerate() {
+                let t = a as u64 * b as u64 + product[i + j] as u64 + carry;
+                product[i + j] = t as u32;
+                carry = t >> 32;
+            }
+            product[i + other.magnitude.len()] = carry as u32;
+        }
+        BigInt::new(self.negative != other.negative, product)
+    }
+}
+
+impl Not for &BigInt {
+    type Output = BigInt;
+
+    fn not(self) -> BigInt {
+        &-self - &BigInt::from(1i64)
+    }
+}
+
+impl BitAnd for &BigInt {
+    type Output = BigInt;
+
+    fn bitand(self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a & b)
+    }
+}
+
+impl BitOr for &BigInt {
+    type Output = BigInt;
+
+    fn bitor(self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a | b)
+    }
+}
+
+impl BitXor for &BigInt {
+    type Output = BigInt;
+
+    fn bitxor(self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a ^ b)
+    }
+}
+
+impl Shl<usize> for &BigInt {
+    type Output = BigInt;
+
+    fn shl(self, bits: usize) -> BigInt {
+        BigInt::new(self.negative, shift_left(&self.magnitude, bits))
+    }
+}
+
+/// Rounds towards negative infinity, like dividing by a power of two with
+/// `div_mod_floor`.
+impl Shr<usize> for &BigInt {
+    type Output = BigInt;
+
+    fn shr(self, bits: usize) -> BigInt {
+        if !self.negative {
+            return BigInt::new(false, shift_right(&self.magnitude, bits));
+        }
+        // -m >> n is -((m - 1 >> n) + 1)
+        let one = BigInt::from(1i64);
+        let m = &-self - &one;
+        -&(&BigInt::new(false, shift_right(&m.magnitude, bits)) + &one)
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        // peel off nine decimal digits at a time, least significant first
+        const CHUNK: u32 = 1_000_000_000;
+        let mut chunks = Vec::new();
+        let mut rest = self.magnitude.clone();
+        while !rest.is_empty() {
+            chunks.push(div_rem_small(&mut rest, CHUNK));
+        }
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((first, rest)) => {
+                write!(f, "{first}")?;
+                rest.iter().rev().try_for_each(|chunk| write!(f, "{chunk:09}"))
+            }
+        }
+    }
+}
+
+fn from_u64(n: u64) -> Vec<u32> {
+    let mut magnitude = vec![n as u32, (n >> 32) as u32];
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+    magnitude
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &x) in long.iter().enumerate() {
+        let t = x as u64 + short.get(i).copied().unwrap_or(0) as u64 + carry;
+        sum.push(t as u32);
+        carry = t >> 32;
+    }
+    sum.push(carry as u32);
+    sum
+}
+
+/// `a - b`, where `a` is at least `b`.
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &x) in a.iter().enumerate() {
+        let t = x as i64 - b.get(i).copied().unwrap_or(0) as i64 - borrow;
+        difference.push(t as u32);
+        borrow = (t < 0) as i64;
+    }
+    debug_assert_eq!(borrow, 0);
+    difference
+}
+
+/// `magnitude * factor + addend`, in place.
+fn mul_add_small(magnitude: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = addend as u64;
+    for limb in magnitude.iter_mut() {
+        let t = *limb as u64 * factor as u64 + carry;
+        *limb = t as u32;
+        carry = t >> 32;
+    }
+    if carry != 0 {
+        magnitude.push(carry as u32);
+    }
+}
+
+/// Divides in place, returning the remainder.
+fn div_rem_small(magnitude: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for limb in magnitude.iter_mut().rev() {
+        let t = remainder << 32 | *limb as u64;
+        *limb = (t / divisor as u64) as u32;
+        remainder = t % divisor as u64;
+    }
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+    remainder as u32
+}
+
+fn shift_left(magnitude: &[u32], bits: usize) -> Vec<u32> {
+    let mut shifted = vec![0; bits / 32];
+    let mut carry = 0u64;
+    for &limb in magnitude {
+        let t = (limb as u64) << (bits % 32) | carry;
+        shifted.push(t as u32);
+        carry = t >> 32;
+    }
+    shifted.push(carry as u32);
+    shifted
+}
+
+fn shift_right(magnitude: &[u32], bits: usize) -> Vec<u32> {
+    let (skip, bits) = (bits / 32, bits % 32);
+    (skip..magnitude.len())
+        .map(|i| {
+            let pair = magnitude[i] as u64 | (magnitude.get(i + 1).copied().unwrap_or(0) as u64) << 32;
+            (pair >> bits) as u32
+        })
+        .collect()
+}
+
+/// Truncating division of magnitudes, by Knuth's algorithm D (TAOCP 4.3.1).
+fn div_rem(u: &[u32], v: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if compare_magnitudes(u, v) == Ordering::Less {
+        return (Vec::new(), u.to_vec());
+    }
+    if let [divisor] = v {
+        let mut quotient = u.to_vec();
+        let remainder = div_rem_small(&mut quotient, *divisor);
+        return (quotient, from_u64(remainder as u64));
+    }
+
+    // normalize so the divisor's top limb has its high bit set, which keeps
+    // each estimated quotient digit at most two too large
+    let shift = v[v.len() - 1].leading_zeros() as usize;
+    let vn = shift_left(v, shift);
+    let vn = &vn[..v.len()];
+    let mut un = shift_left(u, shift);
+    let (n, m) = (v.len(), u.len() - v.len());
+    let mut quotient = vec![0u32; m + 1];
+
+    const BASE: u64 = 1 << 32;
+    for j in (0..=m).rev() {
+        let top = (un[j + n] as u64) << 32 | un[j + n - 1] as u64;
+        let mut qhat = top / vn[n - 1] as u64;
+        let mut rhat = top % vn[n - 1] as u64;
+        while qhat >= BASE || qhat * vn[n - 2] as u64 > (rhat << 32 | un[j + n - 2] as u64) {
+            qhat -= 1;
+            rhat += vn[n - 1] as u64;
+            if rhat >= BASE {
+                break;
+            }
+        }
+
+        // un[j..=j + n] -= qhat * vn
+        let mut borrow = 0i64;
+        for i in 0..n {
+            let p = qhat * vn[i] as u64;
+            let t = un[i + j] as i64 - borrow - (p & 0xffff_ffff) as i64;
+            un[i + j] = t as u32;
+            borrow = (p >> 32) as i64 - (t >> 32);
+        }
+        let t = un[j + n] as i64 - borrow;
+        un[j + n] = t as u32;
+
+        quotient[j] = qhat as u32;
+        if t < 0 {
+            // qhat was one too large: add the divisor back
+            quotient[j] -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let t = un[i + j] as u64 + vn[i] as u64 + carry;
+                un[i + j] = t as u32;
+                carry = t >> 32;
+            }
+            un[j + n] = un[j + n].wrapping_add(carry as u32);
+        }
+    }
+
+    // undo the normalization on what's left
+    let remainder = (0..n)
+        .map(|i| ((un[i] as u64 | (un[i + 1] as u64) << 32) >> shift) as u32)
+        .collect();
+    (quotient, remainder)
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use super::number::float_as_int;
+use super::{BigInt, Kind, Obj, Value};
+
+/// A value that can be used as a map key: a number other than NaN, a string,
+/// a bool or nil. Two keys are equal exactly when the values are `==`, and
+/// equal keys hash the same, so `0`, `0.0` and `-0.0` are one key.
+#[derive(Debug, Clone)]
+pub struct HashKey(Value);
+
+impl HashKey {
+    /// The key for `value`, or the message for the runtime error.
+    pub fn new(value: &Value) -> Result<HashKey, String> {
+        match value.kind() {
+            Kind::Number(n) if n.is_nan() => Err("NaN can't be used as a map key.".to_string()),
+            Kind::Number(_) | Kind::Int(_) | Kind::Bool(_) | Kind::Nil | Kind::Obj(Obj::Str(_) | Obj::BigInt(_)) => {
+                Ok(HashKey(value.clone()))
+            }
+            _ => Err(format!("A {} can't be used as a map key.", value.type_name())),
+        }
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.0
+    }
+}
+
+impl PartialEq for HashKey {
+    fn eq(&self, other: &HashKey) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for HashKey {}
+
+impl Hash for HashKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.0.kind() {
+            // a float equal to an int hashes as that int
+            Kind::Int(n) => (0u8, n).hash(state),
+            Kind::Obj(Obj::BigInt(n)) => (5u8, n).hash(state),
+            Kind::Number(n) => match float_as_int(n) {
+                Some(n) => (0u8, n).hash(state),
+                None if n.fract() == 0.0 => (5u8, BigInt::from_f64(n)).hash(state),
+                None => (4u8, n.to_bits()).hash(state),
+            },
+            Kind::Bool(b) => (1u8, b).hash(state),
+            Kind::Nil => 2u8.hash(state),
+            Kind::Obj(Obj::Str(s)) => (3u8, s).hash(state),
+            Kind::Obj(_) => unreachable!("HashKey::new only accepts scalars and strings"),
+        }
+    }
+}
+
+/// The entries of a map, kept in insertion order so iterating and printing
+/// are deterministic.
+#[derive(Default)]
+pub struct Map {
+    entries: Vec<(HashKey, Value)>,
+    index: HashMap<HashKey, usize>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &Value) -> Result<Option<&Value>, String> {
+        let key = HashKey::new(key)?;
+        Ok(self.index.get(&key).map(|&i| &self.entries[i].1))
+    }
+
+    /// Adds or replaces an entry. Returns whether the key is new.
+    pub fn insert(&mut self, key: &Value, value: Value) -> Result<bool, String> {
+        let key = HashKey::new(key)?;
+        match self.index.get(&key) {
+            Some(&i) => {
+                self.entries[i].1 = value;
+                Ok(false)
+            }
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+                Ok(true)
+            }
+        }
+    }
+
+    /// Removes an entry, keeping the others in order.
+    pub fn remove(&mut self, key: &Value) -> Result<Option<Value>, String> {
+        let key = HashKey::new(key)?;
+        let Some(i) = self.index.remove(&key) else {
+            return Ok(None);
+        };
+        let (_, value) = self.entries.remove(i);
+        for (key, _) in &self.entries[i..] {
+            *self.index.get_mut(key).unwrap() -= 1;
+        }
+        Ok(Some(value))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Value, &Value)> {
+        self.entries.iter().map(|(k, v)| (k.value(), v))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(k, _)| k.value())
+    }
+}
@@ -0,0 +1,128 @@
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use super::{Kind, Obj};
+
+// Any double whose quiet-NaN bits are all set is not a number we produce
+// ourselves (see `Value::number`), so the remaining 51 bits are free to encode
+// the other types. Object pointers additionally set the sign bit. Ints that
+// fit in 48 bits set `INT_BIT` instead and keep their two's complement bits
+// in the low 48; wider ones are boxed as `Obj::Int`.
+const SIGN_BIT: u64 = 0x8000_0000_0000_0000;
+const QNAN: u64     = 0x7ffc_0000_0000_0000;
+const INT_BIT: u64  = 0x0002_0000_0000_0000;
+const PAYLOAD: u64  = 0x0000_ffff_ffff_ffff;
+
+const TAG_NIL: u64   = 1;
+const TAG_FALSE: u64 = 2;
+const TAG_TRUE: u64  = 3;
+
+const NIL_VAL: u64   = QNAN | TAG_NIL;
+const FALSE_VAL: u64 = QNAN | TAG_FALSE;
+const TRUE_VAL: u64  = QNAN | TAG_TRUE;
+
+/// The compact representation: every value fits in a single 64-bit word.
+/// Objects are `Rc<Obj>` pointers stored in the low 48 bits; `Clone` and
+/// `Drop` keep the reference count in step. The marker makes a `Value` as
+/// `!Send` and `!Sync` as the `Rc` it may hold, the same as the tagged
+/// representation.
+pub struct Value(u64, PhantomData<Rc<Obj>>);
+
+impl Value {
+    const fn from_bits(bits: u64) -> Value {
+        Value(bits, PhantomData)
+    }
+
+    pub fn number(n: f64) -> Value {
+        // Canonicalize so a computed NaN can never look like a tagged value.
+        if n.is_nan() {
+            Value::from_bits(f64::NAN.to_bits())
+        } else {
+            Value::from_bits(n.to_bits())
+        }
+    }
+
+    pub fn int(n: i64) -> Value {
+        // the round trip through 48 bits loses nothing
+        if (n << 16) >> 16 == n {
+            Value::from_bits(QNAN | INT_BIT | (n as u64 & PAYLOAD))
+        } else {
+            Value::obj(Obj::Int(n))
+        }
+    }
+
+    pub fn boolean(b: bool) -> Value {
+        if b {
+            Value::from_bits(TRUE_VAL)
+        } else {
+            Value::from_bits(FALSE_VAL)
+        }
+    }
+
+    pub fn nil() -> Value {
+        Value::from_bits(NIL_VAL)
+    }
+
+    pub fn string<S: Into<String>>(s: S) -> Value {
+        Value::obj(Obj::Str(s.into()))
+    }
+
+    pub fn obj(o: Obj) -> Value {
+        let ptr = Rc::into_raw(Rc::new(o)) as u64;
+        // checked in release builds too, as a wider pointer would corrupt the
+        // tag bits
+        assert!(ptr & (SIGN_BIT | QNAN) == 0, "object pointer wider than 48 bits");
+        Value::from_bits(SIGN_BIT | QNAN | ptr)
+    }
+
+    fn is_obj(&self) -> bool {
+        self.0 & (QNAN | SIGN_BIT) == (QNAN | SIGN_BIT)
+    }
+
+    fn obj_ptr(&self) -> *const Obj {
+        (self.0 & !(SIGN_BIT | QNAN)) as *const Obj
+    }
+
+    pub(crate) fn kind(&self) -> Kind<'_> {
+        if self.0 & QNAN != QNAN {
+            Kind::Number(f64::from_bits(self.0))
+        } else if self.is_obj() {
+            // SAFETY: the pointer came from `Rc::into_raw` and this value owns
+            // one strong count on it, so it outlives the borrow of `self`.
+            match unsafe { &*self.obj_ptr() } {
+                Obj::Int(n) => Kind::Int(*n),
+                obj => Kind::Obj(obj),
+            }
+        } else if self.0 & INT_BIT != 0 {
+            // shifting back down sign-extends the payload
+            Kind::Int(((self.0 & PAYLOAD) << 16) as i64 >> 16)
+        } else {
+            match self.0 {
+                NIL_VAL   => Kind::Nil,
+                FALSE_VAL => Kind::Bool(false),
+                TRUE_VAL  => Kind::Bool(true),
+                _ => unreachable!("invalid NaN-boxed value {:#x}", self.0),
+            }
+        }
+    }
+}
+
+impl Clone for Value {
+    fn clone(&self) -> Self {
+        if self.is_obj() {
+            // SAFETY: see `kind`; the new `Value` takes ownership of the
+            // extra strong count.
+            unsafe { Rc::increment_strong_count(self.obj_ptr()) };
+        }
+        Value::from_bits(self.0)
+    }
+}
+
+impl Drop for Value {
+    fn drop(&mut self) {
+        if self.is_obj() {
+            // SAFETY: releases the strong count owned by this value.
+            unsafe { Rc::decrement_strong_count(self.obj_ptr()) };
+        }
+    }
+}
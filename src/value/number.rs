@@ -0,0 +1,264 @@
+use std::cmp::Ordering;
+
+use super::{BigInt, Kind, Obj, Value};
+
+// 2^63, the first float past the end of the i64 range
+const INT_LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
+// the most bits `**` and `<<` will produce, so `2 ** 10 ** 10` fails at once
+// rather than after exhausting memory
+const MAX_BITS: u64 = 1 << 20;
+
+/// The arithmetic and bitwise operators, for `arithmetic`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arith {
+    Add,
+    Sub,
+    Mul,
+    /// Always gives a float, like `7 / 2` giving `3.5`.
+    Div,
+    /// `~/`, rounding towards negative infinity.
+    FloorDiv,
+    /// `%`, with the sign of the divisor so it agrees with `~/`.
+    Mod,
+    /// `**`. Exact for ints with a non-negative exponent, otherwise a float.
+    Pow,
+    /// `&`, `|` and `^` work on ints as if in two's complement, so
+    /// `-1 & 0xFF` is `255`.
+    BitAnd,
+    BitOr,
+    BitXor,
+    /// `<<`, which never overflows since ints grow.
+    ShiftLeft,
+    /// `>>`, rounding towards negative infinity like `~/`.
+    ShiftRight,
+}
+
+impl Arith {
+    /// Whether this only makes sense for ints.
+    fn is_bitwise(self) -> bool {
+        matches!(self, Arith::BitAnd | Arith::BitOr | Arith::BitXor | Arith::ShiftLeft | Arith::ShiftRight)
+    }
+}
+
+/// A number taken apart. Ints and big ints stay exact until they meet a
+/// float.
+#[derive(Clone, Copy)]
+enum Num<'a> {
+    Int(i64),
+    Big(&'a BigInt),
+    Float(f64),
+}
+
+impl Num<'_> {
+    fn of(value: &Value) -> Option<Num<'_>> {
+        match value.kind() {
+            Kind::Int(n) => Some(Num::Int(n)),
+            Kind::Obj(Obj::BigInt(n)) => Some(Num::Big(n)),
+            Kind::Number(n) => Some(Num::Float(n)),
+            _ => None,
+        }
+    }
+
+    fn to_f64(self) -> f64 {
+        match self {
+            Num::Int(n) => n as f64,
+            Num::Big(n) => n.to_f64(),
+            Num::Float(n) => n,
+        }
+    }
+
+    /// The exact value of an int or big int.
+    fn to_big(self) -> BigInt {
+        match self {
+            Num::Int(n) => BigInt::from(n),
+            Num::Big(n) => n.clone(),
+            Num::Float(_) => unreachable!("floats are handled before ints widen"),
+        }
+    }
+}
+
+/// `a op b` for two numbers, or the message for the runtime error. Ints that
+/// overflow become big ints, and big ints that shrink back into range become
+/// ints again, so an int result is always exact. The VM and the tree-walking
+/// interpreter both come through here, so they agree on every result.
+pub fn arithmetic(op: Arith, a: &Value, b: &Value) -> Result<Value, String> {
+    let (a, b) = match (Num::of(a), Num::of(b)) {
+        (Some(Num::Float(_)), _) | (_, Some(Num::Float(_))) | (None, _) | (_, None) if op.is_bitwise() => {
+            return Err("Operands must be integers.".to_string())
+        }
+        (Some(a), Some(b)) => (a, b),
+        _ => return Err("Operands must be numbers.".to_string()),
+    };
+    match (a, b) {
+        (Num::Int(a), Num::Int(b)) => int_arithmetic(op, a, b),
+        (Num::Float(_), _) | (_, Num::Float(_)) => Ok(Value::number(float_arithmetic(op, a.to_f64(), b.to_f64()))),
+        _ => big_arithmetic(op, a.to_big(), b.to_big()),
+    }
+}
+
+fn int_arithmetic(op: Arith, a: i64, b: i64) -> Result<Value, String> {
+    // an i64 op never overflows an i128, apart from the ones that go on to
+    // big ints below
+    let (x, y) = (a as i128, b as i128);
+    Ok(int128(match op {
+        Arith::Add => x + y,
+        Arith::Sub => x - y,
+        Arith::Mul => x * y,
+        Arith::Div => return Ok(Value::number(a as f64 / b as f64)),
+        Arith::FloorDiv | Arith::Mod if b == 0 => return Err("Integer division by zero.".to_string()),
+        Arith::FloorDiv => {
+            let q = x / y;
+            if x % y != 0 && (x < 0) != (y < 0) { q - 1 } else { q }
+        }
+        Arith::Mod => {
+            let r = x % y;
+            if r != 0 && (r < 0) != (y < 0) { r + y } else { r }
+        }
+        Arith::BitAnd => x & y,
+        Arith::BitOr => x | y,
+        Arith::BitXor => x ^ y,
+        Arith::ShiftLeft if (0..64).contains(&b) => x << b,
+        Arith::ShiftRight if (0..128).contains(&b) => x >> b,
+        Arith::Pow if b >= 0 => match u32::try_from(b).ok().and_then(|b| x.checked_pow(b)) {
+            Some(n) => n,
+            None => return big_arithmetic(op, BigInt::from(a), BigInt::from(b)),
+        },
+        Arith::ShiftLeft | Arith::ShiftRight | Arith::Pow => {
+            return big_arithmetic(op, BigInt::from(a), BigInt::from(b))
+        }
+    }))
+}
+
+fn big_arithmetic(op: Arith, a: BigInt, b: BigInt) -> Result<Value, String> {
+    Ok(Value::bigint(match op {
+        Arith::Add => &a + &b,
+        Arith::Sub => &a - &b,
+        Arith::Mul => &a * &b,
+        Arith::Div => return Ok(Value::number(a.to_f64() / b.to_f64())),
+        Arith::FloorDiv | Arith::Mod if b.is_zero() => return Err("Integer division by zero.".to_string()),
+        Arith::FloorDiv => a.div_mod_floor(&b).0,
+        Arith::Mod => a.div_mod_floor(&b).1,
+        Arith::BitAnd => &a & &b,
+        Arith::BitOr => &a | &b,
+        Arith::BitXor => &a ^ &b,
+        Arith::ShiftLeft | Arith::ShiftRight if b < BigInt::default() => {
+            return Err("Negative shift count.".to_string())
+        }
+        Arith::Pow if b < BigInt::default() => return Ok(Value::number(a.to_f64().powf(b.to_f64()))),
+        // any count past the end of memory shifts everything out
+        Arith::ShiftRight => &a >> b.to_i64().map_or(usize::MAX, |n| n as usize),
+        Arith::ShiftLeft => {
+            let count = b.to_i64().filter(|&n| a.bits() + n as u64 <= MAX_BITS);
+            match count {
+                _ if a.is_zero() => a,
+                Some(n) => &a << n as usize,
+                None => return Err("Integer result is too large.".to_string()),
+            }
+        }
+        Arith::Pow => {
+            let count = match b.to_i64() {
+                // 0, 1 and -1 stay small however large the exponent, and
+                // only its parity matters
+                None if a.bits() <= 1 => Some(if (&b & &BigInt::from(1i64)).is_zero() { 2 } else { 1 }),
+                count => count.filter(|&n| a.bits().saturating_sub(1).saturating_mul(n as u64) <= MAX_BITS),
+            };
+            match count {
+                Some(n) => a.pow(n as u64),
+                None => return Err("Integer result is too large.".to_string()),
+            }
+        }
+    }))
+}
+
+fn float_arithmetic(op: Arith, a: f64, b: f64) -> f64 {
+    match op {
+        Arith::Add => a + b,
+        Arith::Sub => a - b,
+        Arith::Mul => a * b,
+        Arith::Div => a / b,
+        Arith::FloorDiv => (a / b).floor(),
+        Arith::Mod => {
+            let r = a % b;
+            if r != 0.0 && (r < 0.0) != (b < 0.0) { r + b } else { r }
+        }
+        Arith::Pow => a.powf(b),
+        Arith::BitAnd | Arith::BitOr | Arith::BitXor | Arith::ShiftLeft | Arith::ShiftRight => {
+            unreachable!("bitwise ops reject floats")
+        }
+    }
+}
+
+/// `-a`, or the message for the runtime error.
+pub fn negate(a: &Value) -> Result<Value, String> {
+    match Num::of(a) {
+        Some(Num::Int(n)) => Ok(int128(-(n as i128))),
+        Some(Num::Big(n)) => Ok(Value::bigint(-n)),
+        Some(Num::Float(n)) => Ok(Value::number(-n)),
+        None => Err("Operand must be a number.".to_string()),
+    }
+}
+
+/// `~a`, which is `-a - 1` for ints of any size.
+pub fn bit_not(a: &Value) -> Result<Value, String> {
+    match Num::of(a) {
+        Some(Num::Int(n)) => Ok(Value::int(!n)),
+        Some(Num::Big(n)) => Ok(Value::bigint(!n)),
+        _ => Err("Operand must be an integer.".to_string()),
+    }
+}
+
+/// How two numbers compare by value, exactly even between an int and a
+/// float. `None` if either isn't a number or one is NaN.
+pub fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (Num::of(a)?, Num::of(b)?) {
+        (Num::Int(a), Num::Int(b)) => Some(a.cmp(&b)),
+        (Num::Float(a), Num::Float(b)) => a.partial_cmp(&b),
+        (Num::Int(a), Num::Float(b)) => compare_int_float(a, b),
+        (Num::Float(a), Num::Int(b)) => compare_int_float(b, a).map(Ordering::reverse),
+        (a, Num::Float(b)) => compare_big_float(&a.to_big(), b),
+        (Num::Float(a), b) => compare_big_float(&b.to_big(), a).map(Ordering::reverse),
+        (a, b) => Some(a.to_big().cmp(&b.to_big())),
+    }
+}
+
+// converting `i` to a float could round it, so compare whole parts as ints
+fn compare_int_float(i: i64, f: f64) -> Option<Ordering> {
+    if f.is_nan() {
+        None
+    } else if f >= INT_LIMIT {
+        Some(Ordering::Less)
+    } else if f < -INT_LIMIT {
+        Some(Ordering::Greater)
+    } else {
+        let whole = f.trunc();
+        match i.cmp(&(whole as i64)) {
+            Ordering::Equal => 0.0.partial_cmp(&(f - whole)),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+// the same, for ints of any size
+fn compare_big_float(i: &BigInt, f: f64) -> Option<Ordering> {
+    if f.is_nan() {
+        None
+    } else if f.is_infinite() {
+        Some(if f > 0.0 { Ordering::Less } else { Ordering::Greater })
+    } else {
+        let whole = f.trunc();
+        match i.cmp(&BigInt::from_f64(whole)) {
+            Ordering::Equal => 0.0.partial_cmp(&(f - whole)),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+fn int128(n: i128) -> Value {
+    i64::try_from(n).map(Value::int).unwrap_or_else(|_| Value::bigint(BigInt::from(n)))
+}
+
+/// The int a float is exactly equal to, if there is one.
+pub(crate) fn float_as_int(f: f64) -> Option<i64> {
+    (f.fract() == 0.0 && (-INT_LIMIT..INT_LIMIT).contains(&f)).then_some(f as i64)
+}
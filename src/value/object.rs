@@ -0,0 +1,134 @@
+use std::cell::RefCell;
+use std::fmt::{Debug, Display, Formatter};
+
+use super::{BigInt, Map, Value};
+use crate::natives::Native;
+
+/// Heap-allocated values. A `Value` only ever holds a reference-counted
+/// pointer to one of these, so pushing and popping never copies the payload.
+pub enum Obj {
+    Str(String),
+    Native(Native),
+    /// Lists are mutable and shared: every `Value` holding one sees changes
+    /// made through the others.
+    List(RefCell<Vec<Value>>),
+    /// Maps are shared the same way.
+    Map(RefCell<Map>),
+    /// An int too wide to pack into a NaN-boxed `Value`. `Value::kind`
+    /// reports it as `Kind::Int`, so nothing else sees this variant.
+    Int(i64),
+    /// An int outside the i64 range. See `Value::bigint`.
+    BigInt(BigInt),
+}
+
+impl Obj {
+    /// Heap bytes taken by a string object with `len` bytes of text, as
+    /// counted against `vm::Limits::heap`.
+    pub fn str_size(len: usize) -> usize {
+        std::mem::size_of::<Obj>() + len
+    }
+
+    /// Heap bytes taken by a big int of `limbs` 32-bit limbs.
+    pub fn bigint_size(limbs: usize) -> usize {
+        std::mem::size_of::<Obj>() + limbs * std::mem::size_of::<u32>()
+    }
+
+    /// Heap bytes taken by a list of `len` elements.
+    pub fn list_size(len: usize) -> usize {
+        std::mem::size_of::<Obj>() + len * std::mem::size_of::<Value>()
+    }
+
+    /// Heap bytes taken by a map of `len` entries.
+    pub fn map_size(len: usize) -> usize {
+        std::mem::size_of::<Obj>() + len * Obj::map_entry_size()
+    }
+
+    /// Heap bytes one more map entry takes: the key and value, plus the key
+    /// and position again in the index.
+    pub fn map_entry_size() -> usize {
+        3 * std::mem::size_of::<Value>() + std::mem::size_of::<usize>()
+    }
+}
+
+// strings compare by contents, lists and maps by identity
+impl PartialEq for Obj {
+    fn eq(&self, other: &Obj) -> bool {
+        match (self, other) {
+            (Obj::Str(a), Obj::Str(b)) => a == b,
+            (Obj::Native(a), Obj::Native(b)) => a == b,
+            (Obj::Int(a), Obj::Int(b)) => a == b,
+            (Obj::BigInt(a), Obj::BigInt(b)) => a == b,
+            (Obj::List(_), Obj::List(_)) | (Obj::Map(_), Obj::Map(_)) => std::ptr::eq(self, other),
+            _ => false,
+        }
+    }
+}
+
+thread_local! {
+    // lists and maps being displayed, so one that contains itself prints as
+    // `[...]` or `{...}`
+    static DISPLAYING: RefCell<Vec<*const Obj>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Display for Obj {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Obj::Str(s) => write!(f, "{s}"),
+            Obj::Native(n) => write!(f, "<native fn {}>", n.name),
+            Obj::Int(n) => write!(f, "{n}"),
+            Obj::BigInt(n) => write!(f, "{n}"),
+            Obj::List(items) => guard(self, f, "[...]", |f| {
+                write!(f, "[")?;
+                for (i, item) in items.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }),
+            Obj::Map(map) => guard(self, f, "{...}", |f| {
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                write!(f, "}}")
+            }),
+        }
+    }
+}
+
+/// Displays a container with `contents`, or as `cycle` if it is already being
+/// displayed further up.
+fn guard(
+    obj: &Obj,
+    f: &mut Formatter,
+    cycle: &str,
+    contents: impl FnOnce(&mut Formatter) -> Result<(), std::fmt::Error>,
+) -> Result<(), std::fmt::Error> {
+    let this = obj as *const Obj;
+    if DISPLAYING.with(|d| d.borrow().contains(&this)) {
+        return write!(f, "{cycle}");
+    }
+
+    DISPLAYING.with(|d| d.borrow_mut().push(this));
+    let result = contents(f);
+    DISPLAYING.with(|d| d.borrow_mut().pop());
+    result
+}
+
+impl Debug for Obj {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Obj::Str(s) => write!(f, "Str({s:?})"),
+            Obj::Native(n) => write!(f, "{n:?}"),
+            Obj::Int(n) => write!(f, "Int({n})"),
+            Obj::BigInt(n) => write!(f, "BigInt({n})"),
+            Obj::List(_) => write!(f, "List({self})"),
+            Obj::Map(_) => write!(f, "Map({self})"),
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::rc::Rc;
+
+use super::{Kind, Obj};
+
+/// The default representation: a plain tagged union, 16 bytes wide.
+#[derive(Clone)]
+pub struct Value(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Number(f64),
+    Int(i64),
+    Bool(bool),
+    Nil,
+    Obj(Rc<Obj>),
+}
+
+impl Value {
+    pub fn number(n: f64) -> Value {
+        Value(Repr::Number(n))
+    }
+
+    pub fn int(n: i64) -> Value {
+        Value(Repr::Int(n))
+    }
+
+    pub fn boolean(b: bool) -> Value {
+        Value(Repr::Bool(b))
+    }
+
+    pub fn nil() -> Value {
+        Value(Repr::Nil)
+    }
+
+    pub fn string<S: Into<String>>(s: S) -> Value {
+        Value::obj(Obj::Str(s.into()))
+    }
+
+    pub fn obj(o: Obj) -> Value {
+        Value(Repr::Obj(Rc::new(o)))
+    }
+
+    pub(crate) fn kind(&self) -> Kind<'_> {
+        match &self.0 {
+            Repr::Number(n) => Kind::Number(*n),
+            Repr::Int(n)    => Kind::Int(*n),
+            Repr::Bool(b)   => Kind::Bool(*b),
+            Repr::Nil       => Kind::Nil,
+            Repr::Obj(o)    => Kind::Obj(o),
+        }
+    }
+}
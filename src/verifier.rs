@@ -0,0 +1,220 @@
+use std::fmt::{Display, Formatter};
+
+use crate::chunk::*;
+use crate::STACK_MAX;
+
+/// Why a chunk was rejected, along with where.
+#[derive(Debug, PartialEq)]
+pub struct VerifyError {
+    pub offset: usize,
+    pub line: usize,
+    pub kind: VerifyErrorKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum VerifyErrorKind {
+    EmptyChunk,
+    UnknownOpcode(u8),
+    TruncatedOperand(OpCode),
+    ConstantOutOfRange { index: usize, count: usize },
+    NameNotString(usize),
+    LocalOutOfRange { slot: usize, height: usize },
+    StackUnderflow { op: OpCode, needs: usize, height: usize },
+    StackOverflow(usize),
+    StackMismatch { expected: usize, found: usize },
+    UnbalancedReturn(usize),
+    FallsOffEnd,
+    BadJumpTarget(usize),
+}
+
+impl Display for VerifyErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        use VerifyErrorKind::*;
+        match self {
+            EmptyChunk => write!(f, "chunk contains no code"),
+            UnknownOpcode(b) => write!(f, "unknown opcode {b}"),
+            TruncatedOperand(op) => write!(f, "{op:?} is missing its operand"),
+            ConstantOutOfRange { index, count } =>
+                write!(f, "constant index {index} out of range ({count} constants)"),
+            NameNotString(index) =>
+                write!(f, "constant {index} used as a variable name is not a string"),
+            LocalOutOfRange { slot, height } =>
+                write!(f, "local slot {slot} out of range (stack height {height})"),
+            StackUnderflow { op, needs, height } =>
+                write!(f, "{op:?} needs {needs} stack values but only {height} are available"),
+            StackOverflow(height) =>
+                write!(f, "stack height {height} exceeds the maximum of {STACK_MAX}"),
+            StackMismatch { expected, found } =>
+                write!(f, "stack height {found} does not match {expected} on another path"),
+            UnbalancedReturn(height) =>
+                write!(f, "{height} values left on the stack at return"),
+            FallsOffEnd => write!(f, "execution runs past the end of the chunk"),
+            BadJumpTarget(target) => write!(f, "jump to {target:04} does not land on an instruction"),
+        }
+    }
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "[line {}] at offset {:04}: {}", self.line, self.offset, self.kind)
+    }
+}
+
+/// Checks a chunk before it is run. Once this passes, `VM::run` can read the
+/// code without bounds checks:
+///
+/// - every opcode is known and its operands are inside the code,
+/// - constant operands index the constant table, and global names are strings,
+/// - local slots refer to values already on the stack,
+/// - jumps land on an instruction,
+/// - every path has a consistent stack height that never underflows or
+///   exceeds `STACK_MAX`, and ends at an `OP_RETURN` with an empty stack.
+pub fn verify(chunk: &Chunk) -> Result<(), VerifyError> {
+    let code = chunk.code();
+    if code.is_empty() {
+        return Err(VerifyError { offset: 0, line: 0, kind: VerifyErrorKind::EmptyChunk });
+    }
+
+    let error = |offset: usize, kind| VerifyError {
+        offset,
+        line: chunk.get_line(offset),
+        kind,
+    };
+
+    // Decode every instruction once. `ops[offset]` is only set at instruction
+    // boundaries, so branch targets can be checked against it.
+    let mut ops: Vec<Option<OpCode>> = vec![None; code.len()];
+    let mut offset = 0;
+    while offset < code.len() {
+        let op = OpCode::try_from(code[offset])
+            .map_err(|b| error(offset, VerifyErrorKind::UnknownOpcode(b)))?;
+
+        if offset + op.operand_len() >= code.len() {
+            return Err(error(offset, VerifyErrorKind::TruncatedOperand(op)));
+        }
+
+        if op.has_constant_operand() {
+            let index = code[offset + 1] as usize;
+            if index >= chunk.constant_count() {
+                return Err(error(offset, VerifyErrorKind::ConstantOutOfRange {
+                    index,
+                    count: chunk.constant_count(),
+                }));
+            }
+            if op != OpCode::Constant && !chunk.read_constant(index).is_string() {
+                return Err(error(offset, VerifyErrorKind::NameNotString(index)));
+            }
+        }
+
+        ops[offset] = Some(op);
+        offset += 1 + op.operand_len();
+    }
+
+    // Propagate stack heights along every path from the entry point.
+    let mut heights: Vec<Option<usize>> = vec![None; code.len()];
+    let mut worklist = vec![(0, 0)];
+
+    while let Some((offset, height)) = worklist.pop() {
+        match heights[offset] {
+            Some(expected) if expected == height => continue,
+            Some(expected) => {
+                return Err(error(offset, VerifyErrorKind::StackMismatch { expected, found: height }));
+            }
+            None => heights[offset] = Some(height),
+        }
+
+        let op = ops[offset].expect("only instruction boundaries are queued");
+        let (pops, pushes) = stack_effect(op, code[offset + op.operand_len()]);
+        if height < pops {
+            return Err(error(offset, VerifyErrorKind::StackUnderflow { op, needs: pops, height }));
+        }
+
+        if let OpCode::GetLocal | OpCode::SetLocal = op {
+            let slot = code[offset + 1] as usize;
+            if slot >= height {
+                return Err(error(offset, VerifyErrorKind::LocalOutOfRange { slot, height }));
+            }
+        }
+
+        let after = height - pops + pushes;
+        if after > STACK_MAX {
+            return Err(error(offset, VerifyErrorKind::StackOverflow(after)));
+        }
+
+        if op == OpCode::Return {
+            if height != 0 {
+                return Err(error(offset, VerifyErrorKind::UnbalancedReturn(height)));
+            }
+            continue;
+        }
+
+        if op.is_jump() {
+            let target = chunk.jump_target(offset);
+            if ops.get(target).is_none_or(Option::is_none) {
+                return Err(error(offset, VerifyErrorKind::BadJumpTarget(target)));
+            }
+            worklist.push((target, after));
+            if op == OpCode::Jump {
+                continue;
+            }
+        }
+
+        let next = offset + 1 + op.operand_len();
+        if next >= code.len() {
+            return Err(error(offset, VerifyErrorKind::FallsOffEnd));
+        }
+        worklist.push((next, after));
+    }
+
+    Ok(())
+}
+
+/// How many values an instruction pops and then pushes. `operand` is its
+/// last operand byte, or the opcode itself if it has none.
+fn stack_effect(op: OpCode, operand: u8) -> (usize, usize) {
+    match op {
+        OpCode::Call => (operand as usize + 1, 1),
+        OpCode::BuildList => (operand as usize, 1),
+        // moves the top value under the `operand` below it
+        OpCode::Bury => (operand as usize + 1, operand as usize + 1),
+        OpCode::Dup => (1, 2),
+        OpCode::Dup2 => (2, 4),
+        // the map stays on the stack under the key and value
+        OpCode::SetIndex | OpCode::MapEntry => (3, 1),
+        OpCode::Return | OpCode::Jump => (0, 0),
+        // the value tested stays on the stack on both paths
+        OpCode::JumpIfFalse | OpCode::JumpIfNotNil => (1, 1),
+        OpCode::Constant
+            | OpCode::Nil
+            | OpCode::True
+            | OpCode::False
+            | OpCode::GetGlobal
+            | OpCode::GetLocal
+            | OpCode::Map => (0, 1),
+        OpCode::Pop
+            | OpCode::DefineGlobal
+            | OpCode::Print => (1, 0),
+        OpCode::SetGlobal
+            | OpCode::SetLocal
+            | OpCode::Negate
+            | OpCode::Not
+            | OpCode::BitNot
+            | OpCode::ToString => (1, 1),
+        OpCode::Equal
+            | OpCode::Greater
+            | OpCode::Less
+            | OpCode::Add
+            | OpCode::Sub
+            | OpCode::Mul
+            | OpCode::Div
+            | OpCode::FloorDiv
+            | OpCode::Mod
+            | OpCode::Pow
+            | OpCode::BitAnd
+            | OpCode::BitOr
+            | OpCode::BitXor
+            | OpCode::ShiftLeft
+            | OpCode::ShiftRight
+            | OpCode::GetIndex => (2, 1),
+    }
+}
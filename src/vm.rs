@@ -1,41 +1,203 @@
 use std::collections::HashMap;
+use std::io::{self, Write};
+use std::cmp::Ordering;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::Arc;
+use std::time::Instant;
 
 use crate::chunk::*;
 use crate::value::*;
-use crate::STACK_MAX;
+use crate::{FRAMES_MAX, STACK_MAX};
 use crate::compiler::*;
+use crate::verifier::verify;
+use crate::debugger::*;
+use crate::natives::{builtins, Capabilities, Context, Native};
 
 pub struct VM {
 //    chunk: Chunk,
-    ip: usize,  // instruction index
     stack: Vec<Value>,
 //    stack_top: usize,
     globals: HashMap<String, Value>,
+    debugger: Option<Debugger>,
+    // where `print` writes
+    output: Box<dyn Write>,
+    // where compile and runtime errors are reported
+    errors: Box<dyn Write>,
+    limits: Limits,
+    // bytes of objects allocated by the current run, see `Limits::heap`
+    heap_bytes: usize,
+    interrupt: InterruptHandle,
+    deadline: Option<Instant>,
+    // what natives may do, fixed when the VM is built
+    capabilities: Capabilities,
 }
 
+/// How many instructions `run` executes between checks for an interrupt or
+/// a passed deadline.
+const POLL_INTERVAL: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InterpretError {
     Compile,
+    Verify,
     Runtime,
+    /// One of the `Limits` was exceeded.
+    InstructionLimit,
+    StackLimit,
+    FrameLimit,
+    HeapLimit,
+    /// Stopped through an `InterruptHandle`, by the deadline, or by the
+    /// debugger with `Resume::Terminate`.
+    Interrupted,
+    /// The script called `exit` with this status. Nothing after the call ran.
+    Exit(i32),
+}
+
+/// Stops a running `VM` from another thread; see `VM::interrupt_handle`.
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    /// Asks the VM to stop the run in progress, which it does within
+    /// `POLL_INTERVAL` instructions with `InterpretError::Interrupted`. Every
+    /// `interpret` starts with the request cleared, so one made while nothing
+    /// is running has no effect.
+    pub fn interrupt(&self) {
+        self.0.store(true, atomic::Ordering::Relaxed);
+    }
+}
+
+/// Bounds on what one `interpret` may use, so untrusted code can't run
+/// forever or exhaust memory. Exceeding one reports a runtime error and
+/// returns the matching `InterpretError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Instructions executed, or `None` for no limit.
+    pub instructions: Option<usize>,
+    /// Values on the stack, across all frames.
+    pub stack: usize,
+    /// Call frames, counting the top-level script.
+    pub frames: usize,
+    /// Bytes of objects allocated while running, or `None` for no limit.
+    /// Nothing is freed, so this bounds the total allocated.
+    pub heap: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            instructions: None,
+            stack: STACK_MAX * FRAMES_MAX,
+            frames: FRAMES_MAX,
+            heap: None,
+        }
+    }
+}
+
+/// Execution state for one chunk of code. `ip` points straight into the
+/// chunk's bytes rather than being an index, so reading the next instruction
+/// is a single load.
+struct CallFrame<'a> {
+    chunk: &'a Chunk,
+    ip: *const u8,
+}
+
+impl<'a> CallFrame<'a> {
+    /// `chunk` must have passed `verifier::verify`, which is what makes the
+    /// unchecked reads below sound.
+    fn new(chunk: &'a Chunk) -> Self {
+        Self {
+            chunk,
+            ip: chunk.code().as_ptr(),
+        }
+    }
+
+    #[inline(always)]
+    fn read_byte(&mut self) -> u8 {
+        // SAFETY: the verifier guarantees every operand is inside the code and
+        // that the code ends in OP_RETURN, so ip never passes the end.
+        unsafe {
+            let byte = *self.ip;
+            self.ip = self.ip.add(1);
+            byte
+        }
+    }
+
+    #[inline(always)]
+    fn read_short(&mut self) -> u16 {
+        u16::from_be_bytes([self.read_byte(), self.read_byte()])
+    }
+
+    /// Skips `distance` bytes forward.
+    #[inline(always)]
+    fn jump(&mut self, distance: u16) {
+        // SAFETY: the verifier checked that every jump lands on an
+        // instruction inside the code.
+        unsafe { self.ip = self.ip.add(distance as usize) }
+    }
+
+    #[inline(always)]
+    fn read_op(&mut self) -> OpCode {
+        // SAFETY: the verifier checked that this byte is an opcode.
+        unsafe { OpCode::from_unchecked(self.read_byte()) }
+    }
+
+    #[inline(always)]
+    fn read_constant(&mut self) -> &'a Value {
+        let index = self.read_byte() as usize;
+        // SAFETY: the verifier checked every constant operand against the table.
+        unsafe { self.chunk.read_constant_unchecked(index) }
+    }
+
+    /// Offset of the next instruction from the start of the chunk.
+    fn offset(&self) -> usize {
+        self.ip as usize - self.chunk.code().as_ptr() as usize
+    }
 }
 
-enum BinaryOp {
-    Add,
-    Sub,
-    Mul,
-    Div,
-    Less,
-    Greater,
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VM {
+    /// A VM with the default capabilities: output and the clock.
     pub fn new() -> Self {
-        Self {
+        Self::with_capabilities(Capabilities::default())
+    }
+
+    /// A VM whose natives may only use `capabilities`. All the builtin
+    /// natives are defined, but calling one that needs more is a runtime
+    /// error.
+    pub fn with_capabilities(capabilities: Capabilities) -> Self {
+        let mut vm = Self {
 //            chunk: Chunk::new(),
-            ip: 0,
             stack: Vec::with_capacity(STACK_MAX),
 //            stack_top: 0,
             globals: HashMap::new(),
+            debugger: None,
+            output: Box::new(io::stdout()),
+            errors: Box::new(io::stderr()),
+            limits: Limits::default(),
+            heap_bytes: 0,
+            interrupt: InterruptHandle::default(),
+            deadline: None,
+            capabilities,
+        };
+        for native in builtins() {
+            vm.define_native(native);
         }
+        vm
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// Makes `native` a global, replacing any global of the same name.
+    pub fn define_native(&mut self, native: Native) {
+        self.globals.insert(native.name.clone(), Value::obj(Obj::Native(native)));
     }
 
     // might be unnecessary
@@ -44,6 +206,50 @@ impl VM {
         self.globals = HashMap::new();
     }
 
+    /// Attaches (or with `None`, detaches) a debugger. While one is attached
+    /// `run` checks for breakpoints and steps before every instruction.
+    pub fn set_debugger(&mut self, debugger: Option<Debugger>) {
+        self.debugger = debugger;
+    }
+
+    /// Redirects the output of `print` statements, e.g. to capture it.
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    /// Redirects error reports, which go to stderr by default.
+    pub fn set_error_output(&mut self, errors: Box<dyn Write>) {
+        self.errors = errors;
+    }
+
+    /// Applies to every later `interpret`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// A handle that can interrupt this VM from any thread.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    /// Interrupts any run still going at `deadline`. It stays set for later
+    /// runs until replaced or cleared with `None`.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    pub fn debugger_mut(&mut self) -> Option<&mut Debugger> {
+        self.debugger.as_mut()
+    }
+
+    pub fn globals(&self) -> &HashMap<String, Value> {
+        &self.globals
+    }
+
     pub fn push(&mut self, value: Value) {
         self.stack.push(value);
     }
@@ -57,17 +263,40 @@ impl VM {
     }
 
     pub fn interpret(&mut self, source: &str) -> Result<(), InterpretError> {
+        // cleared before compiling, so an interrupt while compiling still
+        // stops the run
+        self.clear_interrupt();
         let mut chunk = Chunk::new();
         let mut compiler = Compiler::new(source, &mut chunk);
-        compiler.compile()?;
+        compiler.set_quiet(true);
+        let compiled = compiler.compile();
+        for diagnostic in compiler.diagnostics() {
+            let _ = writeln!(self.errors, "{diagnostic}");
+        }
+        compiled?;
 
-        self.ip = 0;
-        let result = self.run(&chunk);
+        let result = self.verify_and_run(&chunk);
         chunk.free();
 
         result
     }
 
+    /// Runs an already compiled chunk. The chunk is verified once up front so
+    /// the dispatch loop can read it without any further checks.
+    pub fn interpret_chunk(&mut self, chunk: &Chunk) -> Result<(), InterpretError> {
+        self.clear_interrupt();
+        self.verify_and_run(chunk)
+    }
+
+    fn verify_and_run(&mut self, chunk: &Chunk) -> Result<(), InterpretError> {
+        if let Err(e) = verify(chunk) {
+            let _ = writeln!(self.errors, "Invalid bytecode {e}");
+            return Err(InterpretError::Verify);
+        }
+
+        self.run(chunk)
+    }
+
     fn reset_stack(&mut self) {
         self.stack = Vec::<Value>::with_capacity(STACK_MAX);
 //        self.stack_top = 0;
@@ -75,30 +304,68 @@ impl VM {
 
     fn runtime_error<T: ToString> (
         &mut self,
-        chunk: &Chunk,
+        frame: &CallFrame,
+        msg: &T,
+    ) -> Result<(), InterpretError> {
+        self.fail(frame, msg, InterpretError::Runtime)
+    }
+
+    /// Reports `msg` against the instruction just read and unwinds with `error`.
+    fn fail<T: ToString>(
+        &mut self,
+        frame: &CallFrame,
         msg: &T,
+        error: InterpretError,
     ) -> Result<(), InterpretError> {
-        let line = chunk.get_line(self.ip - 1);
-        eprintln!("{}", msg.to_string());
-        eprintln!("[line {line}] in script");
+        let line = frame.chunk.get_line(frame.offset().saturating_sub(1));
+        let _ = writeln!(self.errors, "{}", msg.to_string());
+        let _ = writeln!(self.errors, "[line {line}] in script");
         self.reset_stack();
 
-        Err(InterpretError::Runtime)
+        Err(error)
     }
 
     fn run(&mut self, chunk: &Chunk) -> Result<(), InterpretError> {
+        let mut frame = CallFrame::new(chunk);
+        let mut budget = self.limits.instructions.unwrap_or(usize::MAX);
+        let mut poll = 0;
+        self.heap_bytes = 0;
+
+        // the script itself is the only frame until there are functions
+        if self.limits.frames < 1 {
+            return self.fail(&frame, &"Stack overflow.", InterpretError::FrameLimit);
+        }
+
         loop {
-            if cfg!(debug_assertions) {
+            if cfg!(feature = "trace-execution") {
 
                 print!("         ");
                 for val in self.stack.iter() {
                     print!("[ {:?} ]", val);
                 }
                 println!();
-                chunk.disassemble_instruction(self.ip);
+                chunk.disassemble_instruction(frame.offset());
+            }
+
+            if self.debugger.is_some() && !self.debug_break(&frame) {
+                self.reset_stack();
+                return Err(InterpretError::Interrupted);
+            }
+
+            if budget == 0 {
+                return self.fail(&frame, &"Instruction limit exceeded.", InterpretError::InstructionLimit);
             }
+            budget -= 1;
 
-            let instruction = self.read_byte(chunk);
+            if poll == 0 {
+                poll = POLL_INTERVAL;
+                if self.interrupted() {
+                    return self.fail(&frame, &"Interrupted.", InterpretError::Interrupted);
+                }
+            }
+            poll -= 1;
+
+            let instruction = frame.read_op();
             match instruction {
                 OpCode::Return => {
                     //println!("{:?}", self.pop());
@@ -108,117 +375,324 @@ impl VM {
                 OpCode::Constant => {
                     // ended up cloning here after peppering & around the call stack leading to this,
                     // not sure if its the right choice? I think it might be, though
-                    let constant: Value = self.read_constant(chunk).clone();
+                    let constant: Value = frame.read_constant().clone();
                     self.push(constant);
                 },
                 OpCode::Negate => {
-                    if !self.peek(0).is_number() {
-                        return self.runtime_error(chunk, &"Operand must be a number.");
-                    }
-
-                    let value = self.pop();
-                    self.push(-value);
+                    let result = negate(self.peek(0));
+                    self.push_number(&frame, 1, result)?;
                 },
-                OpCode::Add   => self.binary_op(BinaryOp::Add),
-                OpCode::Sub   => self.binary_op(BinaryOp::Sub),
-                OpCode::Mul   => self.binary_op(BinaryOp::Mul),
-                OpCode::Div   => self.binary_op(BinaryOp::Div),
-                OpCode::Nil   => self.push(Value::Nil),
-                OpCode::True  => self.push(Value::Bool(true)),
-                OpCode::False => self.push(Value::Bool(false)),
+                OpCode::BitNot => {
+                    let result = bit_not(self.peek(0));
+                    self.push_number(&frame, 1, result)?;
+                },
+                OpCode::Add => {
+                    let (b, a) = (self.peek(0), self.peek(1));
+                    if let (Some(a), Some(b)) = (a.as_str(), b.as_str()) {
+                        // charged before concatenating, so the limit holds
+                        // however long the strings get
+                        if !self.allocate(Obj::str_size(a.len() + b.len())) {
+                            return self.fail(&frame, &"Out of memory.", InterpretError::HeapLimit);
+                        }
+                        let (b, a) = (self.pop(), self.pop());
+                        self.push(Value::string(format!("{a}{b}")));
+                    } else if !(a.is_number() && b.is_number()) {
+                        return self.runtime_error(&frame, &"Operands must be two numbers or two strings.");
+                    } else {
+                        let result = arithmetic(Arith::Add, self.peek(1), self.peek(0));
+                        self.push_number(&frame, 2, result)?;
+                    }
+                }
+                OpCode::Sub
+                | OpCode::Mul
+                | OpCode::Div
+                | OpCode::FloorDiv
+                | OpCode::Mod
+                | OpCode::Pow
+                | OpCode::BitAnd
+                | OpCode::BitOr
+                | OpCode::BitXor
+                | OpCode::ShiftLeft
+                | OpCode::ShiftRight => {
+                    let op = match instruction {
+                        OpCode::Sub => Arith::Sub,
+                        OpCode::Mul => Arith::Mul,
+                        OpCode::Div => Arith::Div,
+                        OpCode::FloorDiv => Arith::FloorDiv,
+                        OpCode::Mod => Arith::Mod,
+                        OpCode::Pow => Arith::Pow,
+                        OpCode::BitAnd => Arith::BitAnd,
+                        OpCode::BitOr => Arith::BitOr,
+                        OpCode::BitXor => Arith::BitXor,
+                        OpCode::ShiftLeft => Arith::ShiftLeft,
+                        _ => Arith::ShiftRight,
+                    };
+                    let result = arithmetic(op, self.peek(1), self.peek(0));
+                    self.push_number(&frame, 2, result)?;
+                }
+                OpCode::Nil   => self.push(Value::nil()),
+                OpCode::True  => self.push(Value::boolean(true)),
+                OpCode::False => self.push(Value::boolean(false)),
                 OpCode::Pop   => { self.pop(); }, // why do I need to put this in a block?
+                OpCode::Dup   => self.push(self.peek(0).clone()),
+                OpCode::Dup2  => {
+                    self.push(self.peek(1).clone());
+                    self.push(self.peek(1).clone());
+                }
+                OpCode::Jump  => {
+                    let distance = frame.read_short();
+                    frame.jump(distance);
+                }
+                OpCode::JumpIfFalse => {
+                    let distance = frame.read_short();
+                    if self.peek(0).is_falsey() {
+                        frame.jump(distance);
+                    }
+                }
+                OpCode::JumpIfNotNil => {
+                    let distance = frame.read_short();
+                    if !self.peek(0).is_nil() {
+                        frame.jump(distance);
+                    }
+                }
+                OpCode::Bury  => {
+                    let depth = frame.read_byte() as usize;
+                    let value = self.pop();
+                    self.stack.insert(self.stack.len() - depth, value);
+                }
                 OpCode::GetGlobal => {
-                    let name = self.read_constant(chunk).clone();
+                    let name = frame.read_constant();
 
-                    if let Value::Str(s) = name {
-                        match self.globals.get(&s) {
+                    if let Some(s) = name.as_str() {
+                        match self.globals.get(s) {
                             //TODO do i clone here?
                             Some(v) => self.push(v.clone()),
-                            None    => return self.runtime_error(chunk, &format!("Undefined variable {s}")),
+                            None    => return self.runtime_error(&frame, &format!("Undefined variable {s}")),
                         }
                     }
                 }
                 OpCode::DefineGlobal => {
-                    let name = self.read_constant(chunk).clone();
-                    if let Value::Str(s) = name {
+                    let name = frame.read_constant();
+                    if let Some(s) = name.as_str() {
                         // Might not be necessary here, but we pop the value after we add it to
                         // the hash table to ensure that the VM can still find the value if a GC
                         // is triggerd in the middle of adding it to the hash table since it requires
                         // dynamic allocation when it resizes.
-                        self.globals.insert(s, self.peek(0).clone());
+                        self.globals.insert(s.to_string(), self.peek(0).clone());
                         self.pop();
                     } else {
-                        return self.runtime_error(chunk, &"Unable to read constant from table.");
+                        return self.runtime_error(&frame, &"Unable to read constant from table.");
                     }
                 }
                 OpCode::SetGlobal => {
-                    let name = self.read_constant(chunk).clone();
-                    if let Value::Str(s) = name {
-                        if !self.globals.contains_key(&s) {
-                            return self.runtime_error(chunk, &format!("Undefined variable {s}"));
+                    let name = frame.read_constant();
+                    if let Some(s) = name.as_str() {
+                        if !self.globals.contains_key(s) {
+                            return self.runtime_error(&frame, &format!("Undefined variable {s}"));
                         } else {
-                            self.globals.insert(s, self.peek(0).clone());
+                            self.globals.insert(s.to_string(), self.peek(0).clone());
                         }
                     } else {
-                        return self.runtime_error(chunk, &"Unable to read constant from table.");
+                        return self.runtime_error(&frame, &"Unable to read constant from table.");
                     }
                 }
                 OpCode::GetLocal => {
-                    let slot = self.read_byte(chunk) as usize;
-                    self.stack[slot] = self.peek(0).clone();
+                    let slot = frame.read_byte() as usize;
+                    self.push(self.stack[slot].clone());
                 }
                 OpCode::SetLocal => {
-                    let slot = self.read_byte(chunk) as usize;
+                    let slot = frame.read_byte() as usize;
                     self.stack[slot] = self.peek(0).clone();
                 }
                 OpCode::Equal => {
                     let (b, a) = (self.pop(), self.pop());
-                    self.push(Value::Bool(b == a));
+                    self.push(Value::boolean(b == a));
                 },
                 OpCode::Not   => {
                     let value = self.pop();
-                    self.push(Value::Bool(value.is_falsey()));
+                    self.push(Value::boolean(value.is_falsey()));
                 },
-                OpCode::Greater => self.binary_op(BinaryOp::Greater),
-                OpCode::Less    => self.binary_op(BinaryOp::Less),
+                OpCode::Greater | OpCode::Less => {
+                    if !self.peek(0).is_number() || !self.peek(1).is_number() {
+                        return self.runtime_error(&frame, &"Operands must be numbers.");
+                    }
+                    let (b, a) = (self.pop(), self.pop());
+                    let expected = if instruction == OpCode::Greater { Ordering::Greater } else { Ordering::Less };
+                    self.push(Value::boolean(compare(&a, &b) == Some(expected)));
+                },
+                OpCode::Call => {
+                    let argc = frame.read_byte() as usize;
+                    let callee = self.peek(argc).clone();
+                    let Some(native) = callee.as_native() else {
+                        return self.runtime_error(&frame, &"Can only call functions and classes.");
+                    };
+
+                    let args = self.stack.len() - argc;
+                    let mut context = Context::new(&mut *self.output);
+                    let result = native.call(self.capabilities, &mut context, &self.stack[args..]);
+                    let allocated = context.allocated();
+                    if let Some(code) = context.exit_code() {
+                        self.reset_stack();
+                        return Err(InterpretError::Exit(code));
+                    }
+                    match result {
+                        Ok(value) => {
+                            if !self.allocate(allocated) {
+                                return self.fail(&frame, &"Out of memory.", InterpretError::HeapLimit);
+                            }
+                            self.stack.truncate(args - 1);
+                            self.push(value);
+                        }
+                        Err(msg) => return self.runtime_error(&frame, &msg),
+                    }
+                }
+                OpCode::BuildList => {
+                    let count = frame.read_byte() as usize;
+                    if !self.allocate(Obj::list_size(count)) {
+                        return self.fail(&frame, &"Out of memory.", InterpretError::HeapLimit);
+                    }
+                    let items = self.stack.split_off(self.stack.len() - count);
+                    self.push(Value::list(items));
+                }
+                OpCode::GetIndex => {
+                    let index = self.pop();
+                    let object = self.pop();
+                    match object.get_index(&index) {
+                        Ok(value) => self.push(value),
+                        Err(msg) => return self.runtime_error(&frame, &msg),
+                    }
+                }
+                OpCode::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let object = self.pop();
+                    match object.set_index(&index, value.clone()) {
+                        Ok(added) => {
+                            if added && !self.allocate(Obj::map_entry_size()) {
+                                return self.fail(&frame, &"Out of memory.", InterpretError::HeapLimit);
+                            }
+                        }
+                        Err(msg) => return self.runtime_error(&frame, &msg),
+                    }
+                    self.push(value);
+                }
+                OpCode::ToString => {
+                    if !self.peek(0).is_string() {
+                        let text = self.pop().to_string();
+                        if !self.allocate(Obj::str_size(text.len())) {
+                            return self.fail(&frame, &"Out of memory.", InterpretError::HeapLimit);
+                        }
+                        self.push(Value::string(text));
+                    }
+                }
+                OpCode::Map => {
+                    if !self.allocate(Obj::map_size(0)) {
+                        return self.fail(&frame, &"Out of memory.", InterpretError::HeapLimit);
+                    }
+                    self.push(Value::map(Map::new()));
+                }
+                OpCode::MapEntry => {
+                    let value = self.pop();
+                    let key = self.pop();
+                    // only the compiler emits this, right after OP_MAP, but
+                    // loaded bytecode may not be so well behaved
+                    let inserted = match self.peek(0).as_map() {
+                        Some(map) => map.borrow_mut().insert(&key, value),
+                        None => return self.runtime_error(&frame, &"Expected a map."),
+                    };
+                    match inserted {
+                        Ok(added) => {
+                            if added && !self.allocate(Obj::map_entry_size()) {
+                                return self.fail(&frame, &"Out of memory.", InterpretError::HeapLimit);
+                            }
+                        }
+                        Err(msg) => return self.runtime_error(&frame, &msg),
+                    }
+                }
                 OpCode::Print   => {
                     let value = self.pop();
-                    println!("{}\n", value);
+                    let _ = writeln!(self.output, "{}\n", value);
                 }
             }
+
+            // checked after the instruction rather than before the next, as
+            // some push more than one value
+            if self.stack.len() > self.limits.stack {
+                return self.fail(&frame, &"Value stack overflow.", InterpretError::StackLimit);
+            }
         }
     }
 
-    fn read_byte(&mut self, chunk: &Chunk) -> OpCode {
-        let op: OpCode = chunk.read_code(self.ip).into();
-        self.ip += 1;
-        op
+    /// Drops an interrupt requested before this run, which was meant for an
+    /// earlier one.
+    fn clear_interrupt(&self) {
+        self.interrupt.0.store(false, atomic::Ordering::Relaxed);
+    }
+
+    /// Whether the run should stop.
+    fn interrupted(&self) -> bool {
+        self.interrupt.0.load(atomic::Ordering::Relaxed)
+            || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
     }
 
-    fn read_constant<'a>(&'a mut self, chunk: &'a Chunk) -> &Value {
-        let val: &Value = chunk.read_constant(chunk.read_code(self.ip) as usize);
-        self.ip += 1;
-        val
+    /// Counts `bytes` against `Limits::heap`, saying whether they fit.
+    fn allocate(&mut self, bytes: usize) -> bool {
+        self.heap_bytes = self.heap_bytes.saturating_add(bytes);
+        self.limits.heap.is_none_or(|max| self.heap_bytes <= max)
     }
 
-    fn binary_op(&mut self, op: BinaryOp) {
-        let (b, a) = (self.pop(), self.pop());
-        match op {
-            BinaryOp::Add => self.push(a + b),
-            BinaryOp::Sub => self.push(a - b),
-            BinaryOp::Mul => self.push(a * b),
-            BinaryOp::Div => self.push(a / b),
-            BinaryOp::Less => self.push(Value::Bool(a < b)),
-            BinaryOp::Greater => self.push(Value::Bool(a > b)),
+    /// Hands control to the attached debugger if it wants to stop before the
+    /// next instruction in `frame`. Returns false if it ended the run.
+    fn debug_break(&mut self, frame: &CallFrame) -> bool {
+        let Some(debugger) = self.debugger.as_mut() else { return true };
+
+        let offset = frame.offset();
+        let line = frame.chunk.get_line(offset);
+        // the top-level script is the only frame for now
+        let depth = 1;
+
+        let Some(reason) = debugger.should_pause(line, depth) else { return true };
+
+        let locals = frame.chunk
+            .locals_at(offset)
+            .filter_map(|l| Some((l.name.clone(), self.stack.get(l.slot as usize)?.clone())))
+            .collect();
+        let frames = vec![FrameInfo { name: "script".to_string(), line, offset }];
+
+        let mut ctx = PauseContext {
+            reason,
+            chunk: frame.chunk,
+            line,
+            frames,
+            locals,
+            globals: &self.globals,
+            breakpoints: &mut debugger.breakpoints,
+        };
+        let how = debugger.hook.paused(&mut ctx);
+        debugger.resume(how, depth);
+        how != Resume::Terminate
+    }
+
+    /// Replaces the `operands` on top of the stack with the result of an
+    /// arithmetic op. A result that grew into a big int is charged to the
+    /// heap like any other object.
+    fn push_number(
+        &mut self,
+        frame: &CallFrame,
+        operands: usize,
+        result: Result<Value, String>,
+    ) -> Result<(), InterpretError> {
+        let value = match result {
+            Ok(value) => value,
+            Err(msg) => return self.runtime_error(frame, &msg),
+        };
+        if let Some(n) = value.as_bigint() {
+            if !self.allocate(Obj::bigint_size(n.limbs())) {
+                return self.fail(frame, &"Out of memory.", InterpretError::HeapLimit);
+            }
         }
-        // might need a while loop here, 15.2
-        // if let (Value::Number(b), Value::Number(a)) = (self.pop(), self.pop()) {
-        //     match op {
-        //         BinaryOp::Add => self.push(Value::Number(a + b)),
-        //         BinaryOp::Sub => self.push(Value::Number(a - b)),
-        //         BinaryOp::Mul => self.push(Value::Number(a * b)),
-        //         BinaryOp::Div => self.push(Value::Number(a / b)),
-        //     };
-        // }
+        self.stack.truncate(self.stack.len() - operands);
+        self.push(value);
+        Ok(())
     }
 }
@@ -0,0 +1,87 @@
+mod common;
+
+use rlox::vm::InterpretError;
+
+use common::{compile_error, run};
+
+#[test]
+fn compound_assignment() {
+    let (result, output, _) = run(
+        "var g = 10;\n\
+         write([g += 5, g -= 3, g *= 2, g /= 8, g %= 2, g]);\n\
+         { var s = \"a\"; s += \"b\"; var n = 7; n %= 4; write([s, n]); }",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "[15, 12, 24, 3.0, 1.0, 1.0][ab, 3]");
+}
+
+#[test]
+fn increment_and_decrement() {
+    let (result, output, _) = run(
+        "var i = 5;\n\
+         write([i++, i, ++i, i--, --i, i]);\n\
+         { var j = 1; write([j++ + j, -j--, j]); }",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "[5, 6, 7, 7, 5, 5][3, -2, 1]");
+}
+
+#[test]
+fn subscript_targets_are_evaluated_once() {
+    let (result, output, _) = run(
+        "var xs = [1, 2, 3];\n\
+         var i = 0;\n\
+         xs[i++] += 10;\n\
+         write([xs[i]++, ++xs[2], xs, i]);\n\
+         var m = {\"k\": 2};\n\
+         m[\"k\"] *= 21;\n\
+         write(m);",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "[2, 4, [11, 3, 4], 1]{k: 42}");
+}
+
+#[test]
+fn runtime_errors() {
+    for (source, message) in [
+        ("nope += 1;", "Undefined variable nope"),
+        ("var a = \"s\";\na -= 1;", "Operands must be numbers."),
+        ("var a = nil;\na++;", "Operands must be two numbers or two strings."),
+        ("var xs = [1];\nxs[1]++;", "List index 1 is out of bounds for length 1."),
+        ("var m = {};\nm[\"k\"] += 1;", "Undefined key \"k\"."),
+    ] {
+        let (result, _, errors) = run(source);
+        assert_eq!(result, Err(InterpretError::Runtime), "{source}");
+        assert!(errors.starts_with(message), "{source}: {errors}");
+    }
+}
+
+#[test]
+fn invalid_targets() {
+    for source in [
+        "1 = 2;",
+        "var a; var b; a + b = 1;",
+        "var a; -a = 1;",
+        "var a = 1; a + 1 += 2;",
+        "var a = 1; -a *= 2;",
+        "1++;",
+        "--nil;",
+        "var a = 1; (a)++;",
+        "var a = 1; a++ ++;",
+        "var a = 1; ++a--;",
+    ] {
+        assert_eq!(compile_error(source), "Invalid assignment target.", "{source}");
+    }
+}
+
+#[test]
+fn errors_from_both_passes_are_in_source_order() {
+    // the first is found generating code, the second while parsing
+    let (result, _, errors) = run("{ var a = a; }\n1 = 2;");
+    assert!(matches!(result, Err(InterpretError::Compile)));
+    assert_eq!(
+        errors,
+        "[line 1] Error at 'a': Can't read local variable in its own initalizer.\n\
+         [line 2] Error at '=': Invalid assignment target.\n"
+    );
+}
@@ -0,0 +1,33 @@
+// Pins the bytecode for the script corpus, so a change to the parser or code
+// generator that alters what gets emitted shows up as a diff. After an
+// intended change, regenerate the listings from tests/scripts with
+// `rlox disasm <name>.lox > ../bytecode/<name>.disasm`.
+
+mod common;
+
+use std::path::Path;
+
+use rlox::disassembler::disassemble;
+
+use common::compile;
+
+#[test]
+fn corpus_bytecode_is_unchanged() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut paths: Vec<_> = std::fs::read_dir(root.join("tests/scripts"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "lox"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let name = path.file_name().unwrap().to_str().unwrap();
+        let listing = path.file_stem().unwrap().to_str().unwrap().to_string() + ".disasm";
+        let expected = std::fs::read_to_string(root.join("tests/bytecode").join(&listing))
+            .unwrap_or_else(|_| panic!("no tests/bytecode/{listing} for {name}"));
+        let chunk = compile(&std::fs::read_to_string(&path).unwrap());
+        assert_eq!(disassemble(&chunk, name).to_string(), expected, "bytecode for {name} changed");
+    }
+}
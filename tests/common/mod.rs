@@ -0,0 +1,91 @@
+// Helpers shared by the integration tests. Each test crate uses a different
+// subset of them.
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+use rlox::chunk::Chunk;
+use rlox::compiler::Compiler;
+use rlox::vm::{InterpretError, VM};
+
+/// An output that can be read back after the VM has written to it.
+#[derive(Clone, Default)]
+pub struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuf {
+    pub fn bytes(&self) -> Vec<u8> {
+        self.0.borrow().clone()
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+/// A VM writing to buffers, returned along with them.
+pub fn vm() -> (VM, SharedBuf, SharedBuf) {
+    let (output, errors) = (SharedBuf::default(), SharedBuf::default());
+    let mut vm = VM::new();
+    vm.set_output(Box::new(output.clone()));
+    vm.set_error_output(Box::new(errors.clone()));
+    (vm, output, errors)
+}
+
+/// Runs `source`, returning the result with its output and errors.
+pub fn run(source: &str) -> (Result<(), InterpretError>, String, String) {
+    let (mut vm, output, errors) = vm();
+    let result = vm.interpret(source);
+    (result, output.text(), errors.text())
+}
+
+/// The first error `source` fails to compile with.
+pub fn compile_error(source: &str) -> String {
+    let mut chunk = Chunk::new();
+    let mut compiler = Compiler::new(source, &mut chunk);
+    compiler.set_quiet(true);
+    assert!(compiler.compile().is_err(), "{source}");
+    compiler.diagnostics()[0].message.clone()
+}
+
+/// The bytecode for `source`, which must compile.
+pub fn compile(source: &str) -> Chunk {
+    let mut chunk = Chunk::new();
+    let mut compiler = Compiler::new(source, &mut chunk);
+    compiler.set_quiet(true);
+    assert_eq!(compiler.compile(), Ok(()), "{source}");
+    drop(compiler);
+    chunk
+}
+
+/// A small xorshift generator, so runs are reproducible without a dependency.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    pub fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use rlox::chunk::{Chunk, OpCode};
+use rlox::verifier::{verify, VerifyErrorKind};
+use rlox::vm::InterpretError;
+
+use common::{compile_error, run};
+
+#[test]
+fn conditional_expressions() {
+    let (result, output, _) = run(
+        "write([true ? 1 : 2, nil ? 1 : 2, 0 ? \"zero\" : \"none\", \"\" ? 1 : 2]);\n\
+         var n = 5;\n\
+         write(n < 0 ? \"neg\" : n == 0 ? \"zero\" : n < 10 ? \"small\" : \"big\");\n\
+         write(1 > 2 ? 3 : 4 + 10);",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "[1, 2, zero, 1]small14");
+}
+
+#[test]
+fn null_coalescing() {
+    let (result, output, _) = run(
+        "var config = {\"port\": 8080, \"debug\": false};\n\
+         var missing;\n\
+         write([missing ?? \"default\", config[\"debug\"] ?? true, 0 ?? 1, nil ?? nil ?? 3]);\n\
+         write(missing ?? 1 > 0 ? \"on\" : \"off\");",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "[default, false, 0, 3]on");
+}
+
+#[test]
+fn only_the_chosen_branch_runs() {
+    let (result, output, _) = run(
+        "var n = 0;\n\
+         write([true ? n++ : nope, false ? nope() : n++, 1 ?? nope, nil ?? n++, n]);",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "[0, 1, 1, 2, 3]");
+
+    let (result, _, errors) = run("write(false ? 1 : nope);");
+    assert_eq!(result, Err(InterpretError::Runtime));
+    assert!(errors.starts_with("Undefined variable nope"), "{errors}");
+}
+
+#[test]
+fn syntax_errors() {
+    for (source, message) in [
+        ("print true ? 1;", "Expect ':' after then branch of conditional expression."),
+        ("print ? 1 : 2;", "Expect expression."),
+        ("var a; a ? 1 : a = 2;", "Invalid assignment target."),
+        ("var a; a ?? a = 2;", "Invalid assignment target."),
+    ] {
+        assert_eq!(compile_error(source), message, "{source}");
+    }
+}
+
+#[test]
+fn jumps_must_land_on_an_instruction() {
+    use OpCode::*;
+    let mut chunk = Chunk::new();
+    // 0: nil, 1: jump if false, 4: get local 0, 6: pop, 7: pop, 8: return
+    for byte in [Nil.into(), JumpIfFalse.into(), 0xff, 0xff, GetLocal.into(), 0, Pop.into(), Pop.into(), Return.into()] {
+        chunk.write(byte, 1);
+    }
+    assert_eq!(verify(&chunk).unwrap_err().kind, VerifyErrorKind::BadJumpTarget(65535 + 4));
+
+    // into the operand of OP_GET_LOCAL
+    chunk.patch(2, 0);
+    chunk.patch(3, 1);
+    assert_eq!(verify(&chunk).unwrap_err().kind, VerifyErrorKind::BadJumpTarget(5));
+
+    // to the second OP_POP, where the stack is as high as on the other path
+    chunk.patch(3, 3);
+    assert_eq!(verify(&chunk), Ok(()));
+}
@@ -0,0 +1,197 @@
+// Drives `rlox::dap::serve` with a scripted client: every request is queued up
+// front and the server reads them as it needs them.
+
+mod common;
+
+use std::io::{Cursor, Write};
+
+use rlox::json::Json;
+
+use common::SharedBuf;
+
+fn frame(requests: &[&str]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (seq, body) in requests.iter().enumerate() {
+        let body = body.replace("$SEQ", &(seq + 1).to_string());
+        write!(out, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+    }
+    out
+}
+
+fn unframe(mut bytes: &[u8]) -> Vec<Json> {
+    let mut messages = Vec::new();
+    while !bytes.is_empty() {
+        let text = std::str::from_utf8(bytes).unwrap();
+        let header_end = text.find("\r\n\r\n").unwrap();
+        let length: usize = text[..header_end]
+            .trim_start_matches("Content-Length:")
+            .trim()
+            .parse()
+            .unwrap();
+        let body = &bytes[header_end + 4..header_end + 4 + length];
+        messages.push(Json::parse(std::str::from_utf8(body).unwrap()).unwrap());
+        bytes = &bytes[header_end + 4 + length..];
+    }
+    messages
+}
+
+fn find<'a>(messages: &'a [Json], kind: &str, name: &str) -> Vec<&'a Json> {
+    let key = if kind == "event" { "event" } else { "command" };
+    messages
+        .iter()
+        .filter(|m| m.get("type").as_str() == Some(kind) && m.get(key).as_str() == Some(name))
+        .collect()
+}
+
+#[test]
+fn breakpoint_inspect_and_step() {
+    let path = std::env::temp_dir().join(format!("rlox-dap-{}.lox", std::process::id()));
+    std::fs::write(&path, "var a = 1;\nvar b = 2;\n\n{\n  var c = a + b;\n  print c;\n}\nprint a;\n").unwrap();
+    let path = path.to_str().unwrap().to_string();
+
+    let launch = format!(r#"{{"seq":$SEQ,"type":"request","command":"launch","arguments":{{"program":"{path}"}}}}"#);
+    let set_breakpoints = format!(r#"{{"seq":$SEQ,"type":"request","command":"setBreakpoints","arguments":{{"source":{{"path":"{path}"}},"breakpoints":[{{"line":3}}]}}}}"#);
+    let input = frame(&[
+        r#"{"seq":$SEQ,"type":"request","command":"initialize","arguments":{}}"#,
+        &launch,
+        &set_breakpoints,
+        r#"{"seq":$SEQ,"type":"request","command":"configurationDone"}"#,
+        // stopped on line 5
+        r#"{"seq":$SEQ,"type":"request","command":"stackTrace","arguments":{"threadId":1}}"#,
+        r#"{"seq":$SEQ,"type":"request","command":"scopes","arguments":{"frameId":0}}"#,
+        r#"{"seq":$SEQ,"type":"request","command":"variables","arguments":{"variablesReference":2}}"#,
+        r#"{"seq":$SEQ,"type":"request","command":"next","arguments":{"threadId":1}}"#,
+        // stopped on line 6, after c is initialized
+        r#"{"seq":$SEQ,"type":"request","command":"variables","arguments":{"variablesReference":1}}"#,
+        r#"{"seq":$SEQ,"type":"request","command":"continue","arguments":{"threadId":1}}"#,
+        r#"{"seq":$SEQ,"type":"request","command":"disconnect"}"#,
+    ]);
+
+    let output = SharedBuf::default();
+    rlox::dap::serve(Cursor::new(input), output.clone()).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let messages = unframe(&output.bytes());
+
+    // line 3 is blank, so the breakpoint moves to the next line with code
+    let breakpoints = find(&messages, "response", "setBreakpoints")[0].get("body").get("breakpoints");
+    assert_eq!(breakpoints.as_array().unwrap()[0].get("line").as_usize(), Some(5));
+    assert_eq!(breakpoints.as_array().unwrap()[0].get("verified").as_bool(), Some(true));
+
+    let stopped = find(&messages, "event", "stopped");
+    assert_eq!(stopped.len(), 2);
+    assert_eq!(stopped[0].get("body").get("reason").as_str(), Some("breakpoint"));
+    assert_eq!(stopped[1].get("body").get("reason").as_str(), Some("step"));
+
+    let frames = find(&messages, "response", "stackTrace")[0].get("body").get("stackFrames");
+    assert_eq!(frames.as_array().unwrap()[0].get("line").as_usize(), Some(5));
+
+    let variables = find(&messages, "response", "variables");
+    assert_eq!(variables[0].get("body").get("variables").to_string(),
+               r#"[{"name":"a","value":"1","variablesReference":0},{"name":"b","value":"2","variablesReference":0}]"#);
+    assert_eq!(variables[1].get("body").get("variables").to_string(),
+               r#"[{"name":"c","value":"3","variablesReference":0}]"#);
+
+    let output: String = find(&messages, "event", "output")
+        .iter()
+        .map(|m| m.get("body").get("output").as_str().unwrap())
+        .collect();
+    assert_eq!(output, "3\n\n1\n\n");
+
+    assert_eq!(find(&messages, "event", "exited")[0].get("body").get("exitCode").as_usize(), Some(0));
+    assert_eq!(find(&messages, "event", "terminated").len(), 1);
+    assert_eq!(find(&messages, "response", "disconnect").len(), 1);
+}
+
+#[test]
+fn exit_ends_the_session_not_the_server() {
+    let path = std::env::temp_dir().join(format!("rlox-dap-exit-{}.lox", std::process::id()));
+    std::fs::write(&path, "print 1;\nexit(4);\nprint 2;\n").unwrap();
+    let path = path.to_str().unwrap().to_string();
+
+    let launch = format!(r#"{{"seq":$SEQ,"type":"request","command":"launch","arguments":{{"program":"{path}"}}}}"#);
+    let input = frame(&[
+        r#"{"seq":$SEQ,"type":"request","command":"initialize","arguments":{}}"#,
+        &launch,
+        r#"{"seq":$SEQ,"type":"request","command":"configurationDone"}"#,
+        r#"{"seq":$SEQ,"type":"request","command":"disconnect"}"#,
+    ]);
+
+    let output = SharedBuf::default();
+    rlox::dap::serve(Cursor::new(input), output.clone()).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let messages = unframe(&output.bytes());
+    let printed: String = find(&messages, "event", "output")
+        .iter()
+        .map(|m| m.get("body").get("output").as_str().unwrap())
+        .collect();
+    assert_eq!(printed, "1\n\n");
+    assert_eq!(find(&messages, "event", "exited")[0].get("body").get("exitCode").as_usize(), Some(4));
+    assert_eq!(find(&messages, "response", "disconnect").len(), 1);
+}
+
+#[test]
+fn disconnect_at_a_breakpoint_ends_the_run() {
+    let path = std::env::temp_dir().join(format!("rlox-dap-disconnect-{}.lox", std::process::id()));
+    std::fs::write(&path, "print 1;\nprint 2;\n").unwrap();
+    let path = path.to_str().unwrap().to_string();
+
+    let launch = format!(r#"{{"seq":$SEQ,"type":"request","command":"launch","arguments":{{"program":"{path}"}}}}"#);
+    let set_breakpoints = format!(r#"{{"seq":$SEQ,"type":"request","command":"setBreakpoints","arguments":{{"source":{{"path":"{path}"}},"breakpoints":[{{"line":2}}]}}}}"#);
+    let input = frame(&[
+        r#"{"seq":$SEQ,"type":"request","command":"initialize","arguments":{}}"#,
+        &launch,
+        &set_breakpoints,
+        r#"{"seq":$SEQ,"type":"request","command":"configurationDone"}"#,
+        r#"{"seq":$SEQ,"type":"request","command":"disconnect"}"#,
+        // never read: the server is done once the session ends
+        r#"{"seq":$SEQ,"type":"request","command":"threads"}"#,
+    ]);
+
+    let output = SharedBuf::default();
+    rlox::dap::serve(Cursor::new(input), output.clone()).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let messages = unframe(&output.bytes());
+    assert_eq!(find(&messages, "event", "stopped")[0].get("body").get("reason").as_str(), Some("breakpoint"));
+    let printed: String = find(&messages, "event", "output")
+        .iter()
+        .map(|m| m.get("body").get("output").as_str().unwrap())
+        .collect();
+    assert_eq!(printed, "1\n\n");
+    assert_eq!(find(&messages, "response", "disconnect").len(), 1);
+    assert_eq!(find(&messages, "event", "exited")[0].get("body").get("exitCode").as_usize(), Some(0));
+    assert_eq!(find(&messages, "event", "terminated").len(), 1);
+    assert!(find(&messages, "response", "threads").is_empty());
+}
+
+#[test]
+fn compile_errors_go_to_the_client() {
+    let path = std::env::temp_dir().join(format!("rlox-dap-compile-{}.lox", std::process::id()));
+    std::fs::write(&path, "print 1;\nvar = 2;\nprint;\n").unwrap();
+    let path = path.to_str().unwrap().to_string();
+
+    let launch = format!(r#"{{"seq":$SEQ,"type":"request","command":"launch","arguments":{{"program":"{path}"}}}}"#);
+    let input = frame(&[
+        r#"{"seq":$SEQ,"type":"request","command":"initialize","arguments":{}}"#,
+        &launch,
+        r#"{"seq":$SEQ,"type":"request","command":"disconnect"}"#,
+    ]);
+
+    let output = SharedBuf::default();
+    rlox::dap::serve(Cursor::new(input), output.clone()).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let messages = unframe(&output.bytes());
+    let response = find(&messages, "response", "launch")[0];
+    assert_eq!(response.get("success").as_bool(), Some(false));
+    assert_eq!(
+        response.get("message").as_str().unwrap(),
+        format!(
+            "{path} failed to compile\n\
+             [line 2] Error at '=': Expect variable name.\n\
+             [line 3] Error at ';': Expect expression."
+        )
+    );
+}
@@ -0,0 +1,177 @@
+// Drives the VM's debugger with a scripted hook that records every pause and
+// answers from a fixed list of resumes.
+
+mod common;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+
+use rlox::debugger::{DebugHook, Debugger, PauseContext, Resume};
+use rlox::vm::InterpretError;
+
+use common::{compile, vm};
+
+const SOURCE: &str = "var a = 1;\n\
+                      {\n  \
+                        var b = a + 1;\n\
+                      \n  \
+                        print b;\n  \
+                        {\n    \
+                          var c = b * 2;\n    \
+                          print c;\n  \
+                        }\n\
+                      }\n\
+                      print a;\n";
+
+struct Script {
+    resumes: VecDeque<Resume>,
+    /// Lines to break on, set from the first pause as a front end would.
+    break_at: Vec<usize>,
+    log: Rc<RefCell<Vec<String>>>,
+}
+
+impl DebugHook for Script {
+    fn paused(&mut self, ctx: &mut PauseContext) -> Resume {
+        for line in self.break_at.drain(..) {
+            ctx.breakpoints.insert(ctx.chunk.breakable_line(line).unwrap());
+        }
+        let locals: Vec<String> = ctx.locals.iter().map(|(name, value)| format!("{name}={value}")).collect();
+        assert_eq!(ctx.frames.len(), 1);
+        assert_eq!(ctx.frames[0].line, ctx.line);
+        self.log.borrow_mut().push(format!("{:?} {} [{}]", ctx.reason, ctx.line, locals.join(" ")));
+        self.resumes.pop_front().unwrap_or(Resume::Continue)
+    }
+}
+
+/// Runs `SOURCE` under a debugger, returning each pause and the output.
+fn debug(stop_on_entry: bool, breakpoints: &[usize], resumes: &[Resume], break_at: &[usize]) -> (Vec<String>, String) {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let script = Script {
+        resumes: resumes.iter().copied().collect(),
+        break_at: break_at.to_vec(),
+        log: log.clone(),
+    };
+    let mut debugger = Debugger::new(Box::new(script), stop_on_entry);
+    debugger.breakpoints.extend(breakpoints);
+
+    let (mut vm, output, _) = vm();
+    vm.set_debugger(Some(debugger));
+    assert_eq!(vm.interpret(SOURCE), Ok(()));
+    let log = log.borrow().clone();
+    (log, output.text())
+}
+
+#[test]
+fn breakable_lines() {
+    let chunk = compile(SOURCE);
+    assert_eq!(chunk.breakable_line(1), Some(1));
+    // `{` and blank lines have no code of their own
+    assert_eq!(chunk.breakable_line(2), Some(3));
+    assert_eq!(chunk.breakable_line(4), Some(5));
+    assert_eq!(chunk.breakable_line(6), Some(7));
+    // the implicit return
+    assert_eq!(chunk.breakable_line(12), Some(12));
+    assert_eq!(chunk.breakable_line(13), None);
+}
+
+#[test]
+fn locals_come_into_scope_once_initialized() {
+    let chunk = compile(SOURCE);
+    let names = |offset| chunk.locals_at(offset).map(|l| (l.name.as_str(), l.slot)).collect::<Vec<_>>();
+    // `b`'s initializer runs before `b` exists
+    assert_eq!(names(8), []);
+    assert_eq!(names(9), [("b", 0)]);
+    assert_eq!(names(16), [("b", 0)]);
+    assert_eq!(names(17), [("b", 0), ("c", 1)]);
+    assert_eq!(names(22), []);
+}
+
+#[test]
+fn stops_only_at_breakpoints() {
+    let (log, output) = debug(false, &[5, 8], &[], &[]);
+    assert_eq!(log, ["Breakpoint 5 [b=2]", "Breakpoint 8 [b=2 c=4]"]);
+    assert_eq!(output, "2\n\n4\n\n1\n\n");
+}
+
+#[test]
+fn step_in_stops_once_per_line() {
+    // a block's locals are gone by the time its `}` pops them
+    let (log, _) = debug(true, &[], &[Resume::StepIn; 9], &[]);
+    assert_eq!(
+        log,
+        [
+            "Entry 1 []",
+            "Step 3 []",
+            "Step 5 [b=2]",
+            "Step 7 [b=2]",
+            "Step 8 [b=2 c=4]",
+            "Step 9 [b=2]",
+            "Step 10 []",
+            "Step 11 []",
+            "Step 12 []",
+        ]
+    );
+}
+
+#[test]
+fn step_over_and_out() {
+    // the script is the only frame, so stepping over a line steps to the
+    // next one and stepping out runs to the end, stopping at breakpoints
+    let (log, output) = debug(true, &[], &[Resume::StepOver, Resume::StepOver, Resume::StepOut], &[6]);
+    assert_eq!(log, ["Entry 1 []", "Step 3 []", "Step 5 [b=2]", "Breakpoint 7 [b=2]"]);
+    assert_eq!(output, "2\n\n4\n\n1\n\n");
+}
+
+#[test]
+fn removing_the_debugger_stops_pausing() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let script = Script { resumes: VecDeque::new(), break_at: Vec::new(), log: log.clone() };
+    let (mut vm, _, _) = vm();
+    vm.set_debugger(Some(Debugger::new(Box::new(script), true)));
+    vm.set_debugger(None);
+    assert_eq!(vm.interpret(SOURCE), Ok(()));
+    assert!(log.borrow().is_empty());
+}
+
+#[test]
+fn terminate_ends_the_run() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let script = Script {
+        resumes: VecDeque::from([Resume::StepIn, Resume::Terminate]),
+        break_at: Vec::new(),
+        log: log.clone(),
+    };
+    let (mut vm, output, errors) = vm();
+    vm.set_debugger(Some(Debugger::new(Box::new(script), true)));
+    assert_eq!(vm.interpret(SOURCE), Err(InterpretError::Interrupted));
+    assert_eq!(*log.borrow(), ["Entry 1 []", "Step 3 []"]);
+    assert_eq!(output.text(), "");
+    assert_eq!(errors.text(), "");
+
+    // the VM is still usable afterwards
+    vm.set_debugger(None);
+    assert_eq!(vm.interpret("print 1;"), Ok(()));
+}
+
+#[test]
+fn quit_stops_the_command_line_debugger() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["debug", "tests/scripts/arithmetic.lox"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"step\nquit\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // line 2 ran, line 3 didn't
+    assert!(stdout.starts_with("Stopped at entry."), "{stdout}");
+    assert!(stdout.contains("(rlox) 7\n"), "{stdout}");
+    assert!(!stdout.contains("9\n"), "{stdout}");
+    assert!(!stdout.contains("Program finished."), "{stdout}");
+}
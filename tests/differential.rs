@@ -0,0 +1,287 @@
+// Runs the script corpus and a stream of generated programs through both the
+// bytecode VM and the reference tree-walker, and fails on any difference in
+// output, errors or result.
+
+mod common;
+
+use std::path::Path;
+
+use rlox::interpreter::Interpreter;
+use rlox::vm::InterpretError;
+
+use common::{run, Rng, SharedBuf};
+
+#[derive(Debug, PartialEq)]
+struct Run {
+    result: Result<(), InterpretError>,
+    output: String,
+    errors: String,
+}
+
+fn run_vm(source: &str) -> Run {
+    let (result, output, errors) = run(source);
+    Run { result, output, errors }
+}
+
+fn run_tree_walker(source: &str) -> Run {
+    let (output, errors) = (SharedBuf::default(), SharedBuf::default());
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(output.clone()));
+    interpreter.set_error_output(Box::new(errors.clone()));
+    let result = interpreter.interpret(source);
+    Run { result, output: output.text(), errors: errors.text() }
+}
+
+fn assert_agree(name: &str, source: &str) -> Run {
+    let vm = run_vm(source);
+    let tree_walker = run_tree_walker(source);
+    assert_eq!(vm, tree_walker, "{name} diverges:\n{source}");
+    vm
+}
+
+#[test]
+fn corpus() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scripts");
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "lox"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let source = std::fs::read_to_string(&path).unwrap();
+        let run = assert_agree(&path.display().to_string(), &source);
+        assert_eq!(run.result, Ok(()), "{}: {}", path.display(), run.errors);
+    }
+}
+
+#[test]
+fn errors() {
+    for source in [
+        "print 1 +;",
+        "{ var a = a; }",
+        "print undefined;",
+        "missing = 1;",
+        "var a = 1; print a + \"x\";",
+        "print -\"x\";",
+        "print 1 <\n nil;",
+        "var a = \"s\"; { var b = a * 2; }",
+        "write(1, 2);",
+        "var a = 1;\na(\n);",
+        "write(\"x\" + 1);",
+        "clock()();",
+        "getEnv(\"HOME\");",
+        "var xs = [1, 2];\nprint xs[2];",
+        "var xs = [1, 2];\nxs[0.5] = 1;",
+        "print pop([]);",
+        "print slice([1], 0, 2);",
+        "print nil[0];",
+        "var m = {\"a\": 1};\nprint m[\"b\"];",
+        "print {[]: 1};",
+        "var m = {};\nm[0/0] = 1;",
+        "print remove({}, 1);",
+        "print \"${nope}\";",
+        "print \"a ${[1][2]} b\";",
+        "var a = \"s\";\na -= 1;",
+        "var xs = [1];\nxs[1]++;",
+        "nope += 1;",
+        "print false ? 1 : nope;",
+        "print nil ?? -\"x\";",
+        "var m = {};\nm[nope()] *= 2;",
+    ] {
+        let run = assert_agree("error case", source);
+        assert!(run.result.is_err());
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Number,
+    Literal,
+    Variable,
+    Unary,
+    Grouping,
+    Assign,
+    List,
+    Index,
+    Map,
+    Interpolation,
+    IndexAssign,
+    Conditional,
+    Coalesce,
+    Binary,
+}
+
+/// What `Generator::expression` picks from once it can't nest any deeper.
+const LEAVES: &[(Kind, usize)] = &[(Kind::Number, 1), (Kind::Literal, 1), (Kind::Variable, 2)];
+
+/// What it picks from otherwise, with their relative weights.
+const EXPRESSIONS: &[(Kind, usize)] = &[
+    (Kind::Number, 1),
+    (Kind::Literal, 1),
+    (Kind::Variable, 2),
+    (Kind::Unary, 1),
+    (Kind::Grouping, 1),
+    (Kind::Assign, 1),
+    (Kind::List, 1),
+    (Kind::Index, 1),
+    (Kind::Map, 1),
+    (Kind::Interpolation, 1),
+    (Kind::IndexAssign, 1),
+    (Kind::Conditional, 1),
+    (Kind::Coalesce, 1),
+    (Kind::Binary, 2),
+];
+
+struct Generator {
+    rng: Rng,
+    // variables in scope: globals first, then one list per open block
+    scopes: Vec<Vec<String>>,
+    next_name: usize,
+    source: String,
+}
+
+impl Generator {
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng(seed.wrapping_mul(0x9e3779b97f4a7c15) | 1),
+            scopes: vec![Vec::new()],
+            next_name: 0,
+            source: String::new(),
+        }
+    }
+
+    fn variable(&mut self) -> Option<String> {
+        let names: Vec<&String> = self.scopes.iter().flatten().collect();
+        if names.is_empty() {
+            None
+        } else {
+            Some(names[self.rng.below(names.len())].clone())
+        }
+    }
+
+    /// Picks a kind of expression, each as likely as its weight.
+    fn kind(&mut self, kinds: &[(Kind, usize)]) -> Kind {
+        let mut n = self.rng.below(kinds.iter().map(|(_, weight)| weight).sum());
+        for &(kind, weight) in kinds {
+            if n < weight {
+                return kind;
+            }
+            n -= weight;
+        }
+        unreachable!()
+    }
+
+    fn expression(&mut self, depth: usize) -> String {
+        let kind = if depth == 0 { self.kind(LEAVES) } else { self.kind(EXPRESSIONS) };
+        match kind {
+            Kind::Number => self.rng.pick(&["0", "1", "2", "7", "0.5", "10", "3.25", "0x1F", "0b101", "1e3", "2_500", "2.0", "9223372036854775807", "0x1_0000_0000_0000_0000"]).to_string(),
+            Kind::Literal => self.rng
+                .pick(&["\"a\"", "\"bc\"", "\"\"", "\"\\t\\\"\\u{e9}\"", "r\"\\n\"", "true", "false", "nil"])
+                .to_string(),
+            // now and then an undefined name, for runtime errors
+            Kind::Variable => match self.variable() {
+                Some(name) if self.rng.below(20) > 0 => name,
+                _ => "nope".to_string(),
+            },
+            Kind::Unary => {
+                let op = self.rng.pick(&["-", "!", "~"]);
+                let operand = self.expression(depth - 1);
+                // `- -x` mustn't run together into `--x`
+                let space = if operand.starts_with('-') { " " } else { "" };
+                format!("{op}{space}{operand}")
+            }
+            Kind::Grouping => format!("({})", self.expression(depth - 1)),
+            Kind::Assign => match self.variable() {
+                Some(name) => match self.rng.below(4) {
+                    0 => format!("{name}{}", self.rng.pick(&["++", "--"])),
+                    1 => format!("{}{name}", self.rng.pick(&["++", "--"])),
+                    _ => {
+                        let op = self.rng.pick(&["=", "=", "+=", "-=", "*=", "/=", "%="]);
+                        format!("{name} {op} {}", self.expression(depth - 1))
+                    }
+                },
+                None => self.expression(depth - 1),
+            },
+            Kind::List => {
+                let items: Vec<String> = (0..self.rng.below(4)).map(|_| self.expression(depth - 1)).collect();
+                format!("[{}]", items.join(", "))
+            }
+            Kind::Index => format!("({})[{}]", self.expression(depth - 1), self.expression(depth - 1)),
+            Kind::Map => {
+                let entries: Vec<String> = (0..self.rng.below(4))
+                    .map(|_| format!("{}: {}", self.expression(depth - 1), self.expression(depth - 1)))
+                    .collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+            Kind::Interpolation => format!("\"<${{{}}}>${{{}}}\"", self.expression(depth - 1), self.expression(depth - 1)),
+            Kind::IndexAssign => {
+                let op = self.rng.pick(&["+=", "*=", "-="]);
+                format!("({})[{}] {op} {}", self.expression(depth - 1), self.expression(depth - 1), self.expression(depth - 1))
+            }
+            Kind::Conditional => format!(
+                "{} ? {} : {}",
+                self.expression(depth - 1),
+                self.expression(depth - 1),
+                self.expression(depth - 1)
+            ),
+            Kind::Coalesce => format!("{} ?? {}", self.expression(depth - 1), self.expression(depth - 1)),
+            Kind::Binary => {
+                let op = self.rng.pick(&["+", "+", "-", "*", "/", "~/", "%", "**", "&", "|", "^", "<<", ">>", "==", "!=", "<", "<=", ">", ">="]);
+                format!("{} {op} {}", self.expression(depth - 1), self.expression(depth - 1))
+            }
+        }
+    }
+
+    fn statement(&mut self, depth: usize) {
+        let indent = "  ".repeat(self.scopes.len() - 1);
+        match self.rng.below(if depth == 0 { 3 } else { 5 }) {
+            0 => {
+                // fresh names, since a block can't redeclare an enclosing local
+                let name = format!("v{}", self.next_name);
+                self.next_name += 1;
+                let value = self.expression(3);
+                self.source.push_str(&format!("{indent}var {name} = {value};\n"));
+                self.scopes.last_mut().unwrap().push(name);
+            }
+            1 => {
+                let value = self.expression(3);
+                self.source.push_str(&format!("{indent}print {value};\n"));
+            }
+            2 if self.rng.below(4) == 0 => {
+                let value = self.expression(3);
+                self.source.push_str(&format!("{indent}write({value});\n"));
+            }
+            2 => {
+                let value = self.expression(3);
+                self.source.push_str(&format!("{indent}{value};\n"));
+            }
+            _ => {
+                self.source.push_str(&format!("{indent}{{\n"));
+                self.scopes.push(Vec::new());
+                for _ in 0..self.rng.below(5) {
+                    self.statement(depth - 1);
+                }
+                self.scopes.pop();
+                self.source.push_str(&format!("{indent}}}\n"));
+            }
+        }
+    }
+
+    fn program(mut self) -> String {
+        for _ in 0..12 {
+            self.statement(3);
+        }
+        self.source
+    }
+}
+
+#[test]
+fn generated_programs() {
+    for seed in 0..500 {
+        let source = Generator::new(seed).program();
+        assert_agree(&format!("seed {seed}"), &source);
+    }
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use rlox::chunk::{Chunk, OpCode};
+use rlox::disassembler::disassemble;
+use rlox::json::Json;
+
+use common::compile;
+
+const SOURCE: &str = "var a = nil;\nprint a ?? \"x\";\n{ var b = a ? 1 : 2; print -b; }\n";
+
+/// A file in the temp directory holding `contents`, unique to this test run.
+fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rlox-disasm-{}-{name}", std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn rlox(args: &[&str]) -> (i32, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox")).args(args).output().unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn listing() {
+    assert_eq!(
+        disassemble(&compile(SOURCE), "script").to_string(),
+        "== script ==\n\
+         -- constants --\n   \
+            0 string   'a'\n   \
+            1 string   'x'\n   \
+            2 int      '1'\n   \
+            3 int      '2'\n\
+         -- code --\n\
+         0000    1 OP_NIL\n\
+         0001    | OP_DEFINE_GLOBAL    0 'a'\n\
+         0003    2 OP_GET_GLOBAL       0 'a'\n\
+         0005    | OP_JUMP_IF_NOT_NIL 0011 -> L0\n\
+         0008    | OP_POP\n\
+         0009    | OP_CONSTANT         1 'x'\n\
+         L0:\n\
+         0011    | OP_PRINT\n\
+         0012    3 OP_GET_GLOBAL       0 'a'\n\
+         0014    | OP_JUMP_IF_FALSE 0023 -> L1\n\
+         0017    | OP_POP\n\
+         0018    | OP_CONSTANT         2 '1'\n\
+         0020    | OP_JUMP          0026 -> L2\n\
+         L1:\n\
+         0023    | OP_POP\n\
+         0024    | OP_CONSTANT         3 '2'\n\
+         L2:\n\
+         0026    | OP_GET_LOCAL        0\n\
+         0028    | OP_NEGATE\n\
+         0029    | OP_PRINT\n\
+         0030    | OP_POP\n\
+         0031    4 OP_RETURN\n"
+    );
+}
+
+#[test]
+fn json_listing() {
+    let json = disassemble(&compile(SOURCE), "script").to_json();
+    assert_eq!(json.get("name").as_str(), Some("script"));
+
+    let constants = json.get("constants").as_array().unwrap();
+    assert_eq!(constants.len(), 4);
+    assert_eq!(constants[2].get("type").as_str(), Some("int"));
+    assert_eq!(constants[2].get("value").as_str(), Some("1"));
+
+    let instructions = json.get("instructions").as_array().unwrap();
+    let at = |offset: usize| instructions.iter().find(|i| i.get("offset").as_usize() == Some(offset)).unwrap();
+    assert_eq!(at(1).get("op").as_str(), Some("OP_DEFINE_GLOBAL"));
+    assert_eq!(at(1).get("constant").as_usize(), Some(0));
+    assert_eq!(at(1).get("value").as_str(), Some("a"));
+    assert_eq!(at(14).get("op").as_str(), Some("OP_JUMP_IF_FALSE"));
+    assert_eq!(at(14).get("target").as_usize(), Some(23));
+    assert_eq!(at(14).get("label").as_str(), Some("L1"));
+    assert_eq!(at(26).get("slot").as_usize(), Some(0));
+    assert_eq!(at(31).get("line").as_usize(), Some(4));
+
+    let labels: Vec<_> = json
+        .get("labels")
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|l| (l.get("label").as_str().unwrap().to_string(), l.get("offset").as_usize().unwrap()))
+        .collect();
+    assert_eq!(labels, [("L0".to_string(), 11), ("L1".to_string(), 23), ("L2".to_string(), 26)]);
+}
+
+#[test]
+fn malformed_code_is_listed_not_rejected() {
+    let mut chunk = Chunk::new();
+    for byte in [OpCode::Constant.into(), 3, 250, OpCode::Jump.into(), 0] {
+        chunk.write(byte, 1);
+    }
+    let disassembly = disassemble(&chunk, "bad");
+    assert_eq!(
+        disassembly.to_string(),
+        "== bad ==\n\
+         0000    1 OP_CONSTANT         3 <out of range>\n\
+         0002    | Unknown opcode 250\n\
+         0003    | OP_JUMP          <missing operand>\n"
+    );
+
+    let json = disassembly.to_json();
+    let instructions = json.get("instructions").as_array().unwrap();
+    assert_eq!(instructions[0].get("value"), &Json::Null);
+    assert_eq!(instructions[1].get("op").as_str(), Some("UNKNOWN"));
+    assert_eq!(instructions[1].get("byte").as_usize(), Some(250));
+    assert_eq!(instructions[2].get("truncated").as_bool(), Some(true));
+}
+
+#[test]
+fn command_line() {
+    let source = temp_file("cli.lox", SOURCE.as_bytes());
+    let compiled = source.with_extension("loxc");
+    let (source, compiled) = (source.to_str().unwrap(), compiled.to_str().unwrap());
+
+    let (status, stdout, _) = rlox(&["disasm", "--json", source]);
+    assert_eq!(status, 0);
+    let json = Json::parse(&stdout).unwrap();
+    assert_eq!(json.to_string(), disassemble(&compile(SOURCE), source).to_json().to_string());
+
+    // a compiled file lists the same, apart from its name
+    assert_eq!(rlox(&["compile", source, "-o", compiled]).0, 0);
+    let (status, from_loxc, _) = rlox(&["disasm", compiled]);
+    assert_eq!(status, 0);
+    let (_, from_lox, _) = rlox(&["disasm", source]);
+    assert_eq!(from_loxc.replace(compiled, source), from_lox);
+
+    let bad = temp_file("bad.loxc", b"LOXD\x01");
+    let bad = bad.to_str().unwrap();
+    let (status, stdout, stderr) = rlox(&["disasm", "--json", bad]);
+    assert_eq!((status, stdout.as_str()), (65, ""));
+    assert_eq!(stderr, format!("{bad}: not a .loxc file\n"));
+
+    for path in [source, compiled, bad] {
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,62 @@
+// The formatter must leave the script corpus alone and be idempotent.
+
+use std::path::Path;
+
+use rlox::formatter::{format, FormatErrorKind};
+
+fn corpus() -> Vec<(String, String)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scripts");
+    let mut scripts: Vec<(String, String)> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "lox"))
+        .map(|path| (path.display().to_string(), std::fs::read_to_string(&path).unwrap()))
+        .collect();
+    scripts.sort();
+    assert!(!scripts.is_empty());
+    scripts
+}
+
+#[test]
+fn corpus_is_formatted() {
+    for (path, source) in corpus() {
+        assert_eq!(format(&source).unwrap(), source, "{path} is not formatted");
+    }
+}
+
+#[test]
+fn formatting_is_idempotent() {
+    for (path, source) in corpus() {
+        // squash every script onto as few lines as possible first
+        let squashed: String = source
+            .lines()
+            .map(|line| if line.trim_start().starts_with("//") { format!("\n{line}\n") } else { line.to_string() })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let once = format(&squashed).unwrap();
+        assert_eq!(format(&once).unwrap(), once, "{path} formats differently the second time");
+    }
+}
+
+#[test]
+fn layout() {
+    let messy = "\n\nvar a=1;var b = -a*(2+ -3);   // trailing  \nprint !true ;\n\n\n{\n   var c = a+b ;\n\n}\n\
+                 if (a>b){print a;}else{print b;}\nvar d = 1 + // mid\n2;\nx.y(1,2);\nxs [ 0 ]=[ 1,[2 ] ,-3 ];\n\
+                 var m={ \"a\" :1,\"b\":{ } }[ \"a\" ]-1;\nprint \"<${ m+1 }${-m}>\";\n";
+    let expected = "var a = 1;\nvar b = -a * (2 + -3); // trailing\nprint !true;\n\n{\n  var c = a + b;\n}\n\
+                    if (a > b) {\n  print a;\n} else {\n  print b;\n}\nvar d = 1 + // mid\n  2;\nx.y(1, 2);\nxs[0] = [1, [2], -3];\n\
+                    var m = {\"a\": 1, \"b\": {}}[\"a\"] - 1;\nprint \"<${m + 1}${-m}>\";\n";
+    assert_eq!(format(messy).unwrap(), expected);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(format("print 1;\n}").unwrap_err().kind, FormatErrorKind::Unmatched('}'));
+    assert_eq!(format("{\nprint (1;").unwrap_err().kind, FormatErrorKind::Unclosed('('));
+    assert_eq!(format("{\nprint 1;").unwrap_err().kind, FormatErrorKind::Unclosed('{'));
+    assert_eq!(format("print \"open;").unwrap_err().line, 1);
+    assert_eq!(format("print [1);").unwrap_err().kind, FormatErrorKind::Unmatched(')'));
+    assert_eq!(format("print [1;").unwrap_err().kind, FormatErrorKind::Unclosed('['));
+    assert_eq!(format("print {1: (2};").unwrap_err().kind, FormatErrorKind::Unmatched('}'));
+    assert_eq!(format("print {1: 2;").unwrap_err().kind, FormatErrorKind::Unclosed('{'));
+}
@@ -0,0 +1,100 @@
+// Replays the fuzz seeds and the script corpus through the fuzz targets, then
+// a few thousand deterministic mutations of them, so the no-panic policy is
+// checked on every `cargo test` without libFuzzer.
+
+mod common;
+
+use std::path::Path;
+
+use rlox::fuzz::{compile, interpret, scan_tokens};
+
+use common::Rng;
+
+fn inputs() -> Vec<Vec<u8>> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut paths = Vec::new();
+    for dir in ["fuzz/seeds", "tests/scripts"] {
+        for entry in std::fs::read_dir(root.join(dir)).unwrap() {
+            paths.push(entry.unwrap().path());
+        }
+    }
+    paths.sort();
+    paths.iter().map(|path| std::fs::read(path).unwrap()).collect()
+}
+
+fn run_all(data: &[u8]) {
+    scan_tokens(data);
+    compile(data);
+    interpret(data);
+}
+
+#[test]
+fn seeds() {
+    let inputs = inputs();
+    assert!(!inputs.is_empty());
+    for data in inputs.iter() {
+        run_all(data);
+    }
+}
+
+#[test]
+fn edge_cases() {
+    for data in [
+        &b""[..],
+        b"1.",
+        b"/",
+        b"\"",
+        b"//",
+        b"\xff\xfe",
+        b"\0",
+        b"print",
+        b"var",
+        b"{",
+        b"}",
+        b"a = ",
+        b"1 = 2;",
+    ] {
+        run_all(data);
+    }
+}
+
+const DICTIONARY: &[&[u8]] = &[
+    b"(", b")", b"{", b"}", b"[", b"]", b":", b",", b"${", b"\\", b"\"\"\"", b"r\"", b"\\u{", b"0x", b"0b", b"e-", b"_", b";", b"\"", b"/", b"//", b".", b"=", b"==", b"!",
+    b"-", b"+", b"*", b"%", b"~/", b"**", b"&", b"|", b"^", b"~", b"<<", b">>", b"+=", b"%=", b"++", b"--", b"?", b"??", b"<=", b"var ", b"print ", b"nil", b"true", b"\n", b"1",
+    b"0.5", b"a", "é".as_bytes(),
+];
+
+fn mutate(rng: &mut Rng, data: &mut Vec<u8>, other: &[u8]) {
+    let at = rng.below(data.len() + 1);
+    match rng.below(5) {
+        0 if at < data.len() => data[at] = rng.next() as u8,
+        1 if at < data.len() => {
+            let end = (at + rng.below(8) + 1).min(data.len());
+            data.drain(at..end);
+        }
+        2 => {
+            let word = DICTIONARY[rng.below(DICTIONARY.len())];
+            data.splice(at..at, word.iter().copied());
+        }
+        3 if !other.is_empty() => {
+            let from = rng.below(other.len());
+            let to = (from + rng.below(32) + 1).min(other.len());
+            data.splice(at..at, other[from..to].iter().copied());
+        }
+        _ => data.truncate(at),
+    }
+}
+
+#[test]
+fn mutations() {
+    let inputs = inputs();
+    let mut rng = Rng(0x2545f4914f6cdd1d);
+    for _ in 0..3000 {
+        let mut data = inputs[rng.below(inputs.len())].clone();
+        for _ in 0..rng.below(6) + 1 {
+            let other = &inputs[rng.below(inputs.len())];
+            mutate(&mut rng, &mut data, other);
+        }
+        run_all(&data);
+    }
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use rlox::chunk::{Chunk, OpCode};
+use rlox::debugger::{DebugHook, Debugger, PauseContext, Resume};
+use rlox::vm::{InterpretError, InterruptHandle, Limits, VM};
+
+use common::vm;
+
+fn run(limits: Limits, source: &str) -> Result<(), InterpretError> {
+    let mut vm = VM::new();
+    vm.set_output(Box::new(io::sink()));
+    vm.set_error_output(Box::new(io::sink()));
+    vm.set_limits(limits);
+    vm.interpret(source)
+}
+
+fn nested_sum(depth: usize) -> String {
+    format!("print {}1{};", "1 + (".repeat(depth), ")".repeat(depth))
+}
+
+#[test]
+fn defaults_allow_ordinary_programs() {
+    assert_eq!(run(Limits::default(), &nested_sum(100)), Ok(()));
+}
+
+#[test]
+fn instructions() {
+    let limits = Limits { instructions: Some(10), ..Limits::default() };
+    assert_eq!(run(limits, "print 1 + 2;"), Ok(()));
+    assert_eq!(run(limits, "var a = 1;\n".repeat(10).as_str()), Err(InterpretError::InstructionLimit));
+}
+
+#[test]
+fn stack() {
+    let limits = Limits { stack: 10, ..Limits::default() };
+    assert_eq!(run(limits, &nested_sum(8)), Ok(()));
+    assert_eq!(run(limits, &nested_sum(10)), Err(InterpretError::StackLimit));
+}
+
+#[test]
+fn stack_counts_every_value_an_instruction_pushes() {
+    // OP_DUP2 copies the list and index for `+=`, four values at its peak
+    let source = "var l = [1];\nl[0] += 1;";
+    assert_eq!(run(Limits { stack: 4, ..Limits::default() }, source), Ok(()));
+
+    let (mut vm, _, errors) = vm();
+    vm.set_limits(Limits { stack: 3, ..Limits::default() });
+    assert_eq!(vm.interpret(source), Err(InterpretError::StackLimit));
+    assert_eq!(errors.text(), "Value stack overflow.\n[line 2] in script\n");
+}
+
+#[test]
+fn frames() {
+    let limits = Limits { frames: 0, ..Limits::default() };
+    assert_eq!(run(limits, "print 1;"), Err(InterpretError::FrameLimit));
+}
+
+#[test]
+fn heap() {
+    let limits = Limits { heap: Some(1 << 20), ..Limits::default() };
+    let doubling = format!("var s = \"ab\";\n{}", "s = s + s;\n".repeat(64));
+    assert_eq!(run(limits, &doubling), Err(InterpretError::HeapLimit));
+    assert_eq!(run(limits, "var s = \"ab\" + \"cd\";"), Ok(()));
+}
+
+#[test]
+fn heap_counts_big_ints() {
+    let limits = Limits { heap: Some(1 << 12), ..Limits::default() };
+    let squaring = format!("var n = 3;\n{}", "n = n * n;\n".repeat(24));
+    assert_eq!(run(limits, &squaring), Err(InterpretError::HeapLimit));
+    assert_eq!(run(limits, "var n = 9223372036854775807 * 2;"), Ok(()));
+}
+
+#[test]
+fn vm_is_usable_after_a_limit() {
+    let mut vm = VM::new();
+    vm.set_output(Box::new(io::sink()));
+    vm.set_error_output(Box::new(io::sink()));
+    vm.set_limits(Limits { stack: 4, ..Limits::default() });
+    assert_eq!(vm.interpret(&nested_sum(8)), Err(InterpretError::StackLimit));
+    assert_eq!(vm.interpret("var a = 1 + 2;"), Ok(()));
+    assert!(vm.globals().contains_key("a"));
+}
+
+/// Interrupts the run from another thread the first time it pauses.
+struct InterruptOnPause(InterruptHandle);
+
+impl DebugHook for InterruptOnPause {
+    fn paused(&mut self, _: &mut PauseContext) -> Resume {
+        let handle = self.0.clone();
+        std::thread::spawn(move || handle.interrupt()).join().unwrap();
+        Resume::Continue
+    }
+}
+
+#[test]
+fn interrupt_from_another_thread() {
+    let mut vm = VM::new();
+    vm.set_output(Box::new(io::sink()));
+    vm.set_error_output(Box::new(io::sink()));
+
+    // far more instructions after the interrupt than `POLL_INTERVAL`
+    let counting = format!("var a = 0;\nvar one = 1;\n{}", "a = a + one;\n".repeat(2000));
+    let mut debugger = Debugger::new(Box::new(InterruptOnPause(vm.interrupt_handle())), false);
+    debugger.breakpoints.insert(2);
+    vm.set_debugger(Some(debugger));
+    assert_eq!(vm.interpret(&counting), Err(InterpretError::Interrupted));
+    let a = vm.globals()["a"].as_int().unwrap();
+    assert!((1..2000).contains(&a), "{a}");
+    vm.set_debugger(None);
+
+    // a request made between runs is dropped rather than stopping the next
+    let handle = vm.interrupt_handle();
+    handle.interrupt();
+    assert_eq!(vm.interpret("var b = 1;"), Ok(()));
+    handle.interrupt();
+    let mut chunk = Chunk::new();
+    chunk.write(OpCode::Return.into(), 1);
+    assert_eq!(vm.interpret_chunk(&chunk), Ok(()));
+}
+
+#[test]
+fn deadline() {
+    let mut vm = VM::new();
+    vm.set_output(Box::new(io::sink()));
+    vm.set_error_output(Box::new(io::sink()));
+
+    vm.set_deadline(Some(Instant::now()));
+    assert_eq!(vm.interpret("print 1;"), Err(InterpretError::Interrupted));
+
+    vm.set_deadline(Some(Instant::now() + Duration::from_secs(3600)));
+    assert_eq!(vm.interpret("print 1;"), Ok(()));
+
+    vm.set_deadline(None);
+    assert_eq!(vm.interpret("print 1;"), Ok(()));
+}
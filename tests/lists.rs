@@ -0,0 +1,56 @@
+mod common;
+
+use rlox::vm::InterpretError;
+
+use common::run;
+
+#[test]
+fn literals_and_indexing() {
+    let (result, output, _) = run("var xs = [1, \"a\", [true],];\nxs[2][0] = xs[0] + 1;\nwrite(xs);\nwrite(xs[2]);");
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "[1, a, [2]][2]");
+}
+
+#[test]
+fn operations() {
+    let (result, output, _) = run(
+        "var xs = [];\npush(xs, 1);\npush(xs, 2);\npush(xs, 3);\n\
+         write(len(xs)); write(slice(xs, 1, 3)); write(pop(xs)); write(xs); write(len(\"héllo\"));",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "3[2, 3]3[1, 2]5");
+}
+
+#[test]
+fn lists_are_shared_and_compared_by_identity() {
+    let (result, output, _) = run("var a = [1];\nvar b = a;\npush(b, a);\nwrite(a); write(a == b); write([1] == [1]);");
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "[1, [...]]truefalse");
+}
+
+#[test]
+fn bounds_are_checked() {
+    for (source, message) in [
+        ("[1, 2][2];", "List index 2 is out of bounds for length 2."),
+        ("[1, 2][-1];", "List index -1 is out of bounds for length 2."),
+        ("[1][0.5] = 1;", "List index 0.5 is not an integer."),
+        ("[1][\"0\"];", "List index must be a number."),
+        ("\"abc\"[0];", "Only lists and maps can be indexed."),
+        ("pop([]);", "Can't pop from an empty list."),
+        ("slice([1, 2], 1, 3);", "Slice bound 3 is out of range for length 2."),
+        ("slice([1, 2], 2, 1);", "Slice start 2 is after its end 1."),
+        ("push(1, 2);", "Expected a list but got int."),
+    ] {
+        let (result, _, errors) = run(source);
+        assert_eq!(result, Err(InterpretError::Runtime), "{source}");
+        assert_eq!(errors, format!("{message}\n[line 1] in script\n"), "{source}");
+    }
+}
+
+#[test]
+fn literal_size_is_limited() {
+    let items = vec!["1"; 256].join(", ");
+    let (result, _, errors) = run(&format!("var xs = [{items}];"));
+    assert_eq!(result, Err(InterpretError::Compile));
+    assert!(errors.contains("Can't have more than 255 elements in a list literal."));
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use common::{compile_error, run};
+
+#[test]
+fn reading_a_local_leaves_its_slot_alone() {
+    // OP_GET_LOCAL once wrote over the slot instead of pushing a copy
+    let (result, output, _) = run("{ var a = 1; var b = a; write(a + b); write([a, b]); }");
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "2[1, 1]");
+}
+
+#[test]
+fn locals_shadow_and_assign() {
+    let (result, output, _) = run(
+        "var a = \"global\";\n\
+         {\n\
+           var a = 1;\n\
+           { var c = a + 1; write(c); c = 10; write(c); }\n\
+           write(a);\n\
+           var b = a = 3;\n\
+           write([a, b]);\n\
+         }\n\
+         write(a);",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "2101[3, 3]global");
+}
+
+#[test]
+fn at_most_256_locals() {
+    let declare = |n: usize| (0..n).map(|i| format!("var v{i} = nil;")).collect::<String>();
+    assert_eq!(run(&format!("{{ {} }}", declare(256))).0, Ok(()));
+    assert_eq!(compile_error(&format!("{{ {} }}", declare(257))), "Too many local variables in function.");
+}
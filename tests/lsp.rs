@@ -0,0 +1,208 @@
+// Drives `rlox::lsp::serve` with a scripted client, like tests/dap.rs.
+
+use std::io::{Cursor, Write};
+
+use rlox::json::Json;
+
+fn frame(messages: &[&str]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for body in messages {
+        write!(out, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+    }
+    out
+}
+
+fn unframe(mut bytes: &[u8]) -> Vec<Json> {
+    let mut messages = Vec::new();
+    while !bytes.is_empty() {
+        let text = std::str::from_utf8(bytes).unwrap();
+        let header_end = text.find("\r\n\r\n").unwrap();
+        let length: usize = text[..header_end]
+            .trim_start_matches("Content-Length:")
+            .trim()
+            .parse()
+            .unwrap();
+        let body = &bytes[header_end + 4..header_end + 4 + length];
+        messages.push(Json::parse(std::str::from_utf8(body).unwrap()).unwrap());
+        bytes = &bytes[header_end + 4 + length..];
+    }
+    messages
+}
+
+fn response(messages: &[Json], id: usize) -> &Json {
+    messages
+        .iter()
+        .find(|m| m.get("id").as_usize() == Some(id))
+        .unwrap()
+        .get("result")
+}
+
+fn diagnostics(messages: &[Json]) -> Vec<&Json> {
+    messages
+        .iter()
+        .filter(|m| m.get("method").as_str() == Some("textDocument/publishDiagnostics"))
+        .map(|m| m.get("params").get("diagnostics"))
+        .collect()
+}
+
+const URI: &str = "file:///test.lox";
+
+fn position_request(id: usize, method: &str, line: usize, character: usize) -> String {
+    format!(r#"{{"jsonrpc":"2.0","id":{id},"method":"{method}","params":{{"textDocument":{{"uri":"{URI}"}},"position":{{"line":{line},"character":{character}}}}}}}"#)
+}
+
+#[test]
+fn diagnostics_navigation_and_completion() {
+    let broken = r#"var a = 1;\nprint a +;\n"#;
+    let fixed = r#"var a = 1;\n{\n  var b = a;\n  print b;\n}\nprint a;\n"#;
+
+    let did_open = format!(r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{URI}","languageId":"lox","version":1,"text":"{broken}"}}}}}}"#);
+    let did_change = format!(r#"{{"jsonrpc":"2.0","method":"textDocument/didChange","params":{{"textDocument":{{"uri":"{URI}","version":2}},"contentChanges":[{{"text":"{fixed}"}}]}}}}"#);
+    let symbols = format!(r#"{{"jsonrpc":"2.0","id":6,"method":"textDocument/documentSymbol","params":{{"textDocument":{{"uri":"{URI}"}}}}}}"#);
+    let input = frame(&[
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+        r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#,
+        &did_open,
+        &did_change,
+        // `a` in `var b = a;`
+        &position_request(2, "textDocument/definition", 2, 10),
+        // `b` in `print b;`
+        &position_request(3, "textDocument/definition", 3, 8),
+        &position_request(4, "textDocument/hover", 3, 8),
+        &position_request(5, "textDocument/hover", 5, 6),
+        &symbols,
+        &position_request(7, "textDocument/completion", 5, 0),
+        &symbols.replace(r#""id":6"#, r#""id":9"#).replace("documentSymbol", "semanticTokens/full"),
+        r#"{"jsonrpc":"2.0","id":8,"method":"shutdown"}"#,
+        r#"{"jsonrpc":"2.0","method":"exit"}"#,
+    ]);
+
+    let mut output = Vec::new();
+    let shut_down = rlox::lsp::serve(Cursor::new(input), &mut output).unwrap();
+    assert!(shut_down);
+
+    let messages = unframe(&output);
+
+    let capabilities = response(&messages, 1).get("capabilities");
+    assert_eq!(capabilities.get("textDocumentSync").as_usize(), Some(1));
+    assert_eq!(capabilities.get("definitionProvider").as_bool(), Some(true));
+
+    let published = diagnostics(&messages);
+    assert_eq!(published.len(), 2);
+    assert_eq!(published[0].to_string(),
+               r#"[{"range":{"start":{"line":1,"character":9},"end":{"line":1,"character":10}},"severity":1,"source":"rlox","message":"Error at ';': Expect expression."}]"#);
+    assert_eq!(published[1].to_string(), "[]");
+
+    assert_eq!(response(&messages, 2).get("range").to_string(),
+               r#"{"start":{"line":0,"character":4},"end":{"line":0,"character":5}}"#);
+    assert_eq!(response(&messages, 3).get("range").to_string(),
+               r#"{"start":{"line":2,"character":6},"end":{"line":2,"character":7}}"#);
+
+    let hover = |id| response(&messages, id).get("contents").get("value").as_str().unwrap().to_string();
+    assert!(hover(4).ends_with("local variable, slot 0, declared on line 3"));
+    assert!(hover(5).ends_with("global variable, declared on line 1"));
+
+    let names: Vec<&str> = response(&messages, 6)
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s.get("name").as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["a", "b"]);
+
+    let completions = response(&messages, 7).as_array().unwrap();
+    let label = |label: &str| completions.iter().find(|c| c.get("label").as_str() == Some(label)).unwrap();
+    assert_eq!(label("while").get("kind").as_usize(), Some(14));
+    assert_eq!(label("b").get("kind").as_usize(), Some(6));
+    assert_eq!(label("clock").get("kind").as_usize(), Some(3));
+
+    // `var a = 1;` then `var` on the third line; punctuation isn't highlighted
+    let data: Vec<usize> = response(&messages, 9)
+        .get("data")
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|n| n.as_usize().unwrap())
+        .collect();
+    assert_eq!(data[..25], [0, 0, 3, 0, 0, 0, 4, 1, 1, 0, 0, 2, 1, 4, 0, 0, 2, 1, 3, 0, 2, 2, 3, 0, 0]);
+}
+
+#[test]
+fn positions_count_utf16_code_units() {
+    // 😀 is one char but two UTF-16 code units, so everything after it on
+    // its line is one column further right than its char count
+    let broken = r#"var e = \"😀\" +;\n"#;
+    let fixed = r#"var e = \"😀\"; var f = e;\nprint f;\n"#;
+
+    let did_open = format!(r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{URI}","languageId":"lox","version":1,"text":"{broken}"}}}}}}"#);
+    let did_change = format!(r#"{{"jsonrpc":"2.0","method":"textDocument/didChange","params":{{"textDocument":{{"uri":"{URI}","version":2}},"contentChanges":[{{"text":"{fixed}"}}]}}}}"#);
+    let tokens = format!(r#"{{"jsonrpc":"2.0","id":4,"method":"textDocument/semanticTokens/full","params":{{"textDocument":{{"uri":"{URI}"}}}}}}"#);
+    let input = frame(&[
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+        &did_open,
+        &did_change,
+        // `e` in `var f = e;`
+        &position_request(2, "textDocument/definition", 0, 22),
+        // `f` in `print f;`
+        &position_request(3, "textDocument/definition", 1, 6),
+        &tokens,
+    ]);
+
+    let mut output = Vec::new();
+    rlox::lsp::serve(Cursor::new(input), &mut output).unwrap();
+    let messages = unframe(&output);
+
+    assert_eq!(diagnostics(&messages)[0].as_array().unwrap()[0].get("range").to_string(),
+               r#"{"start":{"line":0,"character":14},"end":{"line":0,"character":15}}"#);
+    assert_eq!(response(&messages, 2).get("range").to_string(),
+               r#"{"start":{"line":0,"character":4},"end":{"line":0,"character":5}}"#);
+    assert_eq!(response(&messages, 3).get("range").to_string(),
+               r#"{"start":{"line":0,"character":18},"end":{"line":0,"character":19}}"#);
+
+    // the string is four units long, and `var` after it starts at 14
+    let data: Vec<usize> = response(&messages, 4)
+        .get("data")
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|n| n.as_usize().unwrap())
+        .collect();
+    assert_eq!(data[..40], [
+        0, 0, 3, 0, 0,
+        0, 4, 1, 1, 0,
+        0, 2, 1, 4, 0,
+        0, 2, 4, 2, 0,
+        0, 6, 3, 0, 0,
+        0, 4, 1, 1, 0,
+        0, 2, 1, 4, 0,
+        0, 2, 1, 1, 0,
+    ]);
+}
+
+#[test]
+fn oversized_messages_are_refused() {
+    let input = b"Content-Length: 18446744073709551615\r\n\r\n{}".to_vec();
+    let error = rlox::lsp::serve(Cursor::new(input), Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(error.to_string().starts_with("Content-Length 18446744073709551615 exceeds the maximum"), "{error}");
+
+    // the same framing serves the debug adapter
+    let input = b"Content-Length: 1000000000\r\n\r\n{}".to_vec();
+    let error = rlox::dap::serve(Cursor::new(input), Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn deeply_nested_messages_are_refused() {
+    let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+    assert!(Json::parse(&nested(512)).is_ok());
+    assert_eq!(Json::parse(&nested(513)), Err("nesting too deep".to_string()));
+
+    // far too deep to recurse through, but well under the length cap
+    let input = frame(&[&"[".repeat(400_000)]);
+    let error = rlox::lsp::serve(Cursor::new(input.clone()), Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(error.to_string(), "nesting too deep");
+    let error = rlox::dap::serve(Cursor::new(input), Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use rlox::vm::InterpretError;
+
+use common::run;
+
+#[test]
+fn literals_and_subscripts() {
+    let (result, output, _) = run(
+        "var m = {\"name\": \"lox\", 1: [true], nil: {},};\n\
+         m[\"name\"] = \"rlox\";\nm[2] = m[1][0];\nwrite(m); write(m[nil]); write(len(m));",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "{name: rlox, 1: [true], nil: {}, 2: true}{}4");
+}
+
+#[test]
+fn keys_follow_value_equality() {
+    let (result, output, _) = run(
+        "var m = {0: \"zero\", \"0\": \"string\", false: \"no\"};\n\
+         m[-0] = \"negative zero\";\nwrite(m[0]); write(m[\"0\"]); write(m[false]); write(len(m));",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "negative zerostringno3");
+}
+
+#[test]
+fn iteration_and_removal() {
+    let (result, output, _) = run(
+        "var m = {\"b\": 1, \"a\": 2, \"c\": 3};\n\
+         write(keys(m)); write(remove(m, \"a\")); write(keys(m)); write(has(m, \"a\")); write(m[\"c\"]);",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "[b, a, c]2[b, c]false3");
+}
+
+#[test]
+fn maps_are_shared_and_compared_by_identity() {
+    let (result, output, _) = run("var a = {};\nvar b = a;\nb[1] = a;\nwrite(a); write(a == b); write({} == {});");
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "{1: {...}}truefalse");
+}
+
+#[test]
+fn unhashable_and_missing_keys_are_errors() {
+    for (source, message) in [
+        ("({})[\"a\"];", "Undefined key \"a\"."),
+        ("({})[1];", "Undefined key 1."),
+        ("({[1]: 2});", "A list can't be used as a map key."),
+        ("({})[{}] = 1;", "A map can't be used as a map key."),
+        ("has({}, 0/0);", "NaN can't be used as a map key."),
+        ("remove({}, true);", "Undefined key true."),
+        ("keys([]);", "Expected a map but got list."),
+    ] {
+        let (result, _, errors) = run(source);
+        assert_eq!(result, Err(InterpretError::Runtime), "{source}");
+        assert_eq!(errors, format!("{message}\n[line 1] in script\n"), "{source}");
+    }
+}
+
+#[test]
+fn braces_at_statement_start_are_blocks() {
+    let (result, output, _) = run("{} { \"a\"; } ({\"a\": 1}); write({\"a\": 1});");
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "{a: 1}");
+
+    // a map there gets one error, and parsing picks up after it
+    for source in [
+        "{\"a\": 1};\nprint 1 +;",
+        "{x: {\"y\": \"${1}\"}, \"z\": 2}\nprint 1 +;",
+        "{ {-1: 2}; }\nprint 1 +;",
+    ] {
+        let (result, _, errors) = run(source);
+        assert_eq!(result, Err(InterpretError::Compile), "{source}");
+        assert_eq!(
+            errors,
+            "[line 1] Error at ':': A statement can't start with a map literal; wrap it in parentheses.\n\
+             [line 2] Error at ';': Expect expression.\n",
+            "{source}"
+        );
+    }
+
+    // a `:` further into a block is just a missing `;`
+    let (_, _, errors) = run("{ print 1; \"a\": 1; }");
+    assert_eq!(errors, "[line 1] Error at ':': Expect ';' after expression.\n");
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use rlox::interpreter::Interpreter;
+use rlox::natives::{Capabilities, Context, Native};
+use rlox::value::Value;
+use rlox::vm::{InterpretError, VM};
+
+use common::SharedBuf;
+
+fn vm(capabilities: Capabilities) -> (VM, SharedBuf, SharedBuf) {
+    let (output, errors) = (SharedBuf::default(), SharedBuf::default());
+    let mut vm = VM::with_capabilities(capabilities);
+    vm.set_output(Box::new(output.clone()));
+    vm.set_error_output(Box::new(errors.clone()));
+    (vm, output, errors)
+}
+
+#[test]
+fn granted_natives_run() {
+    let (mut vm, output, _) = vm(Capabilities::default());
+    assert_eq!(vm.interpret("write(\"a\"); write(1 + 2); print clock() > 0;"), Ok(()));
+    assert_eq!(output.text(), "a3true\n\n");
+}
+
+#[test]
+fn denied_natives_are_runtime_errors() {
+    let (mut vm, output, errors) = vm(Capabilities::NONE);
+    assert_eq!(vm.interpret("var a = 1;\nwrite(a);"), Err(InterpretError::Runtime));
+    assert_eq!(output.text(), "");
+    assert_eq!(errors.text(), "write() needs the stdout capability, which was not granted.\n[line 2] in script\n");
+
+    for source in ["readFile(\"Cargo.toml\");", "writeFile(\"x\", \"y\");", "getEnv(\"HOME\");", "exit(0);"] {
+        assert_eq!(vm.interpret(source), Err(InterpretError::Runtime), "{source}");
+    }
+}
+
+#[test]
+fn filesystem_and_environment() {
+    let (mut vm, output, _) = vm(Capabilities::FS_READ | Capabilities::ENV | Capabilities::STDOUT);
+    let source = format!("write(readFile(\"{}/Cargo.toml\"));", env!("CARGO_MANIFEST_DIR"));
+    assert_eq!(vm.interpret(&source), Ok(()));
+    assert!(output.text().starts_with("[package]"));
+
+    assert_eq!(vm.interpret("print getEnv(\"RLOX_SURELY_UNSET\");"), Ok(()));
+    assert!(output.text().ends_with("nil\n\n"));
+}
+
+#[test]
+fn exit_ends_the_script_not_the_process() {
+    let source = "write(1);\n{ var a = 2; exit(a + 1); }\nwrite(4);";
+    let (mut vm, output, errors) = vm(Capabilities::ALL);
+    assert_eq!(vm.interpret(source), Err(InterpretError::Exit(3)));
+    assert_eq!(output.text(), "1");
+    assert_eq!(errors.text(), "");
+
+    // the VM is left ready for the next script
+    assert_eq!(vm.interpret("write(5);"), Ok(()));
+    assert_eq!(output.text(), "15");
+
+    let output = SharedBuf::default();
+    let mut interpreter = Interpreter::with_capabilities(Capabilities::ALL);
+    interpreter.set_output(Box::new(output.clone()));
+    assert_eq!(interpreter.interpret(source), Err(InterpretError::Exit(3)));
+    assert_eq!(output.text(), "1");
+}
+
+#[test]
+fn call_errors() {
+    let (mut vm, _, errors) = vm(Capabilities::ALL);
+    assert_eq!(vm.interpret("clock(1);"), Err(InterpretError::Runtime));
+    assert_eq!(vm.interpret("var a = 1;\na();"), Err(InterpretError::Runtime));
+    assert_eq!(vm.interpret("readFile(1);"), Err(InterpretError::Runtime));
+    assert_eq!(
+        errors.text(),
+        "Expected 0 arguments but got 1.\n[line 1] in script\n\
+         Can only call functions and classes.\n[line 2] in script\n\
+         Path must be a string.\n[line 1] in script\n"
+    );
+}
+
+#[test]
+fn embedder_natives() {
+    fn twice(_: &mut Context, args: &[Value]) -> Result<Value, String> {
+        let n = args[0].as_number().ok_or("Expected a number.")?;
+        Ok(Value::number(n * 2.0))
+    }
+
+    let (mut vm, output, _) = vm(Capabilities::NONE);
+    vm.define_native(Native::new("twice", 1, Capabilities::NONE, twice));
+    vm.define_native(Native::new("secret", 0, Capabilities::ENV, twice));
+    assert_eq!(vm.interpret("print twice(twice(3));"), Ok(()));
+    assert_eq!(output.text(), "12.0\n\n");
+    assert_eq!(vm.interpret("secret();"), Err(InterpretError::Runtime));
+}
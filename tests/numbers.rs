@@ -0,0 +1,222 @@
+mod common;
+
+use rlox::scanner::{number_value, tokenize, NumberLiteral};
+use rlox::token::TokenType;
+use rlox::value::BigInt;
+use rlox::vm::InterpretError;
+
+use common::{compile_error, run, Rng};
+
+#[test]
+fn literal_values() {
+    use NumberLiteral::{Big, Float, Int};
+    for (lexeme, value) in [
+        ("0", Int(0)),
+        ("42", Int(42)),
+        ("3.25", Float(3.25)),
+        ("1.0", Float(1.0)),
+        ("0xFF", Int(255)),
+        ("0Xff", Int(255)),
+        ("0b1010", Int(10)),
+        ("0o17", Int(15)),
+        ("0xFFFF_FFFF_FFFF_FFFF", Big(BigInt::parse("18446744073709551615", 10))),
+        ("1e-9", Float(1e-9)),
+        ("2.5E3", Float(2500.0)),
+        ("1e+2", Float(100.0)),
+        ("1_000_000", Int(1_000_000)),
+        ("9223372036854775807", Int(i64::MAX)),
+        ("9223372036854775808", Big(BigInt::parse("9223372036854775808", 10))),
+        ("0xFF_FF", Int(65535)),
+        ("1_0.2_5", Float(10.25)),
+    ] {
+        let tokens = tokenize(lexeme, false);
+        assert_eq!(tokens.len(), 1, "{lexeme}");
+        assert_eq!(tokens[0].toke, TokenType::Number, "{lexeme}");
+        assert_eq!(number_value(lexeme), Ok(value), "{lexeme}");
+    }
+}
+
+#[test]
+fn malformed_literals() {
+    for (source, message) in [
+        (".5", "A number can't start with '.'; add a leading '0'."),
+        ("0x", "Expect hexadecimal digits after '0x'."),
+        ("0b", "Expect binary digits after '0b'."),
+        ("0b102", "Invalid digit '2' in binary number."),
+        ("0o8", "Invalid digit '8' in octal number."),
+        ("1e", "Expect digits in exponent."),
+        ("1e-", "Expect digits in exponent."),
+        ("1_", "Underscores in numbers must be between digits."),
+        ("1__0", "Underscores in numbers must be between digits."),
+        ("0x_1", "Underscores in numbers must be between digits."),
+        ("1_.5", "Underscores in numbers must be between digits."),
+        ("12abc", "Unexpected 'a' in number."),
+        ("0xFG", "Unexpected 'G' in number."),
+    ] {
+        let tokens = tokenize(source, false);
+        assert_eq!(tokens.len(), 1, "{source}: the rest of the literal is consumed too");
+        assert_eq!(tokens[0].toke, TokenType::Error, "{source}");
+        assert_eq!(tokens[0].lexeme, message, "{source}");
+    }
+}
+
+#[test]
+fn out_of_range_literals() {
+    assert_eq!(compile_error("print 1e400;"), "Number '1e400' is too large.");
+    assert_eq!(compile_error("print 1.5e308 * 0 + 2e308;"), "Number '2e308' is too large.");
+}
+
+#[test]
+fn radix_literals_past_i64_are_positive_big_ints() {
+    // hex, octal and binary digits are a magnitude, never two's complement,
+    // so a literal with the top bit set doesn't wrap to a negative int
+    let max = "9223372036854775807";
+    let above = NumberLiteral::Big(BigInt::parse("9223372036854775808", 10));
+    for (lexeme, value) in [
+        ("0x7FFF_FFFF_FFFF_FFFF", NumberLiteral::Int(i64::MAX)),
+        ("0x8000_0000_0000_0000", above.clone()),
+        ("0o777_777_777_777_777_777_777", NumberLiteral::Int(i64::MAX)),
+        ("0o1_000_000_000_000_000_000_000", above.clone()),
+        (&format!("0b{}", "1".repeat(63)), NumberLiteral::Int(i64::MAX)),
+        (&format!("0b1{}", "0".repeat(63)), above.clone()),
+    ] {
+        assert_eq!(number_value(lexeme), Ok(value), "{lexeme}");
+    }
+
+    let (result, output, _) = run(&format!(
+        "write([0xFFFF_FFFF_FFFF_FFFF > 0, 0x8000_0000_0000_0000 - 1 == {max}, -0x8000_0000_0000_0000 == -{max} - 1]);"
+    ));
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "[true, true, true]");
+}
+
+#[test]
+fn ints_and_floats_print_differently() {
+    let (result, output, _) = run(
+        "write(1); write(\" \"); write(1.0); write(\" \"); write(7 / 2); write(\" \"); write(6 / 2); write(\" \");\n\
+         write(2 * 3); write(\" \"); write(2 * 3.0); write(\" \"); write(-0.0); write(\" \"); write(1e21);",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "1 1.0 3.5 3.0 6 6.0 -0.0 1e21");
+}
+
+#[test]
+fn floor_division_and_modulo_round_down() {
+    let (result, output, _) = run(
+        "write([7 ~/ 2, -7 ~/ 2, 7 ~/ -2, 7 % 3, -7 % 3, 7 % -3, 7.5 ~/ 2, -7.5 % 2]);",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "[3, -4, -4, 1, 2, -2, 3.0, 0.5]");
+}
+
+#[test]
+fn slash_slash_always_starts_a_comment() {
+    let (result, output, _) = run(
+        "var a = 1; var b = 2;\n\
+         var total = a   // first part\n + b;\n\
+         var n = 7; var half = 2;\n\
+         write([total, n // half\n]);",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "[3, 7]");
+}
+
+#[test]
+fn ints_compare_with_floats_by_value() {
+    let (result, output, _) = run(
+        "write([1 == 1.0, 1 < 1.5, 9007199254740993 > 9007199254740992.0, 9007199254740993 == 9007199254740992.0]);\n\
+         var m = {1: \"int\"};\nm[1.0] = \"float\";\nwrite(m);",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "[true, true, true, false]{1: float}");
+}
+
+#[test]
+fn integer_errors() {
+    for (source, message) in [
+        ("1 ~/ 0;", "Integer division by zero."),
+        ("1 % 0;", "Integer division by zero."),
+        ("1 % \"a\";", "Operands must be numbers."),
+    ] {
+        let (result, _, errors) = run(source);
+        assert_eq!(result, Err(InterpretError::Runtime), "{source}");
+        assert_eq!(errors, format!("{message}\n[line 1] in script\n"), "{source}");
+    }
+}
+
+#[test]
+fn ints_promote_to_big_ints() {
+    let (result, output, _) = run(
+        "write([9223372036854775807 + 1, -(-9223372036854775807 - 1), (-9223372036854775807 - 1) ~/ -1]);\n\
+         var f = 1 * 2 * 3 * 4 * 5 * 6 * 7 * 8 * 9 * 10 * 11 * 12 * 13 * 14 * 15 * 16 * 17 * 18 * 19 * 20 * 21 * 22 * 23 * 24 * 25 * 26 * 27 * 28 * 29 * 30;\nwrite(f);\n\
+         write([f ~/ 1000000007, f % 1000000007, -f ~/ 7, -f % 7, f ~/ -f, f % f]);\n\
+         write((f + 1) - f); write(0x1_0000_0000_0000_0000 * 0x1_0000_0000_0000_0000);",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(
+        output,
+        "[9223372036854775808, 9223372036854775808, 9223372036854775808]\
+         265252859812191058636308480000000\
+         [265252857955421052948361, 109361473, -37893265687455865519472640000000, 0, -1, 0]\
+         1340282366920938463463374607431768211456"
+    );
+}
+
+#[test]
+fn big_ints_agree_with_i128() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    let mut operand = || {
+        let n = ((rng.next() as i128) << 64 | rng.next() as i128) >> (rng.next() % 127);
+        (BigInt::from(n), n)
+    };
+
+    for _ in 0..2000 {
+        let ((a, x), (b, y)) = (operand(), operand());
+        if let (Some(sum), Some(product)) = (x.checked_add(y), x.checked_mul(y)) {
+            assert_eq!(&a + &b, BigInt::from(sum), "{x} + {y}");
+            assert_eq!(&a * &b, BigInt::from(product), "{x} * {y}");
+        }
+        if let Some(difference) = x.checked_sub(y) {
+            assert_eq!(&a - &b, BigInt::from(difference), "{x} - {y}");
+        }
+        assert_eq!(a.cmp(&b), x.cmp(&y), "{x} <=> {y}");
+        assert_eq!(a.to_string(), x.to_string());
+        if y != 0 && !(x == i128::MIN && y == -1) {
+            let q = x / y - (x % y != 0 && (x < 0) != (y < 0)) as i128;
+            assert_eq!(a.div_mod_floor(&b), (BigInt::from(q), BigInt::from(x - q * y)), "{x} ~/ {y}");
+        }
+    }
+}
+
+#[test]
+fn exponent_and_bitwise_operators() {
+    let (result, output, _) = run(
+        "write([-2 ** 2, 2 ** 3 ** 2, 2 ** -1, 2.0 ** 0.5, (-1) ** 12345678901234567891, 2 ** 70]);\n\
+         write([1 | 2 ^ 3 & 6, -1 & 0xFF, ~0, ~(2 ** 70), 1 + 2 << 3 == 24, 1 << 70, -5 >> 1, -5 >> 1000]);\n\
+         write([(2 ** 70 - 1) ^ -1, -(2 ** 70) | 1, (2 ** 70 + 5) & -(2 ** 64)]);",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(
+        output,
+        "[-4, 512, 0.5, 1.4142135623730951, -1, 1180591620717411303424]\
+         [1, 255, -1, -1180591620717411303425, true, 1180591620717411303424, -3, -1]\
+         [-1180591620717411303424, -1180591620717411303423, 1180591620717411303424]"
+    );
+}
+
+#[test]
+fn bitwise_operators_need_ints() {
+    for (source, message) in [
+        ("1 & 1.0;", "Operands must be integers."),
+        ("1 << \"a\";", "Operands must be integers."),
+        ("~1.5;", "Operand must be an integer."),
+        ("1 >> -1;", "Negative shift count."),
+        ("2 ** 12345678901;", "Integer result is too large."),
+        ("1 << 123456789012;", "Integer result is too large."),
+        ("\"a\" ** 2;", "Operands must be numbers."),
+    ] {
+        let (result, _, errors) = run(source);
+        assert_eq!(result, Err(InterpretError::Runtime), "{source}");
+        assert_eq!(errors, format!("{message}\n[line 1] in script\n"), "{source}");
+    }
+}
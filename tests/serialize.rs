@@ -0,0 +1,121 @@
+// `.loxc` files are read from untrusted input, so every malformed case must
+// come back as an error rather than a panic or a bad chunk.
+
+mod common;
+
+use rlox::chunk::{Chunk, OpCode};
+use rlox::serialize::{read_chunk, write_chunk, SerializeError};
+use rlox::value::Value;
+
+use common::{compile, vm};
+
+const SOURCE: &str = "var s = \"text\";\n\
+                      print [1.5, -7, 0x1_0000_0000_0000_0000, -(2 ** 80), s];\n\
+                      { var a = s ?? 1; print a; }";
+
+/// A hand-built `.loxc` file with `constants` already encoded, and `code` all
+/// on line 1.
+fn file(version: u8, constants: &[&[u8]], code: &[u8]) -> Vec<u8> {
+    let mut bytes = b"LOXC".to_vec();
+    bytes.push(version);
+    bytes.extend_from_slice(&(constants.len() as u32).to_le_bytes());
+    for constant in constants {
+        bytes.extend_from_slice(constant);
+    }
+    bytes.extend_from_slice(&(code.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(code);
+    for _ in code {
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+    }
+    bytes
+}
+
+/// A length-prefixed constant with `tag`.
+fn text(tag: u8, s: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![tag];
+    bytes.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(s);
+    bytes
+}
+
+const RETURN: &[u8] = &[OpCode::Return as u8];
+
+fn constants(chunk: &Chunk) -> Vec<String> {
+    (0..chunk.constant_count())
+        .map(|i| format!("{:?}", chunk.read_constant(i)))
+        .collect()
+}
+
+#[test]
+fn round_trip() {
+    let chunk = compile(SOURCE);
+    let bytes = write_chunk(&chunk).unwrap();
+    let loaded = read_chunk(&bytes).unwrap();
+
+    assert_eq!(loaded.code(), chunk.code());
+    assert_eq!(constants(&loaded), constants(&chunk));
+    for offset in 0..chunk.code().len() {
+        assert_eq!(loaded.get_line(offset), chunk.get_line(offset));
+    }
+    assert_eq!(write_chunk(&loaded).unwrap(), bytes);
+
+    let (mut vm, output, _) = vm();
+    assert_eq!(vm.interpret_chunk(&loaded), Ok(()));
+    assert_eq!(output.text(), "[1.5, -7, 18446744073709551616, -1208925819614629174706176, text]\n\ntext\n\n");
+}
+
+#[test]
+fn every_constant_kind() {
+    let mut chunk = Chunk::new();
+    for value in [Value::number(-0.25), Value::int(i64::MIN), Value::string("é"), Value::number(f64::INFINITY)] {
+        chunk.write_constant(value);
+    }
+    chunk.write(OpCode::Return.into(), 1);
+
+    let loaded = read_chunk(&write_chunk(&chunk).unwrap()).unwrap();
+    assert_eq!(constants(&loaded), ["Number(-0.25)", "Int(-9223372036854775808)", "Str(\"é\")", "Number(inf)"]);
+}
+
+#[test]
+fn only_numbers_and_strings_are_written() {
+    let mut chunk = Chunk::new();
+    chunk.write_constant(Value::nil());
+    assert_eq!(write_chunk(&chunk).unwrap_err(), SerializeError::UnsupportedConstant("nil"));
+}
+
+#[test]
+fn rejects_malformed_files() {
+    use SerializeError::*;
+    let int = [&[2u8][..], &5i64.to_le_bytes()].concat();
+    let mut trailing = file(1, &[], RETURN);
+    trailing.extend_from_slice(b"xyz");
+    let mut truncated = file(1, &[&int], RETURN);
+    truncated.truncate(truncated.len() - 1);
+
+    for (bytes, error) in [
+        (b"LOX".to_vec(), BadMagic),
+        (b"ELF\x7f\x01".to_vec(), BadMagic),
+        (file(2, &[], RETURN), UnsupportedVersion(2)),
+        (truncated, UnexpectedEof),
+        (b"LOXC\x01\xff\xff\xff\xff".to_vec(), UnexpectedEof),
+        (trailing, TrailingBytes(3)),
+        (file(1, &[&[9]], RETURN), UnknownConstantTag(9)),
+        (file(1, &[&text(1, b"\xff")], RETURN), InvalidUtf8),
+        (file(1, &[&text(3, b"")], RETURN), InvalidBigInt),
+        (file(1, &[&text(3, b"-")], RETURN), InvalidBigInt),
+        (file(1, &[&text(3, b"12a")], RETURN), InvalidBigInt),
+        (file(1, &[&text(3, b"+1")], RETURN), InvalidBigInt),
+        (file(1, &[&text(1, b"a"), &int, &text(1, b"a")], RETURN), DuplicateConstant(2)),
+    ] {
+        assert_eq!(read_chunk(&bytes).err(), Some(error), "{bytes:?}");
+    }
+}
+
+#[test]
+fn accepts_well_formed_files() {
+    let big = text(3, b"-123456789012345678901234567890");
+    let chunk = read_chunk(&file(1, &[&big], &[OpCode::Constant as u8, 0, OpCode::Print as u8, OpCode::Return as u8])).unwrap();
+    let (mut vm, output, _) = vm();
+    assert_eq!(vm.interpret_chunk(&chunk), Ok(()));
+    assert_eq!(output.text(), "-123456789012345678901234567890\n\n");
+}
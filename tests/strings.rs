@@ -0,0 +1,110 @@
+mod common;
+
+use rlox::scanner::tokenize;
+use rlox::token::TokenType;
+use rlox::vm::InterpretError;
+
+use common::run;
+
+#[test]
+fn interpolation() {
+    let (result, output, _) = run(
+        "var name = \"lox\";\nvar n = 2;\n\
+         write(\"Hello ${name}, you have ${n + 1} items\");\n\
+         write(\"|${n}|${nil}${true}|${[1, \"a\"]}|${\"\"}|\");",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "Hello lox, you have 3 items|2|niltrue|[1, a]||");
+}
+
+#[test]
+fn interpolation_nests() {
+    let (result, output, _) = run(
+        "var m = {\"k\": \"v\"};\n\
+         write(\"${m[\"k\"]} ${ {\"a\": \"${m[\"k\"]}!\"}[\"a\"] } ${\"in ${\"deeper\"}\"}\");",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "v v! in deeper");
+}
+
+#[test]
+fn interpolation_tokens() {
+    let tokens: Vec<(TokenType, String)> = tokenize("\"a ${b} c ${ {} } d\"", false)
+        .into_iter()
+        .map(|t| (t.toke, t.lexeme))
+        .collect();
+    let expected = [
+        (TokenType::Interpolation, "\"a ${"),
+        (TokenType::Identifier, "b"),
+        (TokenType::Interpolation, "} c ${"),
+        (TokenType::Kel, "{"),
+        (TokenType::Ker, "}"),
+        (TokenType::String, "} d\""),
+    ];
+    assert_eq!(tokens, expected.map(|(t, l)| (t, l.to_string())));
+}
+
+#[test]
+fn interpolation_errors() {
+    let (result, _, errors) = run("print \"a ${1 2} b\";");
+    assert_eq!(result, Err(InterpretError::Compile));
+    assert!(errors.contains("Error at '2': Expect '}' after interpolated expression."), "{errors}");
+
+    let (result, _, errors) = run("print \"a ${1} b;");
+    assert_eq!(result, Err(InterpretError::Compile));
+    assert!(errors.contains("Unterminated string."), "{errors}");
+
+    let (result, _, errors) = run("print \"${-nil}\";");
+    assert_eq!(result, Err(InterpretError::Runtime));
+    assert_eq!(errors, "Operand must be a number.\n[line 1] in script\n");
+}
+
+#[test]
+fn escapes() {
+    let (result, output, _) = run(r#"write("a\tb\n\"c\" \\ \$ \${x} \u{1F600}\u{e9}\0|");"#);
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "a\tb\n\"c\" \\ $ ${x} \u{1F600}\u{e9}\0|");
+}
+
+#[test]
+fn raw_and_triple_quoted() {
+    let (result, output, _) = run(
+        "var x = 1;\nwrite(r\"\\n${x}\\\");\nwrite(\"\"\"\n  \"quoted\" ${x}\n  \\tend\"\"\");\nwrite(r\"\"\"\n\"\\n\" x\"\"\");",
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(output, "\\n${x}\\  \"quoted\" 1\n  \tend\"\\n\" x");
+}
+
+#[test]
+fn lines_are_counted_across_literals() {
+    let lines: Vec<usize> = tokenize("\"\"\"\na\n${\nb}\n\"\"\" c\nd", false)
+        .into_iter()
+        .map(|t| t.line)
+        .collect();
+    assert_eq!(lines, [1, 4, 4, 5, 6]);
+
+    let (result, _, errors) = run("var s = \"\"\"\none\ntwo\"\"\";\nprint -s;");
+    assert_eq!(result, Err(InterpretError::Runtime));
+    assert_eq!(errors, "Operand must be a number.\n[line 4] in script\n");
+}
+
+#[test]
+fn bad_escapes_are_errors() {
+    for (source, message) in [
+        (r#"print "\q";"#, r"Invalid escape sequence '\q'."),
+        (r#"print "\u{110000}";"#, r"Invalid Unicode escape '\u{110000}'."),
+        (r#"print "\u{}";"#, r"Invalid Unicode escape '\u{}'."),
+        (r#"print "\u{0000001}";"#, r"Invalid Unicode escape '\u{0000001}'."),
+        (r#"print "\u41";"#, r"Expect '{' after '\u'."),
+        (r#"print "\u{41";"#, "Expect '}' after Unicode escape digits."),
+        (r#"print """\q""";"#, r"Invalid escape sequence '\q'."),
+    ] {
+        let (result, _, errors) = run(source);
+        assert_eq!(result, Err(InterpretError::Compile), "{source}");
+        assert_eq!(errors, format!("[line 1] Error: {message}\n"), "{source}");
+    }
+
+    // scanning carries on after the literal
+    let (_, _, errors) = run("print \"\\q\";\nprint \"\\w\";");
+    assert_eq!(errors, "[line 1] Error: Invalid escape sequence '\\q'.\n[line 2] Error: Invalid escape sequence '\\w'.\n");
+}
@@ -0,0 +1,145 @@
+// Every check `verifier::verify` makes, since the VM reads verified code
+// without bounds checks.
+
+mod common;
+
+use rlox::chunk::{Chunk, OpCode};
+use rlox::serialize::{read_chunk, write_chunk};
+use rlox::value::Value;
+use rlox::verifier::{verify, VerifyError, VerifyErrorKind};
+use rlox::vm::InterpretError;
+use rlox::STACK_MAX;
+
+use common::{compile, vm};
+use OpCode::*;
+
+/// A chunk of `code`, all on line 1, with `constants`.
+fn chunk(code: &[u8], constants: Vec<Value>) -> Chunk {
+    let mut chunk = Chunk::new();
+    for constant in constants {
+        chunk.write_constant(constant);
+    }
+    for &byte in code {
+        chunk.write(byte, 1);
+    }
+    chunk
+}
+
+fn error(code: &[u8], constants: Vec<Value>) -> VerifyError {
+    verify(&chunk(code, constants)).unwrap_err()
+}
+
+fn kind(code: &[u8]) -> VerifyErrorKind {
+    error(code, Vec::new()).kind
+}
+
+#[test]
+fn well_formed_code_passes() {
+    for source in ["", "print 1 + 2;", "var a = 1; { var b = a; print b ?? a; }", "print [1][0] > 0 ? \"y\" : \"n\";"] {
+        assert_eq!(verify(&compile(source)), Ok(()), "{source}");
+    }
+}
+
+#[test]
+fn empty_chunk() {
+    assert_eq!(kind(&[]), VerifyErrorKind::EmptyChunk);
+}
+
+#[test]
+fn unknown_opcode() {
+    let e = error(&[Nil.into(), 200, Return.into()], Vec::new());
+    assert_eq!((e.offset, e.kind), (1, VerifyErrorKind::UnknownOpcode(200)));
+}
+
+#[test]
+fn truncated_operand() {
+    assert_eq!(kind(&[Constant.into()]), VerifyErrorKind::TruncatedOperand(Constant));
+    assert_eq!(kind(&[Jump.into(), 0]), VerifyErrorKind::TruncatedOperand(Jump));
+}
+
+#[test]
+fn constant_out_of_range() {
+    let e = error(&[Constant.into(), 1, Pop.into(), Return.into()], vec![Value::int(7)]);
+    assert_eq!(e.kind, VerifyErrorKind::ConstantOutOfRange { index: 1, count: 1 });
+}
+
+#[test]
+fn global_name_not_a_string() {
+    let e = error(&[GetGlobal.into(), 0, Pop.into(), Return.into()], vec![Value::number(1.5)]);
+    assert_eq!(e.kind, VerifyErrorKind::NameNotString(0));
+}
+
+#[test]
+fn local_slot_out_of_range() {
+    assert_eq!(
+        kind(&[Nil.into(), GetLocal.into(), 1, Pop.into(), Pop.into(), Return.into()]),
+        VerifyErrorKind::LocalOutOfRange { slot: 1, height: 1 }
+    );
+    assert_eq!(
+        kind(&[Nil.into(), SetLocal.into(), 3, Pop.into(), Return.into()]),
+        VerifyErrorKind::LocalOutOfRange { slot: 3, height: 1 }
+    );
+}
+
+#[test]
+fn stack_underflow() {
+    assert_eq!(kind(&[Pop.into(), Return.into()]), VerifyErrorKind::StackUnderflow { op: Pop, needs: 1, height: 0 });
+    assert_eq!(
+        kind(&[Nil.into(), Add.into(), Return.into()]),
+        VerifyErrorKind::StackUnderflow { op: Add, needs: 2, height: 1 }
+    );
+    assert_eq!(
+        kind(&[Nil.into(), BuildList.into(), 2, Pop.into(), Return.into()]),
+        VerifyErrorKind::StackUnderflow { op: BuildList, needs: 2, height: 1 }
+    );
+}
+
+#[test]
+fn stack_overflow() {
+    let mut code = vec![Nil.into(); STACK_MAX + 1];
+    code.extend(vec![u8::from(Pop); STACK_MAX + 1]);
+    code.push(Return.into());
+    let e = error(&code, Vec::new());
+    assert_eq!((e.offset, e.kind), (STACK_MAX, VerifyErrorKind::StackOverflow(STACK_MAX + 1)));
+}
+
+#[test]
+fn stack_height_mismatch() {
+    // the jump skips the second OP_NIL, so the paths meet at offset 5 with
+    // different heights
+    let e = error(
+        &[Nil.into(), JumpIfFalse.into(), 0, 1, Nil.into(), Pop.into(), Pop.into(), Return.into()],
+        Vec::new(),
+    );
+    assert_eq!((e.offset, e.kind), (5, VerifyErrorKind::StackMismatch { expected: 2, found: 1 }));
+}
+
+#[test]
+fn unbalanced_return() {
+    assert_eq!(kind(&[Nil.into(), Return.into()]), VerifyErrorKind::UnbalancedReturn(1));
+}
+
+#[test]
+fn falls_off_end() {
+    let e = error(&[Nil.into(), Pop.into()], Vec::new());
+    assert_eq!((e.offset, e.kind), (1, VerifyErrorKind::FallsOffEnd));
+}
+
+#[test]
+fn bad_jump_target() {
+    assert_eq!(kind(&[Jump.into(), 0, 5, Return.into()]), VerifyErrorKind::BadJumpTarget(8));
+}
+
+#[test]
+fn malformed_loxc_is_rejected_before_running() {
+    let mut bytes = write_chunk(&compile("print 1;")).unwrap();
+    // point OP_CONSTANT past the end of the constant table
+    let at = bytes.windows(3).rposition(|w| w == [Constant.into(), 0, Print.into()]).unwrap();
+    bytes[at + 1] = 9;
+
+    let chunk = read_chunk(&bytes).unwrap();
+    let (mut vm, output, errors) = vm();
+    assert_eq!(vm.interpret_chunk(&chunk), Err(InterpretError::Verify));
+    assert_eq!(output.text(), "");
+    assert!(errors.text().starts_with("Invalid bytecode [line 1] at offset 0000: constant index 9 out of range"), "{}", errors.text());
+}